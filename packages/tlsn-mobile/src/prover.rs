@@ -575,17 +575,40 @@ fn range_to_verifier_json(r: &tlsn_sdk_core::handler::RangeWithHandler) -> serde
             serde_json::json!({"kind": "HASH", "algorithm": alg})
         }
     };
+    let mut handler = serde_json::json!({
+        "type": handler_type,
+        "part": part,
+        "action": action,
+    });
+    if let Some(params) = &r.handler.params {
+        handler["params"] = params_to_verifier_json(params);
+    }
     serde_json::json!({
         "start": r.start,
         "end": r.end,
-        "handler": {
-            "type": handler_type,
-            "part": part,
-            "action": action,
-        }
+        "handler": handler,
     })
 }
 
+/// Convert handler params to the camelCase shape the extension sends, so the
+/// verifier can decode JSON `path` values. Unset params are omitted.
+fn params_to_verifier_json(p: &tlsn_sdk_core::HandlerParams) -> serde_json::Value {
+    let fields = [
+        ("key", p.key.clone().map(serde_json::Value::from)),
+        ("hideKey", p.hide_key.map(serde_json::Value::from)),
+        ("hideValue", p.hide_value.map(serde_json::Value::from)),
+        ("type", p.content_type.clone().map(serde_json::Value::from)),
+        ("path", p.path.clone().map(serde_json::Value::from)),
+        ("regex", p.regex.clone().map(serde_json::Value::from)),
+        ("flags", p.flags.clone().map(serde_json::Value::from)),
+    ];
+    fields
+        .into_iter()
+        .filter_map(|(name, value)| value.map(|value| (name.to_string(), value)))
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
+
 /// Build the `reveal_config` JSON message for the verifier session WebSocket.
 fn build_reveal_config(
     transcript: &tlsn_sdk_core::Transcript,
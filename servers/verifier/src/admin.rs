@@ -0,0 +1,214 @@
+//! Operator endpoints under `/admin`.
+//!
+//! Only mounted when an `admin` section with a `token` is configured. Every
+//! request must send `Authorization: Bearer <token>`.
+//!
+//! Besides the webhook queue and trust roots, operators can list the sessions
+//! in flight and cancel one, e.g. a session stuck waiting for its prover.
+
+use axum::{
+    extract::{Path as UrlPath, Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Router,
+};
+use eyre::eyre;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::sync::Arc;
+use subtle::ConstantTimeEq;
+use tracing::info;
+
+use crate::webhook::WebhookDelivery;
+use crate::AppState;
+
+/// `admin` section of `config.yaml`.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct AdminConfig {
+    /// Bearer token required on every admin request
+    pub(crate) token: String,
+}
+
+impl AdminConfig {
+    /// Rejects an empty token, which would match an empty bearer string.
+    pub(crate) fn validate(&self) -> Result<(), eyre::ErrReport> {
+        if self.token.is_empty() {
+            return Err(eyre!("admin: `token` must not be empty"));
+        }
+        Ok(())
+    }
+}
+
+/// Webhook queue snapshot returned by `GET /admin/webhooks`
+#[derive(Debug, Serialize)]
+struct WebhookQueueSnapshot {
+    pending: Vec<WebhookDelivery>,
+    dead: Vec<WebhookDelivery>,
+}
+
+/// What the admin API shows of a session in flight, recorded at registration
+#[derive(Debug, Clone, Default, Serialize)]
+pub(crate) struct SessionOverview {
+    /// Unix timestamp (seconds) of the `register` message
+    pub(crate) registered_at: u64,
+    /// Address of the client that registered the session
+    pub(crate) remote_ip: Option<IpAddr>,
+    /// Declared server name, or else the first verified one
+    pub(crate) server_name: Option<String>,
+    pub(crate) max_sent_data: usize,
+    pub(crate) max_recv_data: usize,
+    pub(crate) connections: usize,
+    /// Keys of the session data; the values may be personal and are left out
+    pub(crate) session_data_keys: Vec<String>,
+}
+
+/// A session in flight as listed by `GET /admin/sessions`
+#[derive(Debug, Serialize)]
+pub(crate) struct ActiveSession {
+    pub(crate) id: String,
+    pub(crate) tenant: Option<String>,
+    /// Last phase reported, `registered` before the prover connects
+    pub(crate) phase: &'static str,
+    /// TLS connection the phase is about, for per-connection phases
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) phase_connection: Option<usize>,
+    #[serde(flatten)]
+    pub(crate) overview: SessionOverview,
+}
+
+/// Builds the admin router, or `None` when admin access is not configured.
+pub(crate) fn router(state: &Arc<AppState>) -> Option<Router<Arc<AppState>>> {
+    state.config.admin.as_ref()?;
+
+    Some(
+        Router::new()
+            .route("/admin/webhooks", get(list_webhooks_handler))
+            .route(
+                "/admin/webhooks/dead-letters",
+                get(list_dead_letters_handler),
+            )
+            .route(
+                "/admin/webhooks/dead-letters/{id}/replay",
+                post(replay_dead_letter_handler),
+            )
+            .route("/admin/trust/reload", post(reload_trust_handler))
+            .route("/admin/sessions", get(list_sessions_handler))
+            .route("/admin/sessions/{id}", delete(cancel_session_handler))
+            .route_layer(middleware::from_fn_with_state(state.clone(), require_token)),
+    )
+}
+
+async fn require_token(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(admin) = &state.config.admin else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let authorized = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|token| bool::from(token.as_bytes().ct_eq(admin.token.as_bytes())));
+
+    if !authorized {
+        return (StatusCode::UNAUTHORIZED, "Invalid admin token").into_response();
+    }
+    next.run(request).await
+}
+
+/// Pending and dead-lettered webhook deliveries
+async fn list_webhooks_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    axum::Json(WebhookQueueSnapshot {
+        pending: state.webhooks.pending().await,
+        dead: state.webhooks.dead_letters().await,
+    })
+}
+
+/// Dead-lettered webhook deliveries, oldest first
+async fn list_dead_letters_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    axum::Json(state.webhooks.dead_letters().await)
+}
+
+/// Re-queues a dead-lettered delivery with a fresh attempt budget
+async fn replay_dead_letter_handler(
+    State(state): State<Arc<AppState>>,
+    UrlPath(id): UrlPath<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    state
+        .webhooks
+        .replay(&id)
+        .await
+        .map(|()| StatusCode::ACCEPTED)
+        .map_err(|e| (StatusCode::NOT_FOUND, e.to_string()))
+}
+
+/// Re-reads the trusted CA bundles; the previous roots stay in use on error
+async fn reload_trust_handler(
+    State(state): State<Arc<AppState>>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    state
+        .trust
+        .reload()
+        .map(|()| StatusCode::NO_CONTENT)
+        .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e.to_string()))
+}
+
+/// Sessions in flight, oldest first
+pub(crate) async fn list_sessions_handler(
+    State(state): State<Arc<AppState>>,
+) -> axum::Json<Vec<ActiveSession>> {
+    let mut active: Vec<ActiveSession> = state
+        .sessions
+        .lock()
+        .await
+        .iter()
+        .map(|(id, session)| {
+            let progress = state.resumable.latest_progress(id);
+            ActiveSession {
+                id: id.clone(),
+                tenant: session.tenant.clone(),
+                phase: progress
+                    .as_ref()
+                    .map_or("registered", |event| event.phase.name()),
+                phase_connection: progress.and_then(|event| event.connection),
+                overview: session.overview.clone(),
+            }
+        })
+        .collect();
+    active.sort_by_key(|session| (session.overview.registered_at, session.id.clone()));
+    axum::Json(active)
+}
+
+/// Aborts a session's verifier task. The session fails with `CANCELLED`,
+/// which its client is sent, and is cleaned up like any failed session.
+pub(crate) async fn cancel_session_handler(
+    State(state): State<Arc<AppState>>,
+    UrlPath(id): UrlPath<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let cancel_tx = state
+        .sessions
+        .lock()
+        .await
+        .get_mut(&id)
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("Unknown session {}", id)))?
+        .cancel_tx
+        .take()
+        .ok_or_else(|| {
+            (
+                StatusCode::CONFLICT,
+                format!("Session {} is already being cancelled", id),
+            )
+        })?;
+    // The task has finished if it dropped the receiver
+    cancel_tx
+        .send(())
+        .map_err(|()| (StatusCode::NOT_FOUND, format!("Unknown session {}", id)))?;
+    info!("[{}] Session cancelled through the admin API", id);
+    Ok(StatusCode::ACCEPTED)
+}
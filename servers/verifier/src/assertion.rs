@@ -0,0 +1,313 @@
+//! Declarative assertions over revealed values.
+//!
+//! A policy (see `policy.rs`) can list assertions that the verifier evaluates
+//! after mapping the reveal config to handler results. Each assertion picks
+//! the revealed values of one transcript part, optionally navigates into them
+//! with a JSONPath, and compares the result:
+//!
+//! ```yaml
+//! assertions:
+//!   - name: balance_above_threshold
+//!     type: RECV
+//!     part: BODY
+//!     path: $.accounts[0].balance
+//!     op: gte
+//!     value: 1000
+//! ```
+//!
+//! Only revealed values spanning their whole part are evaluated, so a path is
+//! resolved from the body root rather than from a fragment the prover chose
+//! to reveal, and every such value must satisfy the assertion.
+//!
+//! Assertions never fail a session; every outcome is reported in
+//! `session_completed`, the webhook payload and the attestation.
+//!
+//! Only a JSONPath subset is supported: `$`, `.key`, `['key']` and `[index]`.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::{HandlerAction, HandlerPart, HandlerResult, HandlerType};
+
+/// Comparison applied to the selected value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum AssertionOp {
+    /// The path resolves to a value
+    Exists,
+    Eq,
+    Ne,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    /// The value, as a string, matches the regex in `value`
+    Matches,
+    /// The value equals the session data entry named by `value`
+    EqSession,
+}
+
+/// One assertion from a policy's `assertions` list.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct AssertionConfig {
+    pub(crate) name: String,
+    #[serde(rename = "type")]
+    pub(crate) handler_type: HandlerType,
+    pub(crate) part: HandlerPart,
+    /// JSONPath into the revealed value; the whole value when absent
+    #[serde(default)]
+    pub(crate) path: Option<String>,
+    pub(crate) op: AssertionOp,
+    /// Operand; unused for `exists`
+    #[serde(default)]
+    pub(crate) value: Value,
+}
+
+/// Outcome of one assertion.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct AssertionResult {
+    pub(crate) name: String,
+    pub(crate) passed: bool,
+    /// The value the assertion was evaluated against
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) actual: Option<Value>,
+    /// Why the assertion could not be evaluated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) error: Option<String>,
+}
+
+/// Evaluates every assertion against the handler results.
+///
+/// `whole_parts` holds, for each result, whether its range spans the whole
+/// part it is labelled with.
+pub(crate) fn evaluate(
+    assertions: &[AssertionConfig],
+    results: &[HandlerResult],
+    whole_parts: &[bool],
+    session_data: &HashMap<String, String>,
+) -> Vec<AssertionResult> {
+    assertions
+        .iter()
+        .map(|assertion| assertion.evaluate(results, whole_parts, session_data))
+        .collect()
+}
+
+impl AssertionConfig {
+    /// Checks that the path and operand can be evaluated, so that a broken
+    /// assertion fails at startup rather than in every session.
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if let Some(path) = &self.path {
+            parse_json_path(path)?;
+        }
+        match self.op {
+            AssertionOp::Gt | AssertionOp::Gte | AssertionOp::Lt | AssertionOp::Lte => {
+                as_number(&self.value).ok_or("Operand is not a number")?;
+            }
+            AssertionOp::Matches => {
+                let pattern = self.value.as_str().ok_or("Operand is not a regex string")?;
+                regex::Regex::new(pattern).map_err(|e| format!("Invalid regex: {}", e))?;
+            }
+            AssertionOp::EqSession => {
+                self.value
+                    .as_str()
+                    .ok_or("Operand is not a session data key")?;
+            }
+            AssertionOp::Exists | AssertionOp::Eq | AssertionOp::Ne => {}
+        }
+        Ok(())
+    }
+
+    /// Passes if every revealed value spanning the whole selected part
+    /// satisfies the comparison. Reports the first value that does not, or
+    /// the first value if all do.
+    fn evaluate(
+        &self,
+        results: &[HandlerResult],
+        whole_parts: &[bool],
+        session_data: &HashMap<String, String>,
+    ) -> AssertionResult {
+        let mut outcome = AssertionResult {
+            name: self.name.clone(),
+            passed: false,
+            actual: None,
+            error: Some("No revealed value spans this whole part".to_string()),
+        };
+
+        let candidates = results
+            .iter()
+            .zip(whole_parts)
+            .filter(|(result, &whole_part)| {
+                whole_part
+                    && matches!(result.handler.action, HandlerAction::Reveal)
+                    && result.handler.handler_type == self.handler_type
+                    && result.handler.part == self.part
+            })
+            .map(|(result, _)| result);
+
+        for (i, result) in candidates.enumerate() {
+            let (actual, passed) = match self.select(&result.value) {
+                Ok(actual) => {
+                    let passed = self.compare(actual.as_ref(), session_data);
+                    (actual, passed)
+                }
+                Err(e) => (None, Err(e)),
+            };
+
+            let passed_here = matches!(passed, Ok(true));
+            if i == 0 || !passed_here {
+                outcome.actual = actual;
+                outcome.passed = passed_here;
+                outcome.error = passed.err();
+            }
+            if !outcome.passed {
+                break;
+            }
+        }
+
+        outcome
+    }
+
+    /// Picks the value to compare: the revealed string, or the JSONPath
+    /// target inside it. `Ok(None)` means the path did not resolve.
+    fn select(&self, revealed: &str) -> Result<Option<Value>, String> {
+        let Some(path) = &self.path else {
+            return Ok(Some(Value::String(revealed.to_string())));
+        };
+        let document: Value = serde_json::from_str(revealed)
+            .map_err(|e| format!("Revealed value is not JSON: {}", e))?;
+        Ok(json_path(&document, path)?.cloned())
+    }
+
+    fn compare(
+        &self,
+        actual: Option<&Value>,
+        session_data: &HashMap<String, String>,
+    ) -> Result<bool, String> {
+        if self.op == AssertionOp::Exists {
+            return Ok(actual.is_some());
+        }
+        let actual = actual.ok_or_else(|| "Path not found".to_string())?;
+
+        match self.op {
+            AssertionOp::Exists => unreachable!("handled above"),
+            AssertionOp::Eq => Ok(values_equal(actual, &self.value)),
+            AssertionOp::Ne => Ok(!values_equal(actual, &self.value)),
+            AssertionOp::Gt | AssertionOp::Gte | AssertionOp::Lt | AssertionOp::Lte => {
+                let lhs = as_number(actual).ok_or("Value is not a number")?;
+                let rhs = as_number(&self.value).ok_or("Operand is not a number")?;
+                Ok(match self.op {
+                    AssertionOp::Gt => lhs > rhs,
+                    AssertionOp::Gte => lhs >= rhs,
+                    AssertionOp::Lt => lhs < rhs,
+                    _ => lhs <= rhs,
+                })
+            }
+            AssertionOp::Matches => {
+                let pattern = self.value.as_str().ok_or("Operand is not a regex string")?;
+                let regex =
+                    regex::Regex::new(pattern).map_err(|e| format!("Invalid regex: {}", e))?;
+                Ok(regex.is_match(&as_string(actual)))
+            }
+            AssertionOp::EqSession => {
+                let key = self
+                    .value
+                    .as_str()
+                    .ok_or("Operand is not a session data key")?;
+                let expected = session_data
+                    .get(key)
+                    .ok_or_else(|| format!("Session data has no {}", key))?;
+                Ok(as_string(actual) == *expected)
+            }
+        }
+    }
+}
+
+/// String form of a value: strings unquoted, everything else as JSON.
+fn as_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Numeric value of a JSON number or a numeric string.
+fn as_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// Equality that treats `"1000"` and `1000` as equal.
+fn values_equal(actual: &Value, expected: &Value) -> bool {
+    if actual == expected {
+        return true;
+    }
+    match (as_number(actual), as_number(expected)) {
+        (Some(lhs), Some(rhs)) => lhs == rhs,
+        _ => as_string(actual) == as_string(expected),
+    }
+}
+
+/// One step of a JSONPath: an object key or an array index.
+enum PathStep<'a> {
+    Key(&'a str),
+    Index(usize),
+}
+
+/// Parses a JSONPath subset (`$`, `.key`, `['key']`, `[index]`) into its steps.
+fn parse_json_path(path: &str) -> Result<Vec<PathStep<'_>>, String> {
+    let invalid = || format!("Unsupported JSONPath: {}", path);
+    let mut rest = path.strip_prefix('$').ok_or_else(invalid)?;
+    let mut steps = Vec::new();
+
+    while !rest.is_empty() {
+        if let Some(after_dot) = rest.strip_prefix('.') {
+            let end = after_dot.find(['.', '[']).unwrap_or(after_dot.len());
+            let key = &after_dot[..end];
+            if key.is_empty() {
+                return Err(invalid());
+            }
+            rest = &after_dot[end..];
+            steps.push(PathStep::Key(key));
+        } else if let Some(after_bracket) = rest.strip_prefix('[') {
+            let end = after_bracket.find(']').ok_or_else(invalid)?;
+            let selector = &after_bracket[..end];
+            rest = &after_bracket[end + 1..];
+            steps.push(
+                match selector
+                    .strip_prefix('\'')
+                    .and_then(|s| s.strip_suffix('\''))
+                    .or_else(|| selector.strip_prefix('"').and_then(|s| s.strip_suffix('"')))
+                {
+                    Some(key) => PathStep::Key(key),
+                    None => PathStep::Index(selector.parse().map_err(|_| invalid())?),
+                },
+            );
+        } else {
+            return Err(invalid());
+        }
+    }
+
+    Ok(steps)
+}
+
+/// Resolves a JSONPath subset (`$`, `.key`, `['key']`, `[index]`).
+///
+/// Returns `Ok(None)` when the path is well-formed but does not resolve.
+pub(crate) fn json_path<'a>(document: &'a Value, path: &str) -> Result<Option<&'a Value>, String> {
+    let mut current = document;
+    for step in parse_json_path(path)? {
+        let next = match step {
+            PathStep::Key(key) => current.get(key),
+            PathStep::Index(index) => current.get(index),
+        };
+        match next {
+            Some(value) => current = value,
+            None => return Ok(None),
+        }
+    }
+    Ok(Some(current))
+}
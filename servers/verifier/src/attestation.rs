@@ -0,0 +1,178 @@
+//! Signed attestations over verification results.
+//!
+//! When an `attestation` section is present in `config.yaml` the verifier
+//! holds a signing key and signs every successful verification. The signed
+//! message is the canonical JSON encoding of [`AttestationBody`], carried
+//! base64-encoded in [`Attestation::payload`] so consumers verify the exact
+//! bytes that were signed instead of a re-serialization.
+//!
+//! Signatures:
+//! - `secp256k1`: ECDSA over SHA-256 of the payload, 64-byte `r || s`.
+//! - `ed25519`: plain Ed25519 over the payload.
+//!
+//! Public keys are published at `GET /pubkey`. The [`Attestation`] format and
+//! its offline check, `verify_attestation`, live in the crate's library so
+//! consuming services can reuse them.
+
+use base64::Engine;
+use eyre::eyre;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+
+use crate::assertion::AssertionResult;
+use crate::commitment::CommitmentInfo;
+use crate::{HandlerResult, RangeWithHandler};
+
+pub(crate) use tlsn_verifier_server::attestation::{Attestation, SigningAlgorithm};
+
+/// Version of the [`AttestationBody`] layout. Bumped on breaking changes.
+pub(crate) const ATTESTATION_VERSION: u32 = 1;
+
+/// `attestation` section of `config.yaml`.
+///
+/// The secret key is a hex-encoded 32-byte scalar (secp256k1) or seed
+/// (Ed25519), given either inline as `key` or via `key_file`.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct AttestationConfig {
+    pub(crate) algorithm: SigningAlgorithm,
+    #[serde(default)]
+    pub(crate) key: Option<String>,
+    #[serde(default)]
+    pub(crate) key_file: Option<PathBuf>,
+}
+
+/// Hash commitment digest covered by an attestation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct AttestedHash {
+    /// Index of the TLS connection the range is in
+    #[serde(default)]
+    pub(crate) connection: usize,
+    pub(crate) direction: String,
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+    pub(crate) algorithm: String,
+    pub(crate) digest: String,
+}
+
+/// The attested statement. Serialized to JSON and signed as-is.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct AttestationBody {
+    pub(crate) version: u32,
+    pub(crate) session_id: String,
+    pub(crate) server_name: String,
+    pub(crate) sent: Vec<RangeWithHandler>,
+    pub(crate) recv: Vec<RangeWithHandler>,
+    pub(crate) results: Vec<HandlerResult>,
+    /// Policy assertion outcomes; omitted when the policy has none
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) assertions: Vec<AssertionResult>,
+    pub(crate) hashes: Vec<AttestedHash>,
+    /// Transcript commitments the prover made; omitted when there are none
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) commitments: Vec<CommitmentInfo>,
+    /// Unix timestamp (seconds) at signing time
+    pub(crate) timestamp: u64,
+}
+
+/// Public key description served at `GET /pubkey`.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct PublicKeyInfo {
+    pub(crate) algorithm: SigningAlgorithm,
+    /// First 8 bytes of SHA-256 over the public key, hex-encoded
+    pub(crate) key_id: String,
+    /// Hex-encoded public key (compressed SEC1 for secp256k1, raw for Ed25519)
+    pub(crate) public_key: String,
+}
+
+/// Signing key loaded from [`AttestationConfig`].
+pub(crate) enum Attestor {
+    Secp256k1(k256::ecdsa::SigningKey),
+    Ed25519(ed25519_dalek::SigningKey),
+}
+
+impl Attestor {
+    /// Loads the signing key described by the config.
+    pub(crate) fn from_config(config: &AttestationConfig) -> Result<Self, eyre::ErrReport> {
+        let key_hex = match (&config.key, &config.key_file) {
+            (Some(key), None) => key.clone(),
+            (None, Some(path)) => std::fs::read_to_string(path)
+                .map_err(|e| eyre!("Failed to read attestation key file {:?}: {}", path, e))?,
+            (Some(_), Some(_)) => {
+                return Err(eyre!("attestation: set only one of `key` or `key_file`"))
+            }
+            (None, None) => return Err(eyre!("attestation: `key` or `key_file` is required")),
+        };
+        let secret: [u8; 32] = hex::decode(key_hex.trim())
+            .map_err(|e| eyre!("attestation: key is not valid hex: {}", e))?
+            .try_into()
+            .map_err(|_| eyre!("attestation: key must be 32 bytes"))?;
+
+        Ok(match config.algorithm {
+            SigningAlgorithm::Secp256k1 => Self::Secp256k1(
+                k256::ecdsa::SigningKey::from_bytes(&secret.into())
+                    .map_err(|e| eyre!("attestation: invalid secp256k1 key: {}", e))?,
+            ),
+            SigningAlgorithm::Ed25519 => {
+                Self::Ed25519(ed25519_dalek::SigningKey::from_bytes(&secret))
+            }
+        })
+    }
+
+    pub(crate) fn algorithm(&self) -> SigningAlgorithm {
+        match self {
+            Self::Secp256k1(_) => SigningAlgorithm::Secp256k1,
+            Self::Ed25519(_) => SigningAlgorithm::Ed25519,
+        }
+    }
+
+    fn public_key_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::Secp256k1(key) => key
+                .verifying_key()
+                .to_encoded_point(true)
+                .as_bytes()
+                .to_vec(),
+            Self::Ed25519(key) => key.verifying_key().to_bytes().to_vec(),
+        }
+    }
+
+    pub(crate) fn public_key(&self) -> PublicKeyInfo {
+        let public_key = self.public_key_bytes();
+        PublicKeyInfo {
+            algorithm: self.algorithm(),
+            key_id: key_id(&public_key),
+            public_key: hex::encode(public_key),
+        }
+    }
+
+    /// Signs the body, stamping it with the current time.
+    pub(crate) fn sign(&self, mut body: AttestationBody) -> Attestation {
+        body.version = ATTESTATION_VERSION;
+        body.timestamp = crate::unix_timestamp();
+
+        let payload = serde_json::to_vec(&body).expect("attestation body serializes");
+        let signature = match self {
+            Self::Secp256k1(key) => {
+                use k256::ecdsa::signature::Signer;
+                let signature: k256::ecdsa::Signature = key.sign(&payload);
+                signature.to_bytes().to_vec()
+            }
+            Self::Ed25519(key) => {
+                use ed25519_dalek::Signer;
+                key.sign(&payload).to_bytes().to_vec()
+            }
+        };
+
+        Attestation {
+            algorithm: self.algorithm(),
+            key_id: key_id(&self.public_key_bytes()),
+            payload: base64::engine::general_purpose::STANDARD.encode(&payload),
+            signature: hex::encode(signature),
+        }
+    }
+}
+
+fn key_id(public_key: &[u8]) -> String {
+    hex::encode(&Sha256::digest(public_key)[..8])
+}
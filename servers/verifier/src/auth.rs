@@ -0,0 +1,143 @@
+//! API-key authentication for `/session` and the stored session endpoints,
+//! and per-tenant quotas on `/session`.
+//!
+//! Only enforced when an `auth` section is configured. Each API key belongs to
+//! a tenant; clients send it in an `X-API-Key` header, an
+//! `Authorization: Bearer <key>` header or an `apiKey` query parameter (for
+//! browser WebSockets, which cannot set headers).
+
+use axum::http::{header, HeaderMap};
+use eyre::eyre;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use subtle::ConstantTimeEq;
+
+/// Header carrying the API key
+pub(crate) const API_KEY_HEADER: &str = "x-api-key";
+
+/// Window over which `sessions_per_minute` is counted
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// `auth` section of `config.yaml`.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct AuthConfig {
+    /// Keys accepted on `/session` and `/sessions`
+    pub(crate) api_keys: Vec<ApiKeyConfig>,
+    /// Quotas keyed by tenant name; tenants without an entry are unlimited
+    #[serde(default)]
+    pub(crate) tenants: HashMap<String, TenantQuota>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct ApiKeyConfig {
+    pub(crate) key: String,
+    pub(crate) tenant: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub(crate) struct TenantQuota {
+    /// Sessions the tenant may hold at once
+    #[serde(default)]
+    pub(crate) max_concurrent_sessions: Option<usize>,
+    /// Registrations the tenant may make per rolling minute
+    #[serde(default)]
+    pub(crate) sessions_per_minute: Option<usize>,
+}
+
+impl AuthConfig {
+    /// Rejects key and quota definitions the server cannot run with.
+    pub(crate) fn validate(&self) -> Result<(), eyre::ErrReport> {
+        if self.api_keys.is_empty() {
+            return Err(eyre!("auth: at least one entry in `api_keys` is required"));
+        }
+        let mut keys = HashSet::new();
+        for api_key in &self.api_keys {
+            if api_key.key.is_empty() || api_key.tenant.is_empty() {
+                return Err(eyre!("auth: every api key needs a `key` and a `tenant`"));
+            }
+            if !keys.insert(api_key.key.as_str()) {
+                return Err(eyre!(
+                    "auth: duplicate api key for tenant `{}`",
+                    api_key.tenant
+                ));
+            }
+        }
+        for (tenant, quota) in &self.tenants {
+            if !self
+                .api_keys
+                .iter()
+                .any(|api_key| &api_key.tenant == tenant)
+            {
+                return Err(eyre!("auth: quota for unknown tenant `{}`", tenant));
+            }
+            if quota.max_concurrent_sessions == Some(0) || quota.sessions_per_minute == Some(0) {
+                return Err(eyre!(
+                    "auth: quotas of tenant `{}` must be greater than zero",
+                    tenant
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Tenant owning `key`, if it is a configured API key.
+    ///
+    /// Every configured key is compared in constant time, so the response
+    /// time does not reveal which (or whether a) key matched.
+    pub(crate) fn authenticate(&self, key: &str) -> Option<&str> {
+        let mut tenant = None;
+        for api_key in &self.api_keys {
+            if bool::from(key.as_bytes().ct_eq(api_key.key.as_bytes())) {
+                tenant = Some(api_key.tenant.as_str());
+            }
+        }
+        tenant
+    }
+
+    pub(crate) fn quota(&self, tenant: &str) -> Option<&TenantQuota> {
+        self.tenants.get(tenant)
+    }
+}
+
+/// API key sent with a request, from the headers or the `apiKey` query param.
+pub(crate) fn request_api_key<'a>(
+    headers: &'a HeaderMap,
+    query_key: Option<&'a str>,
+) -> Option<&'a str> {
+    let header_key = headers
+        .get(API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok());
+    let bearer_key = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    header_key.or(bearer_key).or(query_key)
+}
+
+/// Rolling one-minute registration counts per tenant.
+#[derive(Debug, Default)]
+pub(crate) struct SessionRateLimiter {
+    windows: Mutex<HashMap<String, VecDeque<Instant>>>,
+}
+
+impl SessionRateLimiter {
+    /// Records a registration for `tenant` at `now` unless it already made
+    /// `per_minute` registrations in the preceding minute.
+    pub(crate) fn try_acquire(&self, tenant: &str, per_minute: usize, now: Instant) -> bool {
+        let mut windows = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+        let window = windows.entry(tenant.to_string()).or_default();
+        while window
+            .front()
+            .is_some_and(|&at| now.duration_since(at) >= RATE_WINDOW)
+        {
+            window.pop_front();
+        }
+        if window.len() >= per_minute {
+            return false;
+        }
+        window.push_back(now);
+        true
+    }
+}
@@ -0,0 +1,132 @@
+//! Transcript commitments made by the prover.
+//!
+//! Besides revealing plaintext, a prover can commit to parts of its
+//! transcript without revealing them: a plaintext hash binds one range to a
+//! digest, and an encoding commitment binds the ranges the prover chose to a
+//! Merkle root that it can later open selectively to a third party. Every
+//! commitment a session receives is listed in its result, webhook payload
+//! and attestation.
+//!
+//! An encoding commitment holds only its root. The ranges it covers are taken
+//! from the prover's commit request ([`EncodedRanges`]), which the verifier
+//! computed the commitment over.
+
+use rangeset::prelude::RangeSet;
+use serde::{Deserialize, Serialize};
+use tlsn::transcript::{Direction, TranscriptCommitRequest, TranscriptCommitment};
+
+/// Kind of a transcript commitment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub(crate) enum CommitmentKind {
+    /// Hash of the plaintext of one range
+    Hash,
+    /// Merkle root over the encodings of the committed ranges
+    Encoding,
+}
+
+/// A transcript commitment, as listed with a session result
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct CommitmentInfo {
+    pub(crate) kind: CommitmentKind,
+    /// Index of the TLS connection whose transcript is committed to
+    #[serde(default)]
+    pub(crate) connection: usize,
+    /// `sent` or `recv`; absent when the commitment does not name one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) direction: Option<String>,
+    /// Committed `[start, end)` ranges; empty when the commitment does not
+    /// name them, as for encoding commitments
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) ranges: Vec<[usize; 2]>,
+    /// Hash algorithm of `value`, e.g. `SHA256`
+    pub(crate) algorithm: String,
+    /// Hex-encoded digest or Merkle root
+    pub(crate) value: String,
+}
+
+impl CommitmentInfo {
+    /// Describes a commitment to the transcript of TLS connection
+    /// `connection`, or returns `None` for a kind this server does not know.
+    pub(crate) fn new(commitment: &TranscriptCommitment, connection: usize) -> Option<Self> {
+        match commitment {
+            TranscriptCommitment::Hash(hash) => Some(Self {
+                kind: CommitmentKind::Hash,
+                connection,
+                direction: Some(direction_label(hash.direction).to_string()),
+                ranges: hash
+                    .idx
+                    .iter_ranges()
+                    .map(|range| [range.start, range.end])
+                    .collect(),
+                algorithm: hash.hash.alg.to_string().to_uppercase(),
+                value: hex::encode(hash.hash.value.as_bytes()),
+            }),
+            TranscriptCommitment::Encoding(encoding) => Some(Self {
+                kind: CommitmentKind::Encoding,
+                connection,
+                direction: None,
+                ranges: vec![],
+                algorithm: encoding.root.alg.to_string().to_uppercase(),
+                value: hex::encode(encoding.root.value.as_bytes()),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Transcript ranges covered by the prover's encoding commitment.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct EncodedRanges {
+    pub(crate) sent: RangeSet<usize>,
+    pub(crate) recv: RangeSet<usize>,
+}
+
+impl EncodedRanges {
+    /// Collects the encoding ranges named in the prover's commit request.
+    pub(crate) fn from_request(request: &TranscriptCommitRequest) -> Self {
+        let mut ranges = Self::default();
+        for (direction, idx) in request.encoding().into_iter().flatten() {
+            match direction {
+                Direction::Sent => ranges.sent.union_mut(idx),
+                Direction::Received => ranges.recv.union_mut(idx),
+            }
+        }
+        ranges
+    }
+}
+
+/// Whether the prover made an encoding commitment.
+pub(crate) fn has_encoding(commitments: &[TranscriptCommitment]) -> bool {
+    commitments
+        .iter()
+        .any(|commitment| matches!(commitment, TranscriptCommitment::Encoding(_)))
+}
+
+/// Algorithm of every plaintext hash commitment, e.g. `SHA256`.
+pub(crate) fn hash_algorithms(commitments: &[TranscriptCommitment]) -> Vec<String> {
+    commitments
+        .iter()
+        .filter_map(|commitment| match commitment {
+            TranscriptCommitment::Hash(hash) => Some(hash.hash.alg.to_string().to_uppercase()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Hex-encoded Merkle root of the prover's encoding commitment, if it made one.
+pub(crate) fn encoding_root(commitments: &[TranscriptCommitment]) -> Option<String> {
+    commitments.iter().find_map(|commitment| match commitment {
+        TranscriptCommitment::Encoding(encoding) => {
+            Some(hex::encode(encoding.root.value.as_bytes()))
+        }
+        _ => None,
+    })
+}
+
+fn direction_label(direction: Direction) -> &'static str {
+    match direction {
+        Direction::Sent => "sent",
+        Direction::Received => "recv",
+    }
+}
@@ -0,0 +1,227 @@
+//! Outbound TCP connections to the servers provers talk to.
+//!
+//! The `/proxy` bridge and the verifier in Proxy mode both reach servers
+//! through the [`Connector`]. It applies the target rules of the `proxy`
+//! section (see `proxy_guard.rs`) and its `upstreams` overrides, resolves
+//! names through a short-lived cache, dials within `connect_timeout_secs` and
+//! records every attempt in the metrics. Only addresses that passed the
+//! checks are dialled, so a cached or fresh lookup cannot smuggle in a
+//! blocked address. When an `egress` proxy is configured (see `egress.rs`),
+//! the connections are tunnelled through it.
+
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+use tracing::info;
+
+use crate::egress::EgressConfig;
+use crate::match_server_name;
+use crate::metrics::Metrics;
+use crate::proxy_guard::{parse_target, ProxyConfig, ProxyRejection};
+
+/// Cached names before expired lookups are pruned
+const MAX_CACHED_LOOKUPS: usize = 1024;
+
+/// Which path an outbound connection serves, for the metrics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Route {
+    /// The `/proxy` WebSocket-to-TCP bridge
+    Bridge,
+    /// The verifier dialling the server in Proxy mode
+    Verifier,
+}
+
+impl Route {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Bridge => "proxy",
+            Self::Verifier => "verifier",
+        }
+    }
+}
+
+struct CachedLookup {
+    addrs: Vec<SocketAddr>,
+    expires: Instant,
+}
+
+/// Checks, resolves and dials upstream targets.
+pub(crate) struct Connector {
+    config: ProxyConfig,
+    egress: Option<EgressConfig>,
+    metrics: Arc<Metrics>,
+    lookups: Mutex<HashMap<(String, u16), CachedLookup>>,
+}
+
+impl Connector {
+    pub(crate) fn new(
+        config: ProxyConfig,
+        egress: Option<EgressConfig>,
+        metrics: Arc<Metrics>,
+    ) -> Self {
+        Self {
+            config,
+            egress,
+            metrics,
+            lookups: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Connects to `host:port` once the target rules allow it. A configured
+    /// upstream is dialled instead, without the address checks, as the
+    /// operator chose it.
+    pub(crate) async fn connect(
+        &self,
+        route: Route,
+        host: &str,
+        port: u16,
+    ) -> Result<TcpStream, ProxyRejection> {
+        let started = Instant::now();
+        let result = self.try_connect(host, port).await;
+        let outcome = match &result {
+            Ok(_) => "connected",
+            Err(rejection) => rejection.reason(),
+        };
+        self.metrics
+            .upstream_connect(route.label(), outcome, started.elapsed());
+        result
+    }
+
+    async fn try_connect(&self, host: &str, port: u16) -> Result<TcpStream, ProxyRejection> {
+        self.config.check_target(host, port)?;
+        let upstream = match_server_name(&self.config.upstreams, host)
+            .map(|upstream| parse_target(upstream))
+            .transpose()?;
+        let (dial_host, dial_port) = upstream.clone().unwrap_or((host.to_string(), port));
+        let egress = self
+            .egress
+            .as_ref()
+            .filter(|egress| egress.applies_to(&dial_host));
+
+        // With remote DNS the egress proxy resolves names, so there are no
+        // addresses to check here and the name alone has to pass
+        let addrs = if egress.is_some_and(|egress| egress.remote_dns)
+            && dial_host.parse::<IpAddr>().is_err()
+        {
+            if upstream.is_none() {
+                self.config.check_name(host)?;
+            }
+            None
+        } else {
+            let addrs = self.resolve(&dial_host, dial_port).await?;
+            match upstream {
+                Some(_) => Some(addrs),
+                None => Some(self.config.check_addrs(host, &addrs)?),
+            }
+        };
+
+        match (&addrs, egress) {
+            (Some(addrs), None) => info!("[Proxy] Connecting to {}:{} via {:?}", host, port, addrs),
+            (Some(addrs), Some(_)) => info!(
+                "[Proxy] Connecting to {}:{} via {:?} through the egress proxy",
+                host, port, addrs
+            ),
+            (None, _) => info!(
+                "[Proxy] Connecting to {}:{} through the egress proxy",
+                host, port
+            ),
+        }
+        match timeout(
+            self.config.connect_timeout(),
+            dial(egress, &dial_host, dial_port, addrs),
+        )
+        .await
+        {
+            Ok(Ok(stream)) => Ok(stream),
+            Ok(Err(e)) => Err(ProxyRejection::ConnectFailed(format!(
+                "Failed to connect to {}:{} - {}",
+                host, port, e
+            ))),
+            Err(_) => Err(ProxyRejection::ConnectTimeout(format!(
+                "Timed out connecting to {}:{}",
+                host, port
+            ))),
+        }
+    }
+
+    /// Addresses of `host`, from the cache while its lookup is fresh.
+    async fn resolve(&self, host: &str, port: u16) -> Result<Vec<SocketAddr>, ProxyRejection> {
+        if let Ok(ip) = host.parse::<IpAddr>() {
+            return Ok(vec![SocketAddr::new(ip, port)]);
+        }
+
+        let key = (host.to_string(), port);
+        let ttl = self.config.dns_cache_ttl();
+        if !ttl.is_zero() {
+            let lookups = self.lookups.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(cached) = lookups.get(&key).filter(|c| c.expires > Instant::now()) {
+                self.metrics.dns_lookup("hit");
+                return Ok(cached.addrs.clone());
+            }
+        }
+        self.metrics.dns_lookup("miss");
+
+        let addrs: Vec<SocketAddr> = match timeout(
+            self.config.connect_timeout(),
+            tokio::net::lookup_host((host, port)),
+        )
+        .await
+        {
+            Ok(Ok(addrs)) => addrs.collect(),
+            Ok(Err(e)) => {
+                return Err(ProxyRejection::Unresolvable(format!(
+                    "Failed to resolve {}: {}",
+                    host, e
+                )))
+            }
+            Err(_) => {
+                return Err(ProxyRejection::ConnectTimeout(format!(
+                    "Timed out resolving {}",
+                    host
+                )))
+            }
+        };
+
+        if !ttl.is_zero() {
+            let now = Instant::now();
+            let mut lookups = self.lookups.lock().unwrap_or_else(|e| e.into_inner());
+            if lookups.len() >= MAX_CACHED_LOOKUPS {
+                lookups.retain(|_, cached| cached.expires > now);
+            }
+            if lookups.len() < MAX_CACHED_LOOKUPS {
+                let expires = now + ttl;
+                let addrs = addrs.clone();
+                lookups.insert(key, CachedLookup { addrs, expires });
+            }
+        }
+        Ok(addrs)
+    }
+}
+
+/// Dials the checked addresses, directly or through the egress proxy; without
+/// addresses the proxy is asked for `host` by name.
+async fn dial(
+    egress: Option<&EgressConfig>,
+    host: &str,
+    port: u16,
+    addrs: Option<Vec<SocketAddr>>,
+) -> io::Result<TcpStream> {
+    let Some(egress) = egress else {
+        return TcpStream::connect(addrs.unwrap_or_default().as_slice()).await;
+    };
+    let Some(addrs) = addrs else {
+        return egress.connect(host, port).await;
+    };
+    let mut last_error = None;
+    for addr in addrs {
+        match egress.connect(&addr.ip().to_string(), addr.port()).await {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| io::Error::other("no addresses to connect to")))
+}
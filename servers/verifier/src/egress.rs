@@ -0,0 +1,298 @@
+//! Outbound connections through an HTTP CONNECT or SOCKS5 proxy.
+//!
+//! Configured in the `egress` section of `config.yaml`, for networks where
+//! servers can only be reached through a forward proxy. When set, the
+//! [`Connector`](crate::connector::Connector) opens every upstream connection
+//! as a tunnel through it, except to targets matching `no_proxy`. The proxy
+//! itself is trusted as configured and not subject to the `proxy` rules.
+
+use base64::Engine;
+use serde::Deserialize;
+use std::io;
+use std::net::{IpAddr, Ipv6Addr};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::proxy_guard::{parse_target, TargetRule};
+
+/// Longest CONNECT response header accepted
+const MAX_RESPONSE_HEADER: usize = 8192;
+
+/// Protocol spoken to the egress proxy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum EgressScheme {
+    /// HTTP `CONNECT` (RFC 9110)
+    Http,
+    /// SOCKS version 5 (RFC 1928), with RFC 1929 username/password auth
+    Socks5,
+}
+
+impl EgressScheme {
+    fn default_port(self) -> u16 {
+        match self {
+            Self::Http => 8080,
+            Self::Socks5 => 1080,
+        }
+    }
+}
+
+/// Address of the egress proxy, given as `http://host:port` or
+/// `socks5://host:port`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub(crate) struct EgressUrl {
+    pub(crate) scheme: EgressScheme,
+    pub(crate) host: String,
+    pub(crate) port: u16,
+}
+
+impl TryFrom<String> for EgressUrl {
+    type Error = String;
+
+    fn try_from(url: String) -> Result<Self, Self::Error> {
+        let invalid = || format!("invalid egress proxy url `{}`", url);
+        let (scheme, authority) = url.split_once("://").ok_or_else(invalid)?;
+        let scheme = match scheme.to_ascii_lowercase().as_str() {
+            "http" => EgressScheme::Http,
+            "socks5" => EgressScheme::Socks5,
+            _ => return Err(format!("unsupported egress proxy scheme in `{}`", url)),
+        };
+        let authority = authority.trim_end_matches('/');
+        if authority.contains(['@', '/', '?', '#']) {
+            return Err(format!(
+                "egress proxy url `{}` must be scheme://host:port; set credentials with \
+                 username and password",
+                url
+            ));
+        }
+        let has_port = match authority.strip_prefix('[') {
+            Some(rest) => rest.contains("]:"),
+            None => authority.contains(':') && authority.parse::<Ipv6Addr>().is_err(),
+        };
+        let target = if has_port {
+            authority.to_string()
+        } else if authority.parse::<Ipv6Addr>().is_ok() {
+            format!("[{}]:{}", authority, scheme.default_port())
+        } else {
+            format!("{}:{}", authority, scheme.default_port())
+        };
+        let (host, port) = parse_target(&target).map_err(|_| invalid())?;
+        Ok(Self { scheme, host, port })
+    }
+}
+
+/// `egress` section of `config.yaml`.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct EgressConfig {
+    /// The proxy to tunnel through
+    pub(crate) url: EgressUrl,
+    /// Credentials: Basic auth for HTTP, username/password auth for SOCKS5
+    #[serde(default)]
+    pub(crate) username: Option<String>,
+    #[serde(default)]
+    pub(crate) password: Option<String>,
+    /// Targets dialled directly: hostnames, `*.domain` wildcards, IPs, CIDRs
+    #[serde(default)]
+    pub(crate) no_proxy: Vec<TargetRule>,
+    /// Let the proxy resolve server names, for networks without outside DNS.
+    /// The `proxy` address rules then only apply to IP targets
+    #[serde(default)]
+    pub(crate) remote_dns: bool,
+}
+
+impl EgressConfig {
+    /// Rejects a password without a username, and credentials SOCKS5 cannot
+    /// carry.
+    pub(crate) fn validate(&self) -> Result<(), eyre::ErrReport> {
+        if self.password.is_some() && self.username.is_none() {
+            return Err(eyre::eyre!("egress: password is set without a username"));
+        }
+        if self.url.scheme == EgressScheme::Socks5 {
+            let username = self.username.as_deref().unwrap_or_default();
+            let password = self.password.as_deref().unwrap_or_default();
+            if username.len() > 255 || password.len() > 255 {
+                return Err(eyre::eyre!(
+                    "egress: SOCKS5 credentials are limited to 255 bytes each"
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether connections to `host` go through the proxy.
+    pub(crate) fn applies_to(&self, host: &str) -> bool {
+        match host.parse::<IpAddr>() {
+            Ok(ip) => !self.no_proxy.iter().any(|rule| rule.matches_ip(ip)),
+            Err(_) => !self.no_proxy.iter().any(|rule| rule.matches_host(host)),
+        }
+    }
+
+    /// Opens a tunnel to `host:port`, where `host` is a name or an IP.
+    pub(crate) async fn connect(&self, host: &str, port: u16) -> io::Result<TcpStream> {
+        let mut stream = TcpStream::connect((self.url.host.as_str(), self.url.port))
+            .await
+            .map_err(|e| {
+                io::Error::new(
+                    e.kind(),
+                    format!(
+                        "egress proxy {}:{} unreachable: {}",
+                        self.url.host, self.url.port, e
+                    ),
+                )
+            })?;
+        match self.url.scheme {
+            EgressScheme::Http => self.http_connect(&mut stream, host, port).await?,
+            EgressScheme::Socks5 => self.socks5_connect(&mut stream, host, port).await?,
+        }
+        Ok(stream)
+    }
+
+    async fn http_connect(&self, stream: &mut TcpStream, host: &str, port: u16) -> io::Result<()> {
+        let authority = match host.parse::<Ipv6Addr>() {
+            Ok(_) => format!("[{}]:{}", host, port),
+            Err(_) => format!("{}:{}", host, port),
+        };
+        let mut request = format!(
+            "CONNECT {authority} HTTP/1.1\r\nHost: {authority}\r\n",
+            authority = authority
+        );
+        if let Some(username) = &self.username {
+            let credentials = format!("{}:{}", username, self.password.as_deref().unwrap_or(""));
+            request.push_str(&format!(
+                "Proxy-Authorization: Basic {}\r\n",
+                base64::engine::general_purpose::STANDARD.encode(credentials)
+            ));
+        }
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes()).await?;
+
+        // Read byte by byte so no tunnelled data is consumed with the header
+        let mut header = Vec::new();
+        while !header.ends_with(b"\r\n\r\n") {
+            if header.len() >= MAX_RESPONSE_HEADER {
+                return Err(proxy_error("egress proxy response header too long"));
+            }
+            let byte = stream.read_u8().await.map_err(|e| match e.kind() {
+                io::ErrorKind::UnexpectedEof => proxy_error("egress proxy closed the connection"),
+                _ => e,
+            })?;
+            header.push(byte);
+        }
+        let status_line = String::from_utf8_lossy(&header);
+        let status_line = status_line.lines().next().unwrap_or_default();
+        let status = status_line
+            .strip_prefix("HTTP/1.")
+            .and_then(|rest| rest.get(2..5))
+            .and_then(|status| status.parse::<u16>().ok());
+        match status {
+            Some(200..=299) => Ok(()),
+            Some(_) => Err(proxy_error(&format!(
+                "egress proxy refused CONNECT to {}: {}",
+                authority, status_line
+            ))),
+            None => Err(proxy_error("egress proxy sent an invalid response")),
+        }
+    }
+
+    async fn socks5_connect(
+        &self,
+        stream: &mut TcpStream,
+        host: &str,
+        port: u16,
+    ) -> io::Result<()> {
+        // Method selection: no auth, or username/password when configured
+        let greeting: &[u8] = match self.username {
+            Some(_) => &[5, 2, 0x00, 0x02],
+            None => &[5, 1, 0x00],
+        };
+        stream.write_all(greeting).await?;
+        let mut choice = [0u8; 2];
+        stream.read_exact(&mut choice).await?;
+        match choice {
+            [5, 0x00] => {}
+            [5, 0x02] if self.username.is_some() => {
+                let username = self.username.as_deref().unwrap_or_default().as_bytes();
+                let password = self.password.as_deref().unwrap_or_default().as_bytes();
+                let mut auth = vec![1, username.len() as u8];
+                auth.extend_from_slice(username);
+                auth.push(password.len() as u8);
+                auth.extend_from_slice(password);
+                stream.write_all(&auth).await?;
+                let mut status = [0u8; 2];
+                stream.read_exact(&mut status).await?;
+                if status[1] != 0 {
+                    return Err(proxy_error("egress proxy rejected the credentials"));
+                }
+            }
+            [5, 0xff] => {
+                return Err(proxy_error(
+                    "egress proxy accepts none of the offered auth methods",
+                ))
+            }
+            _ => return Err(proxy_error("egress proxy is not a SOCKS5 proxy")),
+        }
+
+        let mut request = vec![5, 1, 0];
+        match host.parse::<IpAddr>() {
+            Ok(IpAddr::V4(ip)) => {
+                request.push(1);
+                request.extend_from_slice(&ip.octets());
+            }
+            Ok(IpAddr::V6(ip)) => {
+                request.push(4);
+                request.extend_from_slice(&ip.octets());
+            }
+            Err(_) => {
+                let name = u8::try_from(host.len())
+                    .map_err(|_| proxy_error("server name too long for SOCKS5"))?;
+                request.extend_from_slice(&[3, name]);
+                request.extend_from_slice(host.as_bytes());
+            }
+        }
+        request.extend_from_slice(&port.to_be_bytes());
+        stream.write_all(&request).await?;
+
+        let mut reply = [0u8; 4];
+        stream.read_exact(&mut reply).await?;
+        if reply[0] != 5 {
+            return Err(proxy_error("egress proxy is not a SOCKS5 proxy"));
+        }
+        if reply[1] != 0 {
+            return Err(proxy_error(&format!(
+                "egress proxy refused to connect to {}:{}: {}",
+                host,
+                port,
+                socks5_reply(reply[1])
+            )));
+        }
+        // Skip the bound address and port
+        let bound = match reply[3] {
+            1 => 4,
+            4 => 16,
+            3 => stream.read_u8().await? as usize,
+            _ => return Err(proxy_error("egress proxy sent an invalid reply")),
+        };
+        let mut skipped = vec![0u8; bound + 2];
+        stream.read_exact(&mut skipped).await?;
+        Ok(())
+    }
+}
+
+fn proxy_error(msg: &str) -> io::Error {
+    io::Error::other(msg.to_string())
+}
+
+/// Meaning of a SOCKS5 reply code (RFC 1928, section 6).
+fn socks5_reply(code: u8) -> &'static str {
+    match code {
+        1 => "general failure",
+        2 => "connection not allowed by ruleset",
+        3 => "network unreachable",
+        4 => "host unreachable",
+        5 => "connection refused",
+        6 => "TTL expired",
+        7 => "command not supported",
+        8 => "address type not supported",
+        _ => "unknown error",
+    }
+}
@@ -0,0 +1,129 @@
+//! Machine-readable session errors.
+//!
+//! Every error a client sees, whether as an `error` message on the session
+//! socket, in a failure webhook or on a stored session, carries a stable
+//! `code`. Clients branch on the code rather than on the human-readable
+//! `message`, which may change between releases. Some codes come with
+//! `details` naming the offending direction, range or limit.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Stable error code, serialized in SCREAMING_SNAKE_CASE
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub(crate) enum ErrorCode {
+    /// A client message was malformed or not the one expected
+    InvalidMessage,
+    /// Requested data sizes exceed a server or session limit
+    LimitExceeded,
+    /// A verification policy rejected the session
+    PolicyDenied,
+    /// The server or the tenant has no room for another session
+    QuotaExceeded,
+    /// The server is shutting down; the session can be retried
+    Draining,
+    /// A resume named an unknown session or presented a wrong token
+    UnknownSession,
+    /// The prover did not connect in time
+    TimeoutProverConnect,
+    /// The prover's connection went away before verification started
+    ProverDisconnected,
+    /// The verifier could not connect to the target server (Proxy mode)
+    ServerUnreachable,
+    /// The commitment protocol or the TLS session with the server failed
+    CommitFailed,
+    /// The prover's proof did not verify
+    VerificationFailed,
+    /// Verification did not finish in time
+    TimeoutVerification,
+    /// The reveal config did not arrive in time
+    TimeoutRevealConfig,
+    /// The session exceeded its maximum lifetime
+    TimeoutSession,
+    /// A reveal range is not within the authenticated transcript
+    InvalidRevealRange,
+    /// A reveal range lies outside the HTTP part it is labelled with
+    MislabelledRange,
+    /// An operator cancelled the session through the admin API
+    Cancelled,
+    /// An unexpected server-side failure
+    Internal,
+}
+
+/// Specifics of an error, where the code has them
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct ErrorDetails {
+    /// Index of the session's TLS connection the error is about
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) connection: Option<usize>,
+    /// Transcript direction, `sent` or `recv`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) direction: Option<String>,
+    /// Offending `[start, end)` byte range
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) range: Option<[usize; 2]>,
+    /// Size that was asked for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) requested: Option<usize>,
+    /// Limit it exceeded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) limit: Option<usize>,
+}
+
+/// An error reported to the client of a session
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct SessionError {
+    pub(crate) code: ErrorCode,
+    pub(crate) message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) details: Option<ErrorDetails>,
+}
+
+impl SessionError {
+    pub(crate) fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    /// Names the `[start, end)` range of `direction` the error is about.
+    pub(crate) fn with_range(mut self, direction: &str, start: usize, end: usize) -> Self {
+        let details = self.details_mut();
+        details.direction = Some(direction.to_string());
+        details.range = Some([start, end]);
+        self
+    }
+
+    /// Names the transcript direction the error is about.
+    pub(crate) fn with_direction(mut self, direction: &str) -> Self {
+        self.details_mut().direction = Some(direction.to_string());
+        self
+    }
+
+    /// Names the size `requested` and the `limit` it exceeded.
+    pub(crate) fn with_limit(mut self, requested: usize, limit: usize) -> Self {
+        let details = self.details_mut();
+        details.requested = Some(requested);
+        details.limit = Some(limit);
+        self
+    }
+
+    /// Names the TLS connection the error is about.
+    pub(crate) fn with_connection(mut self, connection: usize) -> Self {
+        self.details_mut().connection = Some(connection);
+        self
+    }
+
+    fn details_mut(&mut self) -> &mut ErrorDetails {
+        self.details.get_or_insert_with(ErrorDetails::default)
+    }
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
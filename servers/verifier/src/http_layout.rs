@@ -0,0 +1,618 @@
+//! HTTP/1.1 layout of the authenticated transcript.
+//!
+//! The prover labels every reveal range with the HTTP part it claims to
+//! cover (`HandlerPart`). This module parses the transcript and checks those
+//! labels, so bytes from e.g. a response body cannot be passed off as a
+//! status code or header.
+//!
+//! The verifier only sees the bytes the prover revealed; everything else is
+//! redacted and may hide delimiters (a hidden `\r\n\r\n` ends the headers
+//! early). Each delimiter is therefore located as a [`Bound`]: the earliest
+//! position it could occur at given the redacted bytes, and the latest
+//! position it must occur by given the revealed ones. A range is:
+//!
+//! - [`LabelCheck::Verified`] if it lies inside the claimed part for every
+//!   layout consistent with the revealed bytes,
+//! - [`LabelCheck::Mislabelled`] if it lies outside it for every layout,
+//! - [`LabelCheck::Unverifiable`] otherwise (typically because bytes in front
+//!   of it are hidden).
+//!
+//! Messages after the first are only parsed when the framing of the previous
+//! message (`Content-Length` or chunked encoding) is fully revealed; otherwise
+//! the last parsed message is assumed to extend to the end of the transcript.
+//!
+//! The same layout tells policies where a part may lie regardless of labels
+//! ([`part_regions`]), so forbidden parts are found in whatever bytes the
+//! prover revealed, and whether a part was revealed in full
+//! ([`part_revealed`]).
+
+use crate::policy::label;
+use crate::{HandlerPart, HandlerType, RangeWithHandler, RevealConfig};
+
+/// Shortest start line: `A / HTTP/1.1` or `HTTP/1.1 200`
+const MIN_START_LINE: usize = 12;
+
+/// Result of checking one range label.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LabelCheck {
+    Verified,
+    Unverifiable,
+    Mislabelled,
+}
+
+/// Position of a delimiter, known to lie within `lo..=hi`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Bound {
+    lo: usize,
+    hi: usize,
+}
+
+impl Bound {
+    fn exact(at: usize) -> Self {
+        Self { lo: at, hi: at }
+    }
+
+    fn is_exact(&self) -> bool {
+        self.lo == self.hi
+    }
+
+    fn add(self, n: usize) -> Self {
+        Self {
+            lo: self.lo + n,
+            hi: self.hi + n,
+        }
+    }
+
+    fn sub(self, n: usize) -> Self {
+        Self {
+            lo: self.lo.saturating_sub(n),
+            hi: self.hi.saturating_sub(n),
+        }
+    }
+}
+
+/// A part of a message spanning `start..end`.
+#[derive(Debug, Clone, Copy)]
+struct Span {
+    start: Bound,
+    end: Bound,
+}
+
+impl Span {
+    fn check(&self, start: usize, end: usize) -> LabelCheck {
+        if start >= self.start.hi && end <= self.end.lo {
+            LabelCheck::Verified
+        } else if start < self.start.lo || end > self.end.hi {
+            LabelCheck::Mislabelled
+        } else {
+            LabelCheck::Unverifiable
+        }
+    }
+
+    /// Whether `start..end` is exactly this span, with both ends revealed.
+    fn is_exactly(&self, start: usize, end: usize) -> bool {
+        self.start.is_exact() && self.end.is_exact() && self.start.lo == start && self.end.lo == end
+    }
+}
+
+/// Layout of one HTTP/1.1 message.
+#[derive(Debug)]
+struct Message {
+    start_line: Span,
+    protocol: Span,
+    /// Requests only
+    method: Option<Span>,
+    /// Requests only
+    request_target: Option<Span>,
+    /// Responses only
+    status_code: Option<Span>,
+    headers: Span,
+    /// Message body, including any chunked transfer coding framing
+    body: Span,
+}
+
+/// Transcript bytes together with which of them were revealed.
+struct Revealed<'a, F> {
+    bytes: &'a [u8],
+    is_known: F,
+}
+
+impl<F: Fn(usize) -> bool> Revealed<'_, F> {
+    fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Whether `pattern` could occur at `at`, treating hidden bytes as wildcards.
+    fn may_match(&self, at: usize, pattern: &[u8]) -> bool {
+        at.checked_add(pattern.len())
+            .is_some_and(|end| end <= self.len())
+            && pattern
+                .iter()
+                .enumerate()
+                .all(|(k, &c)| !(self.is_known)(at + k) || self.bytes[at + k] == c)
+    }
+
+    /// Whether `pattern` is revealed at `at`.
+    fn does_match(&self, at: usize, pattern: &[u8]) -> bool {
+        at.checked_add(pattern.len())
+            .is_some_and(|end| end <= self.len())
+            && pattern
+                .iter()
+                .enumerate()
+                .all(|(k, &c)| (self.is_known)(at + k) && self.bytes[at + k] == c)
+    }
+
+    fn all_known(&self, start: usize, end: usize) -> bool {
+        (start..end).all(|i| (self.is_known)(i))
+    }
+
+    /// Whether `name:` could occur at `at`, ignoring ASCII case.
+    fn may_match_header(&self, at: usize, name: &str) -> bool {
+        let pattern = name.bytes().chain(std::iter::once(b':'));
+        at.checked_add(name.len())
+            .is_some_and(|end| end < self.len())
+            && pattern.enumerate().all(|(k, c)| {
+                !(self.is_known)(at + k) || self.bytes[at + k].eq_ignore_ascii_case(&c)
+            })
+    }
+
+    /// Lines within `start..end` that may be `name:` header lines, each up to
+    /// its first revealed line break, merged where they overlap.
+    fn header_lines(&self, name: &str, start: usize, end: usize) -> Vec<(usize, usize)> {
+        let end = end.min(self.len());
+        if start >= end {
+            return Vec::new();
+        }
+        // Position of the first revealed CR or LF at or after each byte
+        let mut line_breaks = vec![end; end - start + 1];
+        for i in (start..end).rev() {
+            let is_break = (self.is_known)(i) && matches!(self.bytes[i], b'\r' | b'\n');
+            line_breaks[i - start] = if is_break {
+                i
+            } else {
+                line_breaks[i + 1 - start]
+            };
+        }
+
+        let mut lines: Vec<(usize, usize)> = Vec::new();
+        for at in start..end {
+            let line_start = at == 0 || self.may_match(at - 1, b"\n");
+            if !line_start || !self.may_match_header(at, name) {
+                continue;
+            }
+            let line_end = line_breaks[at - start];
+            match lines.last_mut() {
+                Some(last) if at <= last.1 => last.1 = last.1.max(line_end),
+                _ => lines.push((at, line_end)),
+            }
+        }
+        lines
+    }
+
+    /// End of a request line whose revealed prefix is already complete.
+    ///
+    /// A request line is `method SP target SP HTTP/x.y CRLF`, so once the
+    /// revealed bytes form all three tokens the line must end there, even if
+    /// the CRLF itself is hidden.
+    fn request_line_end(&self, at: usize) -> Option<usize> {
+        let end = (at..self.len())
+            .find(|&i| !(self.is_known)(i) || matches!(self.bytes[i], b'\r' | b'\n'))
+            .unwrap_or(self.len());
+        let line = std::str::from_utf8(&self.bytes[at..end]).ok()?;
+        let mut tokens = line.split(' ');
+        let complete = matches!(
+            (tokens.next(), tokens.next(), tokens.next(), tokens.next()),
+            (Some(method), Some(target), Some(protocol), None)
+                if !method.is_empty()
+                    && !target.is_empty()
+                    && protocol.len() == 8
+                    && protocol.starts_with("HTTP/1.")
+        );
+        complete.then_some(end)
+    }
+
+    /// Bounds on the first occurrence of `pattern` after `from`. A pattern
+    /// that may not occur at all is treated as occurring at the end.
+    fn find(&self, pattern: &[u8], from: Bound) -> Bound {
+        let len = self.len();
+        let lo = (from.lo..len)
+            .find(|&i| self.may_match(i, pattern))
+            .unwrap_or(len);
+        let hi = (from.hi..len)
+            .find(|&i| self.does_match(i, pattern))
+            .unwrap_or(len);
+        Bound { lo, hi: hi.max(lo) }
+    }
+
+    /// Parses the message starting at `at`, returning it and where the next
+    /// message starts if its framing is fully revealed.
+    fn parse_message(&self, at: usize, is_request: bool) -> (Message, Option<usize>) {
+        let len = self.len();
+        let start = Bound::exact(at);
+        // Three tokens, one of them `HTTP/1.x`, come before the first line break
+        let earliest_line_end = start.add(MIN_START_LINE);
+        let line_end = match is_request.then(|| self.request_line_end(at)).flatten() {
+            Some(end) => Bound::exact(end),
+            None => self.find(b"\r\n", earliest_line_end),
+        };
+        let headers_end = self.find(b"\r\n\r\n", earliest_line_end);
+        let body_start = headers_end.add(4);
+
+        // HTTP-version is exactly 8 bytes and status-code exactly 3 digits
+        let (protocol, method, request_target, status_code) = if is_request {
+            let first_space = self.find(b" ", start);
+            let second_space = self.find(b" ", first_space.add(1));
+            (
+                Span {
+                    start: line_end.sub(8),
+                    end: line_end,
+                },
+                Some(Span {
+                    start,
+                    end: first_space,
+                }),
+                Some(Span {
+                    start: first_space.add(1),
+                    end: second_space,
+                }),
+                None,
+            )
+        } else {
+            (
+                Span {
+                    start,
+                    end: start.add(8),
+                },
+                None,
+                None,
+                Some(Span {
+                    start: start.add(9),
+                    end: start.add(12),
+                }),
+            )
+        };
+
+        let mut message = Message {
+            start_line: Span {
+                start,
+                end: line_end.add(2),
+            },
+            protocol,
+            method,
+            request_target,
+            status_code,
+            headers: Span {
+                start: line_end.add(2),
+                end: headers_end.add(2),
+            },
+            body: Span {
+                start: body_start,
+                end: Bound::exact(len),
+            },
+        };
+
+        // Framing can only be trusted when the whole header block is revealed
+        if !headers_end.is_exact() || body_start.lo > len || !self.all_known(at, body_start.lo) {
+            return (message, None);
+        }
+        let body_start = body_start.lo;
+        let headers = &self.bytes[at..body_start];
+
+        let next = if has_chunked_encoding(headers) {
+            self.chunked_body_end(body_start)
+        } else if let Some(length) = content_length(headers) {
+            // A length past `usize::MAX` leaves the framing unknown
+            body_start.checked_add(length).map(|end| end.min(len))
+        } else if is_request {
+            Some(body_start)
+        } else {
+            // Response delimited by connection close
+            None
+        };
+
+        if let Some(end) = next {
+            message.body.end = Bound::exact(end);
+        }
+
+        (message, next.filter(|&end| end < len))
+    }
+
+    /// Follows the chunked framing of a body starting at `at` and returns the
+    /// end of the message. Chunk data may be hidden, but `None` is returned if
+    /// any chunk-size line, chunk delimiter or trailer is hidden or malformed.
+    fn chunked_body_end(&self, mut at: usize) -> Option<usize> {
+        loop {
+            let line_end = (at..self.len()).find(|&i| self.does_match(i, b"\r\n"))?;
+            if !self.all_known(at, line_end) {
+                return None;
+            }
+            let size_line = std::str::from_utf8(&self.bytes[at..line_end]).ok()?;
+            let size_hex = size_line.split(';').next()?.trim();
+            let size = usize::from_str_radix(size_hex, 16).ok()?;
+            let data_start = line_end + 2;
+
+            if size == 0 {
+                // Trailer section, terminated by an empty line
+                let mut line_start = data_start;
+                loop {
+                    let end = (line_start..self.len()).find(|&i| self.does_match(i, b"\r\n"))?;
+                    if !self.all_known(line_start, end) {
+                        return None;
+                    }
+                    if end == line_start {
+                        return Some(end + 2);
+                    }
+                    line_start = end + 2;
+                }
+            }
+
+            let data_end = data_start.checked_add(size)?;
+            if !self.does_match(data_end, b"\r\n") {
+                return None;
+            }
+            at = data_end + 2;
+        }
+    }
+
+    /// Parses consecutive messages until the framing is no longer revealed.
+    fn parse(&self, is_request: bool) -> Vec<Message> {
+        let mut messages = Vec::new();
+        let mut at = 0;
+        loop {
+            let (message, next) = self.parse_message(at, is_request);
+            messages.push(message);
+            match next {
+                Some(next) if next > at => at = next,
+                _ => return messages,
+            }
+        }
+    }
+}
+
+fn header_value<'a>(headers: &'a [u8], name: &str) -> Option<&'a str> {
+    headers.split(|&b| b == b'\n').skip(1).find_map(|line| {
+        let line = std::str::from_utf8(line).ok()?.trim_end_matches('\r');
+        let (key, value) = line.split_once(':')?;
+        key.trim()
+            .eq_ignore_ascii_case(name)
+            .then_some(value.trim())
+    })
+}
+
+fn has_chunked_encoding(headers: &[u8]) -> bool {
+    header_value(headers, "transfer-encoding").is_some_and(|value| {
+        value
+            .rsplit(',')
+            .next()
+            .is_some_and(|coding| coding.trim().eq_ignore_ascii_case("chunked"))
+    })
+}
+
+fn content_length(headers: &[u8]) -> Option<usize> {
+    header_value(headers, "content-length")?.parse().ok()
+}
+
+impl Message {
+    /// Span of a part other than `ALL`, if messages of this kind have it.
+    fn span(&self, part: &HandlerPart) -> Option<&Span> {
+        match part {
+            HandlerPart::All => None,
+            HandlerPart::StartLine => Some(&self.start_line),
+            HandlerPart::Protocol => Some(&self.protocol),
+            HandlerPart::Method => self.method.as_ref(),
+            HandlerPart::RequestTarget => self.request_target.as_ref(),
+            HandlerPart::StatusCode => self.status_code.as_ref(),
+            HandlerPart::Headers => Some(&self.headers),
+            HandlerPart::Body => Some(&self.body),
+        }
+    }
+
+    fn check(&self, part: &HandlerPart, start: usize, end: usize) -> LabelCheck {
+        if *part == HandlerPart::All {
+            return LabelCheck::Verified;
+        }
+        self.span(part)
+            .map_or(LabelCheck::Mislabelled, |span| span.check(start, end))
+    }
+}
+
+/// Most favourable outcome: a range is fine if it fits any message.
+fn best(checks: impl Iterator<Item = LabelCheck>) -> LabelCheck {
+    checks
+        .min_by_key(|check| match check {
+            LabelCheck::Verified => 0,
+            LabelCheck::Unverifiable => 1,
+            LabelCheck::Mislabelled => 2,
+        })
+        .unwrap_or(LabelCheck::Mislabelled)
+}
+
+/// Checks the label of every range in one direction.
+///
+/// `is_known` reports whether a transcript byte was revealed. Returns one
+/// result per range, in order.
+pub(crate) fn check_labels(
+    bytes: &[u8],
+    is_known: impl Fn(usize) -> bool,
+    ranges: &[RangeWithHandler],
+    is_request: bool,
+) -> Vec<LabelCheck> {
+    let revealed = Revealed { bytes, is_known };
+    let messages = revealed.parse(is_request);
+    let expected_type = if is_request {
+        HandlerType::Sent
+    } else {
+        HandlerType::Recv
+    };
+
+    ranges
+        .iter()
+        .map(|range| {
+            if range.handler.handler_type != expected_type || range.start >= range.end {
+                return LabelCheck::Mislabelled;
+            }
+            best(
+                messages
+                    .iter()
+                    .map(|message| message.check(&range.handler.part, range.start, range.end)),
+            )
+        })
+        .collect()
+}
+
+/// Whether each range covers the whole of the part it is labelled with, as
+/// pinned down by the revealed bytes. An `ALL` range must cover the whole
+/// transcript. Returns one result per range, in order.
+pub(crate) fn spans_whole_part(
+    bytes: &[u8],
+    is_known: impl Fn(usize) -> bool,
+    ranges: &[RangeWithHandler],
+    is_request: bool,
+) -> Vec<bool> {
+    let revealed = Revealed { bytes, is_known };
+    let messages = revealed.parse(is_request);
+    ranges
+        .iter()
+        .map(|range| match range.handler.part {
+            HandlerPart::All => range.start == 0 && range.end == bytes.len(),
+            ref part => messages.iter().any(|message| {
+                message
+                    .span(part)
+                    .is_some_and(|span| span.is_exactly(range.start, range.end))
+            }),
+        })
+        .collect()
+}
+
+/// Transcript regions `part` may occupy in any layout consistent with the
+/// revealed bytes, as `(start, end)` pairs in order.
+///
+/// With `header`, only the lines of that header within the header block
+/// (for `HEADERS` or `ALL`). Unlike [`check_labels`], this does not depend on
+/// how the prover labelled its ranges.
+pub(crate) fn part_regions(
+    bytes: &[u8],
+    is_known: impl Fn(usize) -> bool,
+    part: &HandlerPart,
+    header: Option<&str>,
+    is_request: bool,
+) -> Vec<(usize, usize)> {
+    let revealed = Revealed { bytes, is_known };
+    if header.is_none() && *part == HandlerPart::All {
+        return vec![(0, bytes.len())];
+    }
+    let mut regions: Vec<(usize, usize)> = Vec::new();
+    for message in revealed.parse(is_request) {
+        let extents = match (header, part) {
+            (Some(name), HandlerPart::Headers | HandlerPart::All) => {
+                let span = message.headers;
+                revealed.header_lines(name, span.start.lo, span.end.hi)
+            }
+            (Some(_), _) => Vec::new(),
+            (None, part) => message
+                .span(part)
+                .map(|span| (span.start.lo, span.end.hi.min(bytes.len())))
+                .into_iter()
+                .collect(),
+        };
+        for (start, end) in extents.into_iter().filter(|(start, end)| start < end) {
+            match regions.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => regions.push((start, end)),
+            }
+        }
+    }
+    regions
+}
+
+/// Whether `part` is revealed in full in some message, whatever the labels:
+/// its bounds are pinned down by the revealed bytes and every byte between
+/// them is revealed. `ALL` means the whole transcript.
+///
+/// With `header`, one whole `name:` line of a header block (for `HEADERS` or
+/// `ALL`), from its start up to and including its line break.
+pub(crate) fn part_revealed(
+    bytes: &[u8],
+    is_known: impl Fn(usize) -> bool,
+    part: &HandlerPart,
+    header: Option<&str>,
+    is_request: bool,
+) -> bool {
+    let revealed = Revealed { bytes, is_known };
+    if header.is_none() && *part == HandlerPart::All {
+        return revealed.all_known(0, bytes.len());
+    }
+    revealed
+        .parse(is_request)
+        .iter()
+        .any(|message| match (header, part) {
+            (Some(name), HandlerPart::Headers | HandlerPart::All) => {
+                let span = message.headers;
+                revealed
+                    .header_lines(name, span.start.lo, span.end.hi)
+                    .into_iter()
+                    .any(|(start, end)| {
+                        let line_start = (span.start.is_exact() && start == span.start.lo)
+                            || start
+                                .checked_sub(1)
+                                .is_some_and(|at| revealed.does_match(at, b"\n"));
+                        line_start
+                            && end < bytes.len()
+                            && matches!(bytes[end], b'\r' | b'\n')
+                            && revealed.all_known(start, end + 1)
+                            && span.check(start, end) == LabelCheck::Verified
+                    })
+            }
+            (Some(_), _) => false,
+            (None, part) => message.span(part).is_some_and(|span| {
+                span.start.is_exact()
+                    && span.end.is_exact()
+                    && revealed.all_known(span.start.lo, span.end.lo)
+            }),
+        })
+}
+
+/// Ranges whose labels could not be verified, as `(direction, start, end)`.
+pub(crate) type UnverifiedRanges = Vec<(&'static str, usize, usize)>;
+
+/// Checks every range label in the reveal config against the transcript.
+///
+/// Fails on the first mislabelled range; otherwise returns the ranges that
+/// could not be verified because too much of the transcript is hidden.
+pub(crate) fn validate_labels(
+    reveal_config: &RevealConfig,
+    sent: (&[u8], impl Fn(usize) -> bool),
+    recv: (&[u8], impl Fn(usize) -> bool),
+) -> Result<UnverifiedRanges, String> {
+    let mut unverified = Vec::new();
+    for (direction, ranges, checks) in [
+        (
+            "sent",
+            &reveal_config.sent,
+            check_labels(sent.0, sent.1, &reveal_config.sent, true),
+        ),
+        (
+            "recv",
+            &reveal_config.recv,
+            check_labels(recv.0, recv.1, &reveal_config.recv, false),
+        ),
+    ] {
+        for (range, check) in ranges.iter().zip(checks) {
+            match check {
+                LabelCheck::Verified => {}
+                LabelCheck::Unverifiable => unverified.push((direction, range.start, range.end)),
+                LabelCheck::Mislabelled => {
+                    return Err(format!(
+                        "Mislabelled {} range [{}, {}) - not within {} {}",
+                        direction,
+                        range.start,
+                        range.end,
+                        label(&range.handler.handler_type),
+                        label(&range.handler.part)
+                    ))
+                }
+            }
+        }
+    }
+    Ok(unverified)
+}
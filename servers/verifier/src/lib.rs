@@ -0,0 +1,170 @@
+//! Offline checks for what the verifier server sends out, for Rust services
+//! that consume its attestations and webhooks.
+//!
+//! The server signs with the types and functions defined here, so a check
+//! passes exactly for what it sent.
+
+pub mod attestation {
+    //! Attestations returned in `session_completed`, webhooks and stored
+    //! sessions.
+    //!
+    //! The signed message is the JSON attestation body, carried
+    //! base64-encoded in [`Attestation::payload`]. The public key to check it
+    //! against is served at `GET /pubkey`.
+
+    use base64::Engine;
+    use eyre::eyre;
+    use serde::de::DeserializeOwned;
+    use serde::{Deserialize, Serialize};
+
+    /// Signature scheme used for attestations.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    pub enum SigningAlgorithm {
+        /// ECDSA over SHA-256 of the payload, 64-byte `r || s`
+        Secp256k1,
+        /// Plain Ed25519 over the payload
+        Ed25519,
+    }
+
+    /// A signed attestation as returned in `session_completed` and webhooks.
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct Attestation {
+        pub algorithm: SigningAlgorithm,
+        /// Identifier of the signing key, as served at `GET /pubkey`
+        pub key_id: String,
+        /// Base64 (standard, padded) of the signed JSON body
+        pub payload: String,
+        /// Hex-encoded signature over the decoded payload bytes
+        pub signature: String,
+    }
+
+    /// Verifies an attestation against a hex-encoded public key and returns
+    /// the decoded body.
+    ///
+    /// This only needs the public key from `GET /pubkey`, so it can run
+    /// offline in any service that receives attestations. The body is only
+    /// parsed once the signature is checked.
+    pub fn verify_attestation<T: DeserializeOwned>(
+        attestation: &Attestation,
+        public_key_hex: &str,
+    ) -> Result<T, eyre::ErrReport> {
+        let public_key =
+            hex::decode(public_key_hex).map_err(|e| eyre!("Invalid public key hex: {}", e))?;
+        let payload = base64::engine::general_purpose::STANDARD
+            .decode(&attestation.payload)
+            .map_err(|e| eyre!("Invalid payload encoding: {}", e))?;
+        let signature = hex::decode(&attestation.signature)
+            .map_err(|e| eyre!("Invalid signature hex: {}", e))?;
+
+        match attestation.algorithm {
+            SigningAlgorithm::Secp256k1 => {
+                use k256::ecdsa::signature::Verifier;
+                let key = k256::ecdsa::VerifyingKey::from_sec1_bytes(&public_key)
+                    .map_err(|e| eyre!("Invalid secp256k1 public key: {}", e))?;
+                let signature = k256::ecdsa::Signature::from_slice(&signature)
+                    .map_err(|e| eyre!("Invalid secp256k1 signature: {}", e))?;
+                key.verify(&payload, &signature)
+                    .map_err(|_| eyre!("Attestation signature mismatch"))?;
+            }
+            SigningAlgorithm::Ed25519 => {
+                use ed25519_dalek::Verifier;
+                let public_key: [u8; 32] = public_key
+                    .try_into()
+                    .map_err(|_| eyre!("Ed25519 public key must be 32 bytes"))?;
+                let key = ed25519_dalek::VerifyingKey::from_bytes(&public_key)
+                    .map_err(|e| eyre!("Invalid Ed25519 public key: {}", e))?;
+                let signature = ed25519_dalek::Signature::from_slice(&signature)
+                    .map_err(|e| eyre!("Invalid Ed25519 signature: {}", e))?;
+                key.verify(&payload, &signature)
+                    .map_err(|_| eyre!("Attestation signature mismatch"))?;
+            }
+        }
+
+        serde_json::from_slice(&payload).map_err(|e| eyre!("Invalid attestation body: {}", e))
+    }
+}
+
+pub mod webhook {
+    //! Signatures on webhook requests.
+    //!
+    //! When a webhook has a `secret`, each request carries a
+    //! [`SIGNATURE_HEADER`] of the form `t=<unix-seconds>,v1=<hex>`, where
+    //! `v1` is HMAC-SHA256 keyed with the secret over `"<t>.<raw body>"`.
+
+    use eyre::eyre;
+    use hmac::{Hmac, Mac};
+    use sha2::Sha256;
+
+    /// Header carrying the HMAC signature when the webhook has a `secret`
+    pub const SIGNATURE_HEADER: &str = "X-Tlsn-Signature";
+
+    /// Suggested maximum signature age, in seconds, for [`verify_signature`]
+    pub const DEFAULT_SIGNATURE_TOLERANCE_SECS: u64 = 300;
+
+    fn signature_mac(secret: &str, timestamp: u64, body: &[u8]) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+        mac.update(timestamp.to_string().as_bytes());
+        mac.update(b".");
+        mac.update(body);
+        mac
+    }
+
+    /// Builds the [`SIGNATURE_HEADER`] value for a request body.
+    pub fn sign(secret: &str, timestamp: u64, body: &[u8]) -> String {
+        let signature = signature_mac(secret, timestamp, body)
+            .finalize()
+            .into_bytes();
+        format!("t={},v1={}", timestamp, hex::encode(signature))
+    }
+
+    /// Verifies a [`SIGNATURE_HEADER`] value against the raw request body.
+    ///
+    /// Signatures whose timestamp is more than `tolerance_secs` away from
+    /// `now` are rejected, which bounds how long a captured request can be
+    /// replayed. Receivers should still deduplicate on the `Idempotency-Key`
+    /// header.
+    pub fn verify_signature(
+        secret: &str,
+        header: &str,
+        body: &[u8],
+        tolerance_secs: u64,
+        now: u64,
+    ) -> Result<(), eyre::ErrReport> {
+        let mut timestamp = None;
+        let mut signatures = Vec::new();
+        for part in header.split(',') {
+            match part.trim().split_once('=') {
+                Some(("t", value)) => {
+                    timestamp = Some(
+                        value
+                            .parse::<u64>()
+                            .map_err(|_| eyre!("Invalid signature timestamp"))?,
+                    )
+                }
+                Some(("v1", value)) => signatures.push(value),
+                _ => {}
+            }
+        }
+        let timestamp = timestamp.ok_or_else(|| eyre!("Signature header has no timestamp"))?;
+        if signatures.is_empty() {
+            return Err(eyre!("Signature header has no v1 signature"));
+        }
+        if now.abs_diff(timestamp) > tolerance_secs {
+            return Err(eyre!("Signature timestamp outside tolerance"));
+        }
+
+        let matches = signatures.iter().any(|signature| {
+            hex::decode(signature).is_ok_and(|signature| {
+                signature_mac(secret, timestamp, body)
+                    .verify_slice(&signature)
+                    .is_ok()
+            })
+        });
+        if !matches {
+            return Err(eyre!("Webhook signature mismatch"));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,258 @@
+//! Timeouts and server-wide limits.
+//!
+//! Configured in the `limits` section of `config.yaml`. Every field can be
+//! overridden with a `TLSN_VERIFIER_<FIELD>` environment variable, e.g.
+//! `TLSN_VERIFIER_VERIFICATION_TIMEOUT_SECS=300`; setting an optional limit
+//! to an empty string removes it.
+
+use eyre::eyre;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::error::{ErrorCode, SessionError};
+
+/// Prefix of the environment variables overriding `limits` fields
+pub(crate) const ENV_PREFIX: &str = "TLSN_VERIFIER_";
+
+/// What to do with a registration asking for more data than the server allows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum OversizedRegistration {
+    /// Refuse the registration
+    #[default]
+    Reject,
+    /// Accept it with the limits lowered to the server maximum
+    Clamp,
+}
+
+impl FromStr for OversizedRegistration {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reject" => Ok(Self::Reject),
+            "clamp" => Ok(Self::Clamp),
+            other => Err(format!("expected `reject` or `clamp`, got `{}`", other)),
+        }
+    }
+}
+
+/// `limits` section of `config.yaml`, also published on `/info`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct LimitsConfig {
+    /// Time allowed for the prover to connect to `/verifier` after registering
+    pub(crate) prover_connect_timeout_secs: u64,
+    /// Time allowed for the TLS verification protocol
+    pub(crate) verification_timeout_secs: u64,
+    /// Time allowed for the reveal config to arrive after verification
+    pub(crate) reveal_config_timeout_secs: u64,
+    /// Upper bound on a registration's `maxSentData`
+    pub(crate) max_sent_data: Option<usize>,
+    /// Upper bound on a registration's `maxRecvData`
+    pub(crate) max_recv_data: Option<usize>,
+    /// Whether registrations above the data limits are rejected or clamped
+    pub(crate) oversized_registration: OversizedRegistration,
+    /// Sessions held at once; further registrations are rejected
+    pub(crate) max_concurrent_sessions: Option<usize>,
+    /// Wall-clock bound on a session, from registration to its result
+    pub(crate) max_session_lifetime_secs: Option<u64>,
+    /// Time running sessions get to finish after a shutdown signal
+    pub(crate) drain_timeout_secs: u64,
+    /// Time a session result is kept for a resumed session socket
+    pub(crate) resume_buffer_secs: u64,
+    /// Upper bound on a registration's `connections`
+    pub(crate) max_connections_per_session: usize,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            prover_connect_timeout_secs: 30,
+            verification_timeout_secs: 120,
+            reveal_config_timeout_secs: 30,
+            max_sent_data: None,
+            max_recv_data: None,
+            oversized_registration: OversizedRegistration::Reject,
+            max_concurrent_sessions: None,
+            max_session_lifetime_secs: None,
+            drain_timeout_secs: 180,
+            resume_buffer_secs: 60,
+            max_connections_per_session: 4,
+        }
+    }
+}
+
+/// Parses an override value, naming the variable on failure.
+fn parse<T: FromStr>(name: &str, value: &str) -> Result<T, eyre::ErrReport>
+where
+    T::Err: std::fmt::Display,
+{
+    value
+        .trim()
+        .parse()
+        .map_err(|e| eyre!("{}: invalid value {:?}: {}", name, value, e))
+}
+
+/// Parses an override of an optional limit; an empty value clears it.
+fn parse_optional<T: FromStr>(name: &str, value: &str) -> Result<Option<T>, eyre::ErrReport>
+where
+    T::Err: std::fmt::Display,
+{
+    if value.trim().is_empty() {
+        return Ok(None);
+    }
+    parse(name, value).map(Some)
+}
+
+impl LimitsConfig {
+    /// Applies `TLSN_VERIFIER_*` overrides, looked up with `var`.
+    pub(crate) fn apply_env(
+        &mut self,
+        var: impl Fn(&str) -> Option<String>,
+    ) -> Result<(), eyre::ErrReport> {
+        let lookup = |field: &str| {
+            let name = format!("{}{}", ENV_PREFIX, field.to_uppercase());
+            var(&name).map(|value| (name, value))
+        };
+
+        if let Some((name, value)) = lookup("prover_connect_timeout_secs") {
+            self.prover_connect_timeout_secs = parse(&name, &value)?;
+        }
+        if let Some((name, value)) = lookup("verification_timeout_secs") {
+            self.verification_timeout_secs = parse(&name, &value)?;
+        }
+        if let Some((name, value)) = lookup("reveal_config_timeout_secs") {
+            self.reveal_config_timeout_secs = parse(&name, &value)?;
+        }
+        if let Some((name, value)) = lookup("max_sent_data") {
+            self.max_sent_data = parse_optional(&name, &value)?;
+        }
+        if let Some((name, value)) = lookup("max_recv_data") {
+            self.max_recv_data = parse_optional(&name, &value)?;
+        }
+        if let Some((name, value)) = lookup("oversized_registration") {
+            self.oversized_registration = parse(&name, &value)?;
+        }
+        if let Some((name, value)) = lookup("max_concurrent_sessions") {
+            self.max_concurrent_sessions = parse_optional(&name, &value)?;
+        }
+        if let Some((name, value)) = lookup("max_session_lifetime_secs") {
+            self.max_session_lifetime_secs = parse_optional(&name, &value)?;
+        }
+        if let Some((name, value)) = lookup("drain_timeout_secs") {
+            self.drain_timeout_secs = parse(&name, &value)?;
+        }
+        if let Some((name, value)) = lookup("resume_buffer_secs") {
+            self.resume_buffer_secs = parse(&name, &value)?;
+        }
+        if let Some((name, value)) = lookup("max_connections_per_session") {
+            self.max_connections_per_session = parse(&name, &value)?;
+        }
+        Ok(())
+    }
+
+    /// Rejects values the server cannot run with.
+    pub(crate) fn validate(&self) -> Result<(), eyre::ErrReport> {
+        for (field, value) in [
+            (
+                "prover_connect_timeout_secs",
+                Some(self.prover_connect_timeout_secs),
+            ),
+            (
+                "verification_timeout_secs",
+                Some(self.verification_timeout_secs),
+            ),
+            (
+                "reveal_config_timeout_secs",
+                Some(self.reveal_config_timeout_secs),
+            ),
+            ("max_session_lifetime_secs", self.max_session_lifetime_secs),
+            ("max_sent_data", self.max_sent_data.map(|v| v as u64)),
+            ("max_recv_data", self.max_recv_data.map(|v| v as u64)),
+            (
+                "max_concurrent_sessions",
+                self.max_concurrent_sessions.map(|v| v as u64),
+            ),
+            (
+                "max_connections_per_session",
+                Some(self.max_connections_per_session as u64),
+            ),
+        ] {
+            if value == Some(0) {
+                return Err(eyre!("limits: `{}` must be greater than zero", field));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn prover_connect_timeout(&self) -> Duration {
+        Duration::from_secs(self.prover_connect_timeout_secs)
+    }
+
+    pub(crate) fn verification_timeout(&self) -> Duration {
+        Duration::from_secs(self.verification_timeout_secs)
+    }
+
+    pub(crate) fn reveal_config_timeout(&self) -> Duration {
+        Duration::from_secs(self.reveal_config_timeout_secs)
+    }
+
+    pub(crate) fn max_session_lifetime(&self) -> Option<Duration> {
+        self.max_session_lifetime_secs.map(Duration::from_secs)
+    }
+
+    pub(crate) fn drain_timeout(&self) -> Duration {
+        Duration::from_secs(self.drain_timeout_secs)
+    }
+
+    pub(crate) fn resume_buffer(&self) -> Duration {
+        Duration::from_secs(self.resume_buffer_secs)
+    }
+
+    /// Checks a registration's data limits against the server maximum,
+    /// returning the `(sent, recv)` limits to run the session with.
+    pub(crate) fn admit_data_limits(
+        &self,
+        sent: usize,
+        recv: usize,
+    ) -> Result<(usize, usize), SessionError> {
+        let admit = |requested: usize, max: Option<usize>, label: &str, direction: &str| match max {
+            Some(max) if requested > max => match self.oversized_registration {
+                OversizedRegistration::Reject => Err(SessionError::new(
+                    ErrorCode::LimitExceeded,
+                    format!("{} {} exceeds server limit {}", label, requested, max),
+                )
+                .with_direction(direction)
+                .with_limit(requested, max)),
+                OversizedRegistration::Clamp => Ok(max),
+            },
+            _ => Ok(requested),
+        };
+        Ok((
+            admit(sent, self.max_sent_data, "maxSentData", "sent")?,
+            admit(recv, self.max_recv_data, "maxRecvData", "recv")?,
+        ))
+    }
+
+    /// Checks a registration's number of TLS connections.
+    pub(crate) fn admit_connections(&self, connections: usize) -> Result<(), SessionError> {
+        let max = self.max_connections_per_session;
+        if connections == 0 {
+            return Err(SessionError::new(
+                ErrorCode::InvalidMessage,
+                "connections must be at least 1",
+            ));
+        }
+        if connections > max {
+            return Err(SessionError::new(
+                ErrorCode::LimitExceeded,
+                format!("connections {} exceeds server limit {}", connections, max),
+            )
+            .with_limit(connections, max));
+        }
+        Ok(())
+    }
+}
@@ -1,27 +1,65 @@
+mod admin;
+mod assertion;
+mod attestation;
+mod auth;
+mod commitment;
+mod connector;
+mod egress;
+mod error;
+mod http_layout;
+mod limits;
+mod metrics;
+mod opening;
+mod policy;
+mod progress;
+mod proxy_guard;
+mod resume;
+mod shutdown;
+mod store;
+mod trust;
 mod verifier;
+mod webhook;
 mod ws;
 
 #[cfg(test)]
 mod tests;
 
+use assertion::AssertionResult;
 use async_tungstenite::tungstenite::Message;
+use attestation::{
+    Attestation, AttestationBody, AttestationConfig, AttestedHash, Attestor, PublicKeyInfo,
+};
+use auth::{AuthConfig, SessionRateLimiter};
 use axum::{
-    extract::{Query, State},
-    http::StatusCode,
+    extract::{Path as UrlPath, Query, State},
+    http::{HeaderMap, StatusCode},
     response::IntoResponse,
-    routing::get,
+    routing::{get, post},
     serve::ListenerExt,
     Router,
 };
 use bytes::BytesMut;
+use commitment::{CommitmentInfo, EncodedRanges};
+use connector::{Connector, Route};
+use egress::EgressConfig;
+use error::{ErrorCode, SessionError};
 use futures_util::SinkExt;
+use limits::LimitsConfig;
+use metrics::Metrics;
+use opening::OpeningRequest;
+use policy::{PolicyConfig, PolicySummary};
+use progress::{Phase, ProgressEvent};
+use proxy_guard::{PeerAddr, ProxyClients, ProxyConfig, ProxyLease, ProxyRejection};
 use rangeset::prelude::RangeSet;
+use resume::{ResumableSessions, SessionLink};
 use serde::{Deserialize, Serialize};
+use shutdown::Shutdown;
 use std::collections::HashMap;
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::path::Path;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use store::{ResultStore, SessionQuery, StoreConfig, StoredSession};
 use tlsn::transcript::PartialTranscript;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::sync::{oneshot, Mutex};
@@ -29,8 +67,10 @@ use tokio::time::timeout;
 use tokio_util::compat::FuturesAsyncReadCompatExt;
 use tower_http::cors::CorsLayer;
 use tracing::{debug, error, info, warn};
+use trust::{TrustConfig, TrustStore};
 use uuid::Uuid;
 use verifier::verifier;
+use webhook::{WebhookConfig, WebhookQueue, WebhookQueueConfig};
 use ws::{TungsteniteStream, WsUpgrade};
 use ws_stream_tungstenite::WsStream;
 
@@ -45,7 +85,11 @@ async fn main() {
         .init();
 
     // Load configuration from YAML file
-    let config = Config::load(Path::new("config.yaml"));
+    let mut config = Config::load(Path::new("config.yaml")).expect("Invalid config file");
+    config
+        .limits
+        .apply_env(|name| std::env::var(name).ok())
+        .expect("Invalid limits override");
     info!(
         "Webhook configurations loaded: {} endpoints",
         config.webhooks.len()
@@ -55,20 +99,43 @@ async fn main() {
     }
 
     // Create application state with session storage and config
-    let app_state = Arc::new(AppState {
-        sessions: Arc::new(Mutex::new(HashMap::new())),
-        config: Arc::new(config),
-    });
+    let app_state = Arc::new(AppState::new(config).expect("Failed to initialize server state"));
+    if let Some(attestor) = &app_state.attestor {
+        let public_key = attestor.public_key();
+        info!(
+            "Attestation signing enabled: {:?} key {}",
+            public_key.algorithm, public_key.key_id
+        );
+    }
+    if let Err(e) = app_state.webhooks.resume().await {
+        error!("Failed to resume queued webhook deliveries: {}", e);
+    }
+    #[cfg(unix)]
+    tokio::spawn(reload_trust_on_hangup(app_state.clone()));
 
     // Build router with routes
-    let app = Router::new()
+    let mut app = Router::new()
         .route("/health", get(health_handler))
+        .route("/ready", get(ready_handler))
         .route("/info", get(info_handler))
+        .route("/pubkey", get(pubkey_handler))
+        .route("/metrics", get(metrics_handler))
+        .route("/sessions", get(list_sessions_handler))
+        .route("/sessions/{id}", get(get_session_handler))
+        .route("/sessions/{id}/openings", post(open_commitment_handler))
         .route("/session", get(session_ws_handler))
         .route("/verifier", get(verifier_ws_handler))
-        .route("/proxy", get(proxy_ws_handler))
+        .route("/proxy", get(proxy_ws_handler));
+    let admin_enabled = match admin::router(&app_state) {
+        Some(admin_router) => {
+            app = app.merge(admin_router);
+            true
+        }
+        None => false,
+    };
+    let app = app
         .layer(CorsLayer::permissive())
-        .with_state(app_state);
+        .with_state(app_state.clone());
 
     // Start server
     let port: u16 = std::env::var("PORT")
@@ -89,27 +156,96 @@ async fn main() {
 
     info!("Server listening on http://{}", addr);
     info!("Health endpoint: http://{}/health", addr);
+    info!("Readiness endpoint: http://{}/ready", addr);
     info!("Info endpoint: http://{}/info", addr);
+    info!("Public key endpoint: http://{}/pubkey", addr);
+    info!("Metrics endpoint: http://{}/metrics", addr);
+    info!("Session results endpoint: http://{}/sessions/<id>", addr);
     info!("Session WebSocket endpoint: ws://{}/session", addr);
     info!(
         "Verifier WebSocket endpoint: ws://{}/verifier?sessionId=<id>",
         addr
     );
     info!("Proxy WebSocket endpoint: ws://{}/proxy?token=<host>", addr);
+    if admin_enabled {
+        info!("Admin endpoints: http://{}/admin/...", addr);
+    }
 
-    axum::serve(listener, app)
-        .await
-        .expect("Server error");
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(drain_on_shutdown(app_state))
+    .await
+    .expect("Server error");
+    info!("Server stopped");
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+// Resolves once a shutdown signal arrived and running sessions and webhook
+// deliveries have drained, or the drain deadline passed. The server keeps
+// serving /verifier and /proxy until then, as running sessions need them.
+async fn drain_on_shutdown(state: Arc<AppState>) {
+    let signal = shutdown::signal().await;
+    state.shutdown.start_draining();
+    let drain_timeout = state.config.limits.drain_timeout();
+    let deadline = Instant::now() + drain_timeout;
+
+    // Registrations check the draining flag under the sessions lock, so once
+    // it is taken here every admitted session is already tracked
+    drop(state.sessions.lock().await);
+    info!(
+        "Received {}, draining {} active sessions (up to {:?})",
+        signal,
+        state.shutdown.active(),
+        drain_timeout
+    );
+
+    let drain = async {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if timeout(remaining, state.shutdown.drained()).await.is_err() {
+            warn!(
+                "Drain deadline passed with {} sessions still active",
+                state.shutdown.active()
+            );
+        }
+        let pending = state
+            .webhooks
+            .flush(deadline.saturating_duration_since(Instant::now()))
+            .await;
+        if pending > 0 {
+            warn!("Shutting down with {} webhook deliveries pending", pending);
+        }
+    };
+    tokio::select! {
+        _ = drain => info!("Drained, shutting down"),
+        signal = shutdown::signal() => {
+            warn!("Received {} again, shutting down without draining", signal)
+        }
+    }
+}
+
+// Re-reads the trusted CA bundles on every SIGHUP
+#[cfg(unix)]
+async fn reload_trust_on_hangup(state: Arc<AppState>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = signal(SignalKind::hangup()).expect("Failed to listen for SIGHUP");
+    while hangup.recv().await.is_some() {
+        match state.trust.reload() {
+            Ok(()) => info!("Received SIGHUP, reloaded trusted root certificates"),
+            Err(e) => error!("Failed to reload trusted root certificates: {}", e),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "UPPERCASE")]
 pub(crate) enum HandlerType {
     Sent,
     Recv,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub(crate) enum HandlerPart {
     StartLine,
@@ -122,7 +258,7 @@ pub(crate) enum HandlerPart {
     All,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "UPPERCASE")]
 pub(crate) enum HashAlgorithm {
     Blake3,
@@ -130,6 +266,11 @@ pub(crate) enum HashAlgorithm {
     Keccak256,
 }
 
+impl HashAlgorithm {
+    /// Every algorithm HASH handlers may use
+    pub(crate) const ALL: [Self; 3] = [Self::Blake3, Self::Sha256, Self::Keccak256];
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 #[serde(tag = "kind", rename_all = "UPPERCASE")]
 pub(crate) enum HandlerAction {
@@ -137,6 +278,9 @@ pub(crate) enum HandlerAction {
     Hash {
         algorithm: HashAlgorithm,
     },
+    /// Committed with an encoding commitment; the result value is its
+    /// Merkle root
+    Encoding,
 }
 
 impl Default for HandlerAction {
@@ -145,6 +289,30 @@ impl Default for HandlerAction {
     }
 }
 
+/// Optional handler parameters, as sent by the prover (see `HeadersHandler`
+/// and `BodyHandler` in the plugin SDK).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct HandlerParams {
+    /// Header name for `HEADERS` handlers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) hide_key: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) hide_value: Option<bool>,
+    /// Body content type, e.g. `json`
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub(crate) content_type: Option<String>,
+    /// Dotted JSON path for `BODY` handlers, e.g. `accounts.0.balance`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) regex: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) flags: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub(crate) struct Handler {
     #[serde(rename = "type")]
@@ -152,6 +320,8 @@ pub(crate) struct Handler {
     pub(crate) part: HandlerPart,
     #[serde(default)]
     pub(crate) action: HandlerAction,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) params: Option<HandlerParams>,
 }
 
 // Session data structure (without handlers - they come later with ranges)
@@ -161,6 +331,30 @@ struct SessionConfig {
     max_recv_data: usize,
     #[serde(rename = "maxSentData")]
     max_sent_data: usize,
+    /// Unix timestamp (seconds) of the `register` message
+    #[serde(rename = "registeredAt")]
+    registered_at: u64,
+    /// Server name the client declared at `register`, if any. It names the
+    /// server of the first TLS connection.
+    #[serde(rename = "serverName", skip_serializing_if = "Option::is_none")]
+    server_name: Option<String>,
+    /// Port of the declared server, if not 443
+    #[serde(
+        rename = "serverPort",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    server_port: Option<u16>,
+    /// Tenant of the API key the session registered with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tenant: Option<String>,
+    /// Number of TLS connections the prover opens for the session
+    #[serde(default = "default_connections")]
+    connections: usize,
+}
+
+fn default_connections() -> usize {
+    1
 }
 
 // Range with handler metadata
@@ -169,6 +363,9 @@ pub(crate) struct RangeWithHandler {
     pub(crate) start: usize,
     pub(crate) end: usize,
     pub(crate) handler: Handler,
+    /// Index of the TLS connection whose transcript the range is in
+    #[serde(default)]
+    pub(crate) connection: usize,
 }
 
 // Reveal configuration sent before prover.reveal()
@@ -178,32 +375,75 @@ struct RevealConfig {
     recv: Vec<RangeWithHandler>,
 }
 
+impl RevealConfig {
+    /// The ranges in the transcript of TLS connection `connection`
+    fn for_connection(&self, connection: usize) -> RevealConfig {
+        let ranges = |ranges: &[RangeWithHandler]| {
+            ranges
+                .iter()
+                .filter(|range| range.connection == connection)
+                .cloned()
+                .collect()
+        };
+        RevealConfig {
+            sent: ranges(&self.sent),
+            recv: ranges(&self.recv),
+        }
+    }
+}
+
 // Handler result with revealed value
-#[derive(Debug, Clone, Serialize)]
-struct HandlerResult {
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct HandlerResult {
     #[serde(flatten)]
-    handler: Handler,
-    value: String,
+    pub(crate) handler: Handler,
+    pub(crate) value: String,
+    /// Typed value decoded from `value` for handlers with a JSON `path`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) parsed: Option<serde_json::Value>,
+    /// Key the JSON value was found under, when the key was revealed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) key: Option<String>,
+    /// Index of the TLS connection the value was revealed from
+    #[serde(default)]
+    pub(crate) connection: usize,
+    /// Verified server name of that connection
+    #[serde(rename = "serverName", default)]
+    pub(crate) server_name: String,
+    /// Whether the range's HTTP part label was checked against the
+    /// transcript; only false under `allow_unverified_labels`
+    #[serde(rename = "labelVerified", default)]
+    pub(crate) label_verified: bool,
 }
 
 // Verification result containing handler results or an error
 #[derive(Debug, Clone, Serialize)]
 struct VerificationResult {
     results: Vec<HandlerResult>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    assertions: Vec<AssertionResult>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    commitments: Vec<CommitmentInfo>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    error: Option<String>,
+    attestation: Option<Attestation>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<SessionError>,
 }
 
 // Type aliases for WebSocket senders
 type ProverSocketSender = oneshot::Sender<TungsteniteStream>;
-type ProxySocketSender = oneshot::Sender<TungsteniteStream>;
 
 // Session data stored in AppState
+#[derive(Default)]
 pub(crate) struct SessionData {
-    pub(crate) prover_socket_tx: Option<ProverSocketSender>,
-    /// In proxy mode, the verifier task stores a sender here so the /proxy
-    /// endpoint can route the prover's proxy WebSocket to it.
-    pub(crate) proxy_socket_tx: Option<ProxySocketSender>,
+    /// One sender per TLS connection, taken when the prover opens it
+    pub(crate) prover_socket_txs: Vec<Option<ProverSocketSender>>,
+    /// Tenant of the API key the session registered with
+    pub(crate) tenant: Option<String>,
+    /// What `GET /admin/sessions` shows of the session
+    pub(crate) overview: admin::SessionOverview,
+    /// Cancels the verifier task; taken by `DELETE /admin/sessions/{id}`
+    pub(crate) cancel_tx: Option<oneshot::Sender<()>>,
 }
 
 // Application state for sharing data between handlers
@@ -211,6 +451,107 @@ pub(crate) struct SessionData {
 pub(crate) struct AppState {
     pub(crate) sessions: Arc<Mutex<HashMap<String, SessionData>>>,
     pub(crate) config: Arc<Config>,
+    /// Signing key for attestations, if configured
+    pub(crate) attestor: Option<Arc<Attestor>>,
+    /// Persistent store for completed sessions, if configured
+    pub(crate) store: Option<Arc<ResultStore>>,
+    /// Outgoing webhook delivery queue
+    pub(crate) webhooks: Arc<WebhookQueue>,
+    /// Prometheus metrics served on /metrics
+    pub(crate) metrics: Arc<Metrics>,
+    /// Per-tenant registration rate windows
+    pub(crate) session_rates: Arc<SessionRateLimiter>,
+    /// Per-client-IP usage of the /proxy bridge
+    pub(crate) proxy_clients: Arc<ProxyClients>,
+    /// Draining state and sessions in flight, for graceful shutdown
+    pub(crate) shutdown: Arc<Shutdown>,
+    /// Client side of sessions, for resuming dropped session sockets
+    pub(crate) resumable: Arc<ResumableSessions>,
+    /// Trusted root certificates, reloadable at runtime
+    pub(crate) trust: Arc<TrustStore>,
+    /// Outbound connections for /proxy and Proxy-mode verification
+    pub(crate) connector: Arc<Connector>,
+}
+
+impl AppState {
+    /// Build the application state from a loaded config
+    pub(crate) fn new(config: Config) -> Result<Self, eyre::ErrReport> {
+        config.limits.validate()?;
+        config.proxy.validate()?;
+        config.webhook_queue.validate()?;
+        for (server_name, policy) in &config.policies {
+            policy.validate(server_name)?;
+        }
+        for (server_name, webhook) in &config.webhooks {
+            webhook.validate(server_name)?;
+        }
+        if let Some(admin) = &config.admin {
+            admin.validate()?;
+        }
+        if let Some(egress) = &config.egress {
+            egress.validate()?;
+        }
+        if let Some(auth) = &config.auth {
+            auth.validate()?;
+        }
+        let attestor = config
+            .attestation
+            .as_ref()
+            .map(Attestor::from_config)
+            .transpose()?
+            .map(Arc::new);
+        let store = config
+            .store
+            .as_ref()
+            .map(ResultStore::open)
+            .transpose()?
+            .map(Arc::new);
+        let trust = Arc::new(TrustStore::new(config.trust.clone())?);
+
+        let config = Arc::new(config);
+        let metrics = Arc::new(
+            Metrics::new().with_server_names(
+                config
+                    .policies
+                    .keys()
+                    .chain(config.webhooks.keys())
+                    .map(String::as_str)
+                    .filter(|server_name| *server_name != "*"),
+            ),
+        );
+        let connector = Arc::new(Connector::new(
+            config.proxy.clone(),
+            config.egress.clone(),
+            metrics.clone(),
+        ));
+        let webhooks = Arc::new(WebhookQueue::new(
+            config.clone(),
+            store.clone(),
+            metrics.clone(),
+        ));
+
+        Ok(Self {
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+            config,
+            attestor,
+            store,
+            webhooks,
+            metrics,
+            session_rates: Arc::new(SessionRateLimiter::default()),
+            proxy_clients: Arc::new(ProxyClients::default()),
+            shutdown: Arc::new(Shutdown::default()),
+            resumable: Arc::new(ResumableSessions::default()),
+            trust,
+            connector,
+        })
+    }
+}
+
+// Query parameters for session WebSocket connection
+#[derive(Debug, Deserialize)]
+pub(crate) struct SessionWsQuery {
+    #[serde(rename = "apiKey")]
+    api_key: Option<String>,
 }
 
 // Query parameters for verifier WebSocket connection
@@ -218,17 +559,26 @@ pub(crate) struct AppState {
 struct VerifierQuery {
     #[serde(rename = "sessionId")]
     session_id: String,
+    /// Index of the session's TLS connection the socket is for
+    #[serde(default)]
+    connection: usize,
+}
+
+// Query parameters for listing stored sessions
+#[derive(Debug, Deserialize)]
+struct SessionsQuery {
+    server_name: Option<String>,
+    since: Option<u64>,
+    limit: Option<usize>,
+    offset: Option<usize>,
 }
 
 // Query parameters for proxy WebSocket connection
 // Supports both `token` (notary.pse.dev compatible) and `host` (legacy)
-// In proxy mode, `session_id` routes the WS to the verifier task.
 #[derive(Debug, Deserialize)]
 struct ProxyQuery {
     #[serde(alias = "host")]
     token: String,
-    #[serde(rename = "sessionId")]
-    session_id: Option<String>,
 }
 
 // ============================================================================
@@ -247,12 +597,29 @@ enum ClientMessage {
         max_sent_data: usize,
         #[serde(rename = "sessionData", default)]
         session_data: HashMap<String, String>,
+        /// Optional server name, so policies can be enforced before MPC starts
+        #[serde(rename = "serverName", default)]
+        server_name: Option<String>,
+        /// Port of the declared server, dialled by the verifier in Proxy mode
+        #[serde(rename = "serverPort", default)]
+        server_port: Option<u16>,
+        /// Number of TLS connections the prover will open
+        #[serde(default = "default_connections")]
+        connections: usize,
     },
     /// Reveal configuration - sent with ranges and handlers
     RevealConfig {
         sent: Vec<RangeWithHandler>,
         recv: Vec<RangeWithHandler>,
     },
+    /// Reattaches a new socket to a registered session - sent instead of
+    /// register after the previous session socket dropped
+    Resume {
+        #[serde(rename = "sessionId")]
+        session_id: String,
+        #[serde(rename = "resumeToken")]
+        resume_token: String,
+    },
 }
 
 /// Outgoing messages to client (extension)
@@ -263,14 +630,39 @@ enum ServerMessage {
     SessionRegistered {
         #[serde(rename = "sessionId")]
         session_id: String,
+        /// Token for resuming the session from another socket
+        #[serde(rename = "resumeToken")]
+        resume_token: String,
+    },
+    /// Session resumed on a new socket
+    SessionResumed {
+        #[serde(rename = "sessionId")]
+        session_id: String,
+        /// Whether the client still has to send its reveal config
+        #[serde(rename = "awaitingRevealConfig")]
+        awaiting_reveal_config: bool,
+    },
+    /// Session reached a new phase
+    Progress {
+        #[serde(flatten)]
+        event: ProgressEvent,
     },
     /// Session completed with results
     SessionCompleted {
         results: Vec<HandlerResult>,
+        /// Outcomes of the policy's assertions, if any
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        assertions: Vec<AssertionResult>,
+        /// Transcript commitments the prover made
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        commitments: Vec<CommitmentInfo>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        attestation: Option<Attestation>,
     },
-    /// Error occurred
+    /// Error occurred, with a stable code clients can branch on
     Error {
-        message: String,
+        #[serde(flatten)]
+        error: SessionError,
     },
 }
 
@@ -278,50 +670,88 @@ enum ServerMessage {
 // Webhook Types
 // ============================================================================
 
-/// Webhook configuration for a specific server
-#[derive(Debug, Clone, Deserialize)]
-pub(crate) struct WebhookConfig {
-    pub(crate) url: String,
-    #[serde(default)]
-    pub(crate) headers: HashMap<String, String>,
-}
-
 /// Application configuration loaded from YAML
 #[derive(Debug, Clone, Deserialize, Default)]
 pub(crate) struct Config {
     #[serde(default)]
     pub(crate) webhooks: HashMap<String, WebhookConfig>,
+    /// Caps on queued and dead-lettered webhook deliveries
+    #[serde(default)]
+    pub(crate) webhook_queue: WebhookQueueConfig,
+    /// Attestation signing key; attestations are disabled when absent
+    #[serde(default)]
+    pub(crate) attestation: Option<AttestationConfig>,
+    /// Result store; completed sessions are not persisted when absent
+    #[serde(default)]
+    pub(crate) store: Option<StoreConfig>,
+    /// Admin API credentials; `/admin` routes are disabled when absent
+    #[serde(default)]
+    pub(crate) admin: Option<admin::AdminConfig>,
+    /// Verification policies keyed by server name
+    #[serde(default)]
+    pub(crate) policies: HashMap<String, PolicyConfig>,
+    /// Timeouts and server-wide session limits
+    #[serde(default)]
+    pub(crate) limits: LimitsConfig,
+    /// API keys and tenant quotas; `/session` is open when absent
+    #[serde(default)]
+    pub(crate) auth: Option<AuthConfig>,
+    /// Target rules and per-client limits for the /proxy bridge
+    #[serde(default)]
+    pub(crate) proxy: ProxyConfig,
+    /// Root certificates server certificates are verified against
+    #[serde(default)]
+    pub(crate) trust: TrustConfig,
+    /// Forward proxy for upstream connections; servers are dialled directly
+    /// when absent
+    #[serde(default)]
+    pub(crate) egress: Option<EgressConfig>,
+    /// Accept ranges whose HTTP part label cannot be verified, reporting them
+    /// with `labelVerified: false`; such sessions fail when unset
+    #[serde(default)]
+    pub(crate) allow_unverified_labels: bool,
 }
 
 impl Config {
-    /// Load configuration from YAML file, returns default if file doesn't exist
-    fn load(path: &Path) -> Self {
+    /// Load configuration from YAML file, returns default if file doesn't
+    /// exist. A file that exists but cannot be read or parsed is an error.
+    pub(crate) fn load(path: &Path) -> Result<Self, eyre::ErrReport> {
         match std::fs::read_to_string(path) {
-            Ok(contents) => match serde_yaml_ng::from_str(&contents) {
-                Ok(config) => {
-                    info!("Loaded config from {:?}", path);
-                    config
-                }
-                Err(e) => {
-                    warn!("Failed to parse config file {:?}: {}", path, e);
-                    Self::default()
-                }
-            },
-            Err(_) => {
+            Ok(contents) => {
+                let config = serde_yaml_ng::from_str(&contents)
+                    .map_err(|e| eyre::eyre!("Failed to parse config file {:?}: {}", path, e))?;
+                info!("Loaded config from {:?}", path);
+                Ok(config)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
                 info!("No config file found at {:?}, using defaults", path);
-                Self::default()
+                Ok(Self::default())
             }
+            Err(e) => Err(eyre::eyre!("Failed to read config file {:?}: {}", path, e)),
         }
     }
 
     /// Get webhook configuration for a server name (with wildcard fallback)
     fn get_webhook(&self, server_name: &str) -> Option<&WebhookConfig> {
-        self.webhooks
-            .get(server_name)
-            .or_else(|| self.webhooks.get("*"))
+        match_server_name(&self.webhooks, server_name)
+    }
+
+    /// Get the verification policy for a server name (with wildcard fallback)
+    pub(crate) fn get_policy(&self, server_name: &str) -> Option<&PolicyConfig> {
+        match_server_name(&self.policies, server_name)
     }
 }
 
+/// Looks up a server name in a config map keyed by server name.
+///
+/// Tries the exact name, then the catch-all `"*"`.
+pub(crate) fn match_server_name<'a, T>(
+    entries: &'a HashMap<String, T>,
+    server_name: &str,
+) -> Option<&'a T> {
+    entries.get(server_name).or_else(|| entries.get("*"))
+}
+
 /// Webhook payload sent to configured endpoints
 #[derive(Debug, Serialize)]
 struct WebhookPayload {
@@ -329,12 +759,46 @@ struct WebhookPayload {
     server_name: String,
     /// Handler results with revealed values
     results: Vec<HandlerResult>,
+    /// Outcomes of the policy's assertions, if any
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    assertions: Vec<AssertionResult>,
+    /// Transcript commitments the prover made
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    commitments: Vec<CommitmentInfo>,
     /// The reveal configuration (ranges + handlers)
     config: RevealConfigForWebhook,
     /// Session metadata
     session: SessionInfo,
     /// Redacted transcripts (bytes outside revealed ranges replaced with 0x00)
     transcript: RedactedTranscript,
+    /// Signed attestation over the results, if signing is enabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    attestation: Option<Attestation>,
+    /// Server name and redacted transcript of every TLS connection, for
+    /// sessions with more than one; `server_name` and `transcript` above are
+    /// the first connection's
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    connections: Vec<ConnectionTranscript>,
+}
+
+/// Redacted transcript of one of a session's TLS connections
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct ConnectionTranscript {
+    index: usize,
+    server_name: String,
+    transcript: RedactedTranscript,
+}
+
+/// Webhook payload for a failed session, sent if the webhook sets
+/// `notify_failures`
+#[derive(Debug, Serialize)]
+struct FailedSessionWebhookPayload {
+    /// Verified server name, or the one declared at registration
+    server_name: String,
+    /// Session metadata
+    session: SessionInfo,
+    /// Why the session failed
+    error: SessionError,
 }
 
 /// Reveal config for webhook (same structure, different purpose)
@@ -348,13 +812,32 @@ struct RevealConfigForWebhook {
 #[derive(Debug, Serialize)]
 struct SessionInfo {
     id: String,
+    /// Tenant of the API key the session registered with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tenant: Option<String>,
     #[serde(flatten)]
     data: HashMap<String, String>,
 }
 
+impl SessionInfo {
+    /// Session info for the webhook. A client-supplied `tenant` session data
+    /// key is dropped when the session has an authenticated tenant.
+    fn new(id: &str, tenant: Option<String>, data: &HashMap<String, String>) -> Self {
+        let mut data = data.clone();
+        if tenant.is_some() {
+            data.remove("tenant");
+        }
+        Self {
+            id: id.to_string(),
+            tenant,
+            data,
+        }
+    }
+}
+
 /// Redacted transcript data - bytes outside revealed ranges are zeroed out
-#[derive(Debug, Serialize)]
-struct RedactedTranscript {
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct RedactedTranscript {
     /// Redacted sent data (request) - unrevealed bytes replaced with 0x00
     sent: String,
     /// Redacted received data (response) - unrevealed bytes replaced with 0x00
@@ -395,39 +878,297 @@ impl RedactedTranscript {
     }
 }
 
-// Health check endpoint handler
-async fn health_handler() -> impl IntoResponse {
-    "ok"
+/// Current unix timestamp in seconds
+pub(crate) fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+// Health check endpoint handler; reports 503 while draining so load
+// balancers stop routing new sessions here
+pub(crate) async fn health_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    if state.shutdown.is_draining() {
+        (StatusCode::SERVICE_UNAVAILABLE, "draining")
+    } else {
+        (StatusCode::OK, "ok")
+    }
+}
+
+/// Readiness response structure
+#[derive(Debug, Serialize)]
+struct ReadyResponse {
+    /// Whether a new session would currently be admitted
+    ready: bool,
+    /// Whether the server is shutting down
+    draining: bool,
+    /// Sessions currently held
+    active_sessions: usize,
+    /// Server-wide session limit; unlimited when null
+    max_concurrent_sessions: Option<usize>,
+}
+
+/// Readiness endpoint handler - 503 while draining or at capacity
+pub(crate) async fn ready_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let active_sessions = state.sessions.lock().await.len();
+    let max_concurrent_sessions = state.config.limits.max_concurrent_sessions;
+    let draining = state.shutdown.is_draining();
+    let at_capacity = matches!(max_concurrent_sessions, Some(max) if active_sessions >= max);
+    let ready = !draining && !at_capacity;
+
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (
+        status,
+        axum::Json(ReadyResponse {
+            ready,
+            draining,
+            active_sessions,
+            max_concurrent_sessions,
+        }),
+    )
 }
 
 /// Info response structure
 #[derive(Debug, Serialize)]
-struct InfoResponse {
+struct InfoResponse<'a> {
     /// Package version from Cargo.toml
     version: &'static str,
     /// Git commit hash (from GIT_HASH env var, set by CI)
     git_hash: String,
     /// TLSNotary library version
     tlsn_version: &'static str,
+    /// Verification modes a prover may pick
+    modes: [&'static str; 2],
+    /// Algorithms accepted for HASH handlers
+    hash_algorithms: [HashAlgorithm; 3],
+    /// Server-wide timeouts and data limits
+    limits: &'a LimitsConfig,
+    /// Whether `/session` requires an API key
+    api_key_required: bool,
+    /// Whether ranges with unverifiable part labels are accepted
+    allow_unverified_labels: bool,
+    /// Verification policies, sorted by server name
+    policies: Vec<PolicySummary<'a>>,
+    /// Key signing attestations; absent when attestations are disabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    attestation_key: Option<PublicKeyInfo>,
 }
 
-/// Info endpoint handler - returns server information as JSON
-pub(crate) async fn info_handler() -> impl IntoResponse {
+/// Info endpoint handler - returns server information and capabilities as
+/// JSON, so clients can negotiate before registering
+pub(crate) async fn info_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
     let git_hash = std::env::var("GIT_HASH").unwrap_or_else(|_| "dev".to_string());
 
+    let mut policies: Vec<_> = state
+        .config
+        .policies
+        .iter()
+        .map(|(server_name, policy)| policy.summary(server_name))
+        .collect();
+    policies.sort_by_key(|policy| policy.server_name);
+
     axum::Json(InfoResponse {
         version: env!("CARGO_PKG_VERSION"),
         git_hash,
         tlsn_version: "0.1.0-alpha.15",
+        modes: ["Mpc", "Proxy"],
+        hash_algorithms: HashAlgorithm::ALL,
+        limits: &state.config.limits,
+        api_key_required: state.config.auth.is_some(),
+        allow_unverified_labels: state.config.allow_unverified_labels,
+        policies,
+        attestation_key: state
+            .attestor
+            .as_ref()
+            .map(|attestor| attestor.public_key()),
     })
+    .into_response()
+}
+
+/// Public key endpoint handler - returns the attestation verifying key
+pub(crate) async fn pubkey_handler(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    match &state.attestor {
+        Some(attestor) => Ok(axum::Json(attestor.public_key())),
+        None => Err((
+            StatusCode::NOT_FOUND,
+            "Attestation signing is not configured".to_string(),
+        )),
+    }
+}
+
+/// Metrics endpoint handler - Prometheus text exposition format
+pub(crate) async fn metrics_handler(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let active = state.sessions.lock().await.len();
+    state.metrics.set_active_sessions(active);
+
+    let body = state.metrics.render().map_err(|e| {
+        error!("Failed to render metrics: {}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+    })?;
+    Ok((
+        [(
+            axum::http::header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        body,
+    ))
+}
+
+/// Page of stored sessions returned by `GET /sessions`
+#[derive(Debug, Serialize)]
+struct SessionsPage {
+    sessions: Vec<StoredSession>,
+    /// Offset of the next page, absent on the last page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_offset: Option<usize>,
+}
+
+/// Tenant of the API key sent with a request, or `None` when no `auth`
+/// section is configured. Requests without a valid key get a 401.
+fn authenticate_request(
+    state: &AppState,
+    headers: &HeaderMap,
+    query_key: Option<&str>,
+) -> Result<Option<String>, (StatusCode, String)> {
+    let Some(auth) = &state.config.auth else {
+        return Ok(None);
+    };
+    let tenant = auth::request_api_key(headers, query_key)
+        .and_then(|key| auth.authenticate(key))
+        .ok_or_else(|| {
+            warn!("Rejected request without a valid API key");
+            (StatusCode::UNAUTHORIZED, "Invalid API key".to_string())
+        })?;
+    Ok(Some(tenant.to_string()))
+}
+
+fn store_or_404(state: &AppState) -> Result<Arc<ResultStore>, (StatusCode, String)> {
+    state.store.clone().ok_or((
+        StatusCode::NOT_FOUND,
+        "Result store is not configured".to_string(),
+    ))
+}
+
+/// Reads a stored session, or 404s if there is none with this id, or it
+/// belongs to a tenant other than `tenant`
+async fn load_session(
+    state: &AppState,
+    session_id: &str,
+    tenant: Option<&str>,
+) -> Result<StoredSession, (StatusCode, String)> {
+    let store = store_or_404(state)?;
+    let lookup_id = session_id.to_string();
+    let session = tokio::task::spawn_blocking(move || store.get(&lookup_id))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map_err(|e| {
+            error!("[{}] Failed to read stored session: {}", session_id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+        })?;
+
+    session
+        .filter(|session| tenant.is_none() || session.tenant.as_deref() == tenant)
+        .ok_or((
+            StatusCode::NOT_FOUND,
+            format!("Session not found: {}", session_id),
+        ))
+}
+
+/// Stored session lookup - returns the persisted result for a session id
+pub(crate) async fn get_session_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    UrlPath(session_id): UrlPath<String>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let tenant = authenticate_request(&state, &headers, None)?;
+    load_session(&state, &session_id, tenant.as_deref())
+        .await
+        .map(axum::Json)
+}
+
+/// Hash commitment opening - checks a claimed plaintext and blinder against
+/// the commitment stored with a session
+pub(crate) async fn open_commitment_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    UrlPath(session_id): UrlPath<String>,
+    axum::Json(request): axum::Json<OpeningRequest>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let tenant = authenticate_request(&state, &headers, None)?;
+    let session = load_session(&state, &session_id, tenant.as_deref()).await?;
+    opening::verify(&session.commitments, &request).map(axum::Json)
+}
+
+/// Stored session listing - filtered by server name and completion time,
+/// and scoped to the caller's tenant
+pub(crate) async fn list_sessions_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<SessionsQuery>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let tenant = authenticate_request(&state, &headers, None)?;
+    let store = store_or_404(&state)?;
+    let limit = query
+        .limit
+        .unwrap_or(store::DEFAULT_PAGE_SIZE)
+        .clamp(1, store::MAX_PAGE_SIZE);
+    let offset = query.offset.unwrap_or(0);
+
+    // Fetch one extra row to know whether another page follows
+    let store_query = SessionQuery {
+        tenant,
+        server_name: query.server_name,
+        since: query.since,
+        limit: limit + 1,
+        offset,
+    };
+    let mut sessions = tokio::task::spawn_blocking(move || store.list(&store_query))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map_err(|e| {
+            error!("Failed to list stored sessions: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+        })?;
+
+    let next_offset = (sessions.len() > limit).then_some(offset + limit);
+    sessions.truncate(limit);
+
+    Ok(axum::Json(SessionsPage {
+        sessions,
+        next_offset,
+    }))
 }
 
 // WebSocket session handler for extension
 pub(crate) async fn session_ws_handler(
     ws: WsUpgrade,
+    headers: HeaderMap,
+    PeerAddr(peer): PeerAddr,
+    Query(query): Query<SessionWsQuery>,
     State(state): State<Arc<AppState>>,
-) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| handle_session_websocket(socket, state))
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    if state.shutdown.is_draining() {
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            shutdown::DRAINING_MESSAGE.to_string(),
+        ));
+    }
+
+    // Resolve the tenant before upgrading, so unauthenticated clients never
+    // get a session socket
+    let tenant = authenticate_request(&state, &headers, query.api_key.as_deref())?;
+
+    let remote_ip = state.config.proxy.client_ip(&headers, peer);
+    Ok(ws.on_upgrade(move |socket| handle_session_websocket(socket, state, tenant, remote_ip)))
 }
 
 /// Helper to send typed server messages
@@ -445,27 +1186,36 @@ async fn send_server_message(socket: &mut TungsteniteStream, message: &ServerMes
 }
 
 /// Helper to send error message
-async fn send_error(socket: &mut TungsteniteStream, message: &str) {
-    let _ = send_server_message(socket, &ServerMessage::Error {
-        message: message.to_string(),
-    })
-    .await;
+async fn send_error(socket: &mut TungsteniteStream, error: SessionError) {
+    let _ = send_server_message(socket, &ServerMessage::Error { error }).await;
 }
 
 // Handle the session WebSocket connection with typed message protocol
-async fn handle_session_websocket(mut socket: TungsteniteStream, state: Arc<AppState>) {
+async fn handle_session_websocket(
+    mut socket: TungsteniteStream,
+    state: Arc<AppState>,
+    tenant: Option<String>,
+    remote_ip: Option<IpAddr>,
+) {
     use futures_util::StreamExt;
 
     // Generate session ID upfront (but don't send yet - wait for register)
     let session_id = Uuid::new_v4().to_string();
-    info!("[{}] New session WebSocket connected", session_id);
+    match &tenant {
+        Some(tenant) => info!(
+            "[{}] New session WebSocket connected for tenant {}",
+            session_id, tenant
+        ),
+        None => info!("[{}] New session WebSocket connected", session_id),
+    }
 
     // Wait for "register" message first
     let register_msg = match socket.next().await {
         Some(Ok(Message::Text(text))) => text,
         Some(Ok(msg)) => {
             error!("[{}] Expected text message, got: {:?}", session_id, msg);
-            send_error(&mut socket, "Expected text message").await;
+            let error = SessionError::new(ErrorCode::InvalidMessage, "Expected text message");
+            send_error(&mut socket, error).await;
             return;
         }
         Some(Err(e)) => {
@@ -483,72 +1233,206 @@ async fn handle_session_websocket(mut socket: TungsteniteStream, state: Arc<AppS
         Ok(msg) => msg,
         Err(e) => {
             error!("[{}] Failed to parse message: {}", session_id, e);
-            send_error(&mut socket, &format!("Invalid message format: {}", e)).await;
+            let msg = format!("Invalid message format: {}", e);
+            send_error(
+                &mut socket,
+                SessionError::new(ErrorCode::InvalidMessage, msg),
+            )
+            .await;
             return;
         }
     };
 
-    // Expect "register" message type
-    let (max_recv_data, max_sent_data, session_data) = match client_msg {
-        ClientMessage::Register {
-            max_recv_data,
-            max_sent_data,
-            session_data,
-        } => (max_recv_data, max_sent_data, session_data),
-        _ => {
-            error!("[{}] Expected 'register' message type", session_id);
-            send_error(&mut socket, "Expected 'register' message type").await;
-            return;
-        }
-    };
+    // Expect "register" message type, or "resume" for a registered session
+    let (max_recv_data, max_sent_data, session_data, server_name, server_port, connections) =
+        match client_msg {
+            ClientMessage::Register {
+                max_recv_data,
+                max_sent_data,
+                session_data,
+                server_name,
+                server_port,
+                connections,
+            } => (
+                max_recv_data,
+                max_sent_data,
+                session_data,
+                server_name,
+                server_port,
+                connections,
+            ),
+            ClientMessage::Resume {
+                session_id,
+                resume_token,
+            } => {
+                resume_session_websocket(socket, state, tenant, session_id, resume_token).await;
+                return;
+            }
+            _ => {
+                error!("[{}] Expected 'register' message type", session_id);
+                let msg = "Expected 'register' message type";
+                send_error(
+                    &mut socket,
+                    SessionError::new(ErrorCode::InvalidMessage, msg),
+                )
+                .await;
+                return;
+            }
+        };
 
     info!(
-        "[{}] Received registration: maxRecvData={}, maxSentData={}, sessionData keys: {:?}",
+        "[{}] Received registration: maxRecvData={}, maxSentData={}, connections={}, sessionData keys: {:?}",
         session_id,
         max_recv_data,
         max_sent_data,
+        connections,
         session_data.keys().collect::<Vec<_>>()
     );
 
+    if let Err(err) = state.config.limits.admit_connections(connections) {
+        error!("[{}] ❌ {}", session_id, err);
+        state
+            .metrics
+            .session_failed(server_name.as_deref(), "connections");
+        send_error(&mut socket, err).await;
+        return;
+    }
+    if let Err(msg) = check_server_port(server_name.as_deref(), server_port) {
+        error!("[{}] ❌ {}", session_id, msg);
+        send_error(
+            &mut socket,
+            SessionError::new(ErrorCode::InvalidMessage, msg),
+        )
+        .await;
+        return;
+    }
+
+    // Apply the server-wide data limits, which may lower the requested ones
+    let requested = (max_sent_data, max_recv_data);
+    let (max_sent_data, max_recv_data) = match state
+        .config
+        .limits
+        .admit_data_limits(max_sent_data, max_recv_data)
+    {
+        Ok(limits) => limits,
+        Err(err) => {
+            error!("[{}] ❌ {}", session_id, err);
+            state
+                .metrics
+                .session_failed(server_name.as_deref(), "data_limit");
+            send_error(&mut socket, err).await;
+            return;
+        }
+    };
+    if (max_sent_data, max_recv_data) != requested {
+        warn!(
+            "[{}] Clamped registration to server limits: maxRecvData={}, maxSentData={}",
+            session_id, max_recv_data, max_sent_data
+        );
+    }
+
+    // Enforce the policy for a declared server name before any MPC work
+    if let Some(server_name) = &server_name {
+        if let Some(policy) = state.config.get_policy(server_name) {
+            if let Err(msg) = policy
+                .check_allowed(server_name)
+                .and_then(|()| policy.check_limits(max_sent_data, max_recv_data))
+                .and_then(|()| server_port.map_or(Ok(()), |port| policy.check_port(port)))
+            {
+                error!("[{}] ❌ {}", session_id, msg);
+                state.metrics.session_failed(Some(server_name), "policy");
+                send_error(&mut socket, SessionError::new(ErrorCode::PolicyDenied, msg)).await;
+                return;
+            }
+        }
+    }
+
+    // Create channels for prover sockets, reveal config, and results
+    let (prover_socket_txs, prover_socket_rxs): (Vec<_>, Vec<_>) = (0..connections)
+        .map(|_| {
+            let (tx, rx) = oneshot::channel::<TungsteniteStream>();
+            (Some(tx), rx)
+        })
+        .unzip();
+    let (reveal_config_tx, reveal_config_rx) = oneshot::channel::<RevealConfig>();
+    let (result_tx, result_rx) = oneshot::channel::<VerificationResult>();
+    let (cancel_tx, cancel_rx) = oneshot::channel::<()>();
+    let registered_at = unix_timestamp();
+
+    // Store session data (so prover can connect), unless a quota is used up.
+    // The session counts towards a shutdown drain from here on.
+    let _session_guard = {
+        let mut sessions = state.sessions.lock().await;
+        if let Err((reason, msg)) = check_session_quotas(&state, &sessions, tenant.as_deref()) {
+            drop(sessions);
+            error!("[{}] ❌ {}", session_id, msg);
+            state.metrics.session_failed(server_name.as_deref(), reason);
+            let code = match reason {
+                "draining" => ErrorCode::Draining,
+                _ => ErrorCode::QuotaExceeded,
+            };
+            send_error(&mut socket, SessionError::new(code, msg)).await;
+            return;
+        }
+        sessions.insert(
+            session_id.clone(),
+            SessionData {
+                prover_socket_txs,
+                tenant: tenant.clone(),
+                overview: admin::SessionOverview {
+                    registered_at,
+                    remote_ip,
+                    server_name: server_name.clone(),
+                    max_sent_data,
+                    max_recv_data,
+                    connections,
+                    session_data_keys: {
+                        let mut keys: Vec<String> = session_data.keys().cloned().collect();
+                        keys.sort();
+                        keys
+                    },
+                },
+                cancel_tx: Some(cancel_tx),
+            },
+        );
+        state.shutdown.track()
+    };
+
+    // Make the session resumable from another socket should this one drop
+    let link = Arc::new(SessionLink::new(tenant.clone(), reveal_config_tx));
+    state.resumable.insert(&session_id, link.clone());
+
     // Send session_registered response
     if !send_server_message(
         &mut socket,
         &ServerMessage::SessionRegistered {
             session_id: session_id.clone(),
+            resume_token: link.token().to_string(),
         },
     )
     .await
     {
         error!("[{}] Failed to send session_registered", session_id);
+        state.resumable.remove(&session_id);
+        cleanup_session(&state, &session_id).await;
         return;
     }
 
     info!("[{}] Sent session_registered to client", session_id);
-
-    // Create channels for prover socket, reveal config, and results
-    let (prover_socket_tx, prover_socket_rx) = oneshot::channel::<TungsteniteStream>();
-    let (reveal_config_tx, reveal_config_rx) = oneshot::channel::<RevealConfig>();
-    let (result_tx, result_rx) = oneshot::channel::<VerificationResult>();
+    state.metrics.session_registered(server_name.as_deref());
 
     let session_data_storage = Arc::new(session_data.clone());
 
     let session_config = SessionConfig {
         max_recv_data,
         max_sent_data,
+        registered_at,
+        server_name,
+        server_port,
+        tenant,
+        connections,
     };
 
-    // Store session data (so prover can connect)
-    {
-        let mut sessions = state.sessions.lock().await;
-        sessions.insert(
-            session_id.clone(),
-            SessionData {
-                prover_socket_tx: Some(prover_socket_tx),
-                proxy_socket_tx: None,
-            },
-        );
-    }
-
     info!(
         "[{}] Session stored, prover can now connect to /verifier",
         session_id
@@ -558,14 +1442,17 @@ async fn handle_session_websocket(mut socket: TungsteniteStream, state: Arc<AppS
     let session_id_clone = session_id.clone();
     let state_clone = state.clone();
     let session_data_clone = session_data_storage.clone();
+    let task_guard = state.shutdown.track();
     tokio::spawn(async move {
-        run_verifier_task(
+        let _task_guard = task_guard;
+        run_verifier_task_with_lifetime(
             session_id_clone,
             session_config,
             (*session_data_clone).clone(),
             reveal_config_rx,
-            prover_socket_rx,
+            prover_socket_rxs,
             result_tx,
+            cancel_rx,
             state_clone,
         )
         .await;
@@ -576,79 +1463,163 @@ async fn handle_session_websocket(mut socket: TungsteniteStream, state: Arc<AppS
         session_id
     );
 
-    // Wait for reveal_config message
-    let reveal_msg = match socket.next().await {
-        Some(Ok(Message::Text(text))) => text,
-        Some(Ok(msg)) => {
-            error!(
-                "[{}] Expected text message for reveal_config, got: {:?}",
-                session_id, msg
-            );
-            send_error(&mut socket, "Expected text message").await;
-            return;
-        }
-        Some(Err(e)) => {
-            error!("[{}] Error receiving reveal_config: {}", session_id, e);
-            return;
-        }
-        None => {
-            error!(
-                "[{}] Connection closed before receiving reveal_config",
-                session_id
-            );
-            return;
-        }
-    };
+    tokio::spawn(buffer_session_result(
+        state.clone(),
+        session_id.clone(),
+        link.clone(),
+        result_rx,
+    ));
+    serve_session_socket(socket, &session_id, &link).await;
+}
 
-    // Parse as ClientMessage
-    let client_msg: ClientMessage = match serde_json::from_str(&reveal_msg) {
-        Ok(msg) => msg,
-        Err(e) => {
-            error!("[{}] Failed to parse reveal_config: {}", session_id, e);
-            send_error(&mut socket, &format!("Invalid message format: {}", e)).await;
-            return;
-        }
+// Reattaches a new session WebSocket to a registered session
+async fn resume_session_websocket(
+    mut socket: TungsteniteStream,
+    state: Arc<AppState>,
+    tenant: Option<String>,
+    session_id: String,
+    resume_token: String,
+) {
+    let Some(link) = state
+        .resumable
+        .resume(&session_id, &resume_token, tenant.as_deref())
+    else {
+        warn!("[{}] ❌ Rejected session resume", session_id);
+        let msg = "Unknown session or invalid resume token";
+        send_error(
+            &mut socket,
+            SessionError::new(ErrorCode::UnknownSession, msg),
+        )
+        .await;
+        let _ = socket.close(None).await;
+        return;
     };
 
-    // Expect "reveal_config" message type
-    let reveal_config = match client_msg {
-        ClientMessage::RevealConfig { sent, recv } => RevealConfig { sent, recv },
-        _ => {
-            error!("[{}] Expected 'reveal_config' message type", session_id);
-            send_error(&mut socket, "Expected 'reveal_config' message type").await;
-            return;
-        }
-    };
+    info!("[{}] Session WebSocket resumed", session_id);
+    if !send_server_message(
+        &mut socket,
+        &ServerMessage::SessionResumed {
+            session_id: session_id.clone(),
+            awaiting_reveal_config: link.awaiting_reveal_config(),
+        },
+    )
+    .await
+    {
+        error!("[{}] Failed to send session_resumed", session_id);
+        return;
+    }
 
-    info!(
-        "[{}] Received reveal_config: {} sent ranges, {} recv ranges",
-        session_id,
-        reveal_config.sent.len(),
-        reveal_config.recv.len()
-    );
+    serve_session_socket(socket, &session_id, &link).await;
+}
 
-    // Forward reveal config to verifier task
-    if reveal_config_tx.send(reveal_config).is_err() {
+// Waits for the verifier task's result and keeps it for session sockets,
+// including ones resumed later, until the resume buffer period has passed
+async fn buffer_session_result(
+    state: Arc<AppState>,
+    session_id: String,
+    link: Arc<SessionLink>,
+    result_rx: oneshot::Receiver<VerificationResult>,
+) {
+    let result = result_rx.await.unwrap_or_else(|_| {
         error!(
-            "[{}] ❌ Verifier task dropped reveal config receiver",
+            "[{}] ❌ Verifier task closed without sending result",
             session_id
         );
-        return;
-    }
+        VerificationResult {
+            results: vec![],
+            assertions: vec![],
+            commitments: vec![],
+            attestation: None,
+            error: Some(SessionError::new(
+                ErrorCode::Internal,
+                "Verification failed",
+            )),
+        }
+    });
+    link.complete(result);
 
-    info!(
-        "[{}] ✅ Reveal config sent, verifier task can now proceed",
-        session_id
-    );
+    tokio::time::sleep(state.config.limits.resume_buffer()).await;
+    state.resumable.remove(&session_id);
+}
+
+// Serves the client side of a registered session on a (possibly resumed)
+// socket: forwards the reveal config unless an earlier socket already did,
+// then sends the result. Returns early, leaving the session resumable, when
+// the socket drops or another socket resumes the session.
+async fn serve_session_socket(mut socket: TungsteniteStream, session_id: &str, link: &SessionLink) {
+    use futures_util::StreamExt;
+
+    let mut attachment = link.attach();
+
+    if link.awaiting_reveal_config() {
+        // Wait for reveal_config message, unless the session ends first
+        let reveal_msg = loop {
+            let events = tokio::select! {
+                msg = socket.next() => break Some(msg),
+                _ = link.result() => break None,
+                events = attachment.progress() => events,
+                _ = attachment.superseded() => {
+                    info!("[{}] Session WebSocket superseded by a resumed one", session_id);
+                    return;
+                }
+            };
+            send_progress(&mut socket, events).await;
+        };
+        match reveal_msg {
+            // The session ended before the reveal config arrived
+            None => {}
+            Some(Some(Ok(Message::Text(text)))) => {
+                if !forward_reveal_config(&mut socket, session_id, link, &text).await {
+                    return;
+                }
+            }
+            Some(Some(Ok(msg))) => {
+                error!(
+                    "[{}] Expected text message for reveal_config, got: {:?}",
+                    session_id, msg
+                );
+                drop(link.take_reveal_config_tx());
+                let error = SessionError::new(ErrorCode::InvalidMessage, "Expected text message");
+                send_error(&mut socket, error).await;
+                return;
+            }
+            Some(Some(Err(e))) => {
+                warn!(
+                    "[{}] Error receiving reveal_config, session can be resumed: {}",
+                    session_id, e
+                );
+                return;
+            }
+            Some(None) => {
+                warn!(
+                    "[{}] Connection closed before receiving reveal_config, session can be resumed",
+                    session_id
+                );
+                return;
+            }
+        }
+    }
 
     // Wait for verification result
-    match result_rx.await {
-        Ok(result) if result.error.is_some() => {
-            let err_msg = result.error.as_deref().unwrap_or("Unknown error");
-            error!("[{}] ❌ {}", session_id, err_msg);
-            send_error(&mut socket, err_msg).await;
+    let result = loop {
+        let events = tokio::select! {
+            result = link.result() => break result,
+            events = attachment.progress() => events,
+            _ = attachment.superseded() => {
+                info!("[{}] Session WebSocket superseded by a resumed one", session_id);
+                return;
+            }
+        };
+        send_progress(&mut socket, events).await;
+    };
+    // Phases reported just before the result still go out ahead of it
+    send_progress(&mut socket, attachment.unsent_progress()).await;
+    match result.error {
+        Some(err) => {
+            error!("[{}] ❌ {}", session_id, err);
+            send_error(&mut socket, err).await;
         }
-        Ok(result) => {
+        None => {
             info!(
                 "[{}] Received verification result, sending to extension",
                 session_id
@@ -659,6 +1630,9 @@ async fn handle_session_websocket(mut socket: TungsteniteStream, state: Arc<AppS
                 &mut socket,
                 &ServerMessage::SessionCompleted {
                     results: result.results,
+                    assertions: result.assertions,
+                    commitments: result.commitments,
+                    attestation: result.attestation,
                 },
             )
             .await
@@ -668,13 +1642,6 @@ async fn handle_session_websocket(mut socket: TungsteniteStream, state: Arc<AppS
                 error!("[{}] Failed to send session_completed", session_id);
             }
         }
-        Err(_) => {
-            error!(
-                "[{}] ❌ Verifier task closed without sending result",
-                session_id
-            );
-            send_error(&mut socket, "Verification failed").await;
-        }
     }
 
     // Close the WebSocket
@@ -682,6 +1649,74 @@ async fn handle_session_websocket(mut socket: TungsteniteStream, state: Arc<AppS
     info!("[{}] Session WebSocket closed", session_id);
 }
 
+// Sends progress events in the order they were reported. A failed send is
+// left to the socket's next read or the final message to notice.
+async fn send_progress(socket: &mut TungsteniteStream, events: Vec<ProgressEvent>) {
+    for event in events {
+        let _ = send_server_message(socket, &ServerMessage::Progress { event }).await;
+    }
+}
+
+// Parses a reveal_config message and forwards it to the verifier task,
+// returning whether to go on to the result. Invalid messages fail the
+// session, as the verifier task stops waiting for a reveal config.
+async fn forward_reveal_config(
+    socket: &mut TungsteniteStream,
+    session_id: &str,
+    link: &SessionLink,
+    text: &str,
+) -> bool {
+    // Parse as ClientMessage
+    let client_msg: ClientMessage = match serde_json::from_str(text) {
+        Ok(msg) => msg,
+        Err(e) => {
+            error!("[{}] Failed to parse reveal_config: {}", session_id, e);
+            drop(link.take_reveal_config_tx());
+            let msg = format!("Invalid message format: {}", e);
+            send_error(socket, SessionError::new(ErrorCode::InvalidMessage, msg)).await;
+            return false;
+        }
+    };
+
+    // Expect "reveal_config" message type
+    let reveal_config = match client_msg {
+        ClientMessage::RevealConfig { sent, recv } => RevealConfig { sent, recv },
+        _ => {
+            error!("[{}] Expected 'reveal_config' message type", session_id);
+            drop(link.take_reveal_config_tx());
+            let msg = "Expected 'reveal_config' message type";
+            send_error(socket, SessionError::new(ErrorCode::InvalidMessage, msg)).await;
+            return false;
+        }
+    };
+
+    info!(
+        "[{}] Received reveal_config: {} sent ranges, {} recv ranges",
+        session_id,
+        reveal_config.sent.len(),
+        reveal_config.recv.len()
+    );
+
+    // Forward reveal config to verifier task
+    match link.take_reveal_config_tx() {
+        Some(reveal_config_tx) if reveal_config_tx.send(reveal_config).is_ok() => {
+            info!(
+                "[{}] ✅ Reveal config sent, verifier task can now proceed",
+                session_id
+            );
+        }
+        Some(_) => error!(
+            "[{}] ❌ Verifier task dropped reveal config receiver",
+            session_id
+        ),
+        None => warn!(
+            "[{}] Ignoring reveal_config, another session socket already sent one",
+            session_id
+        ),
+    }
+    true
+}
+
 // WebSocket handler for verifier (prover connection)
 pub(crate) async fn verifier_ws_handler(
     ws: WsUpgrade,
@@ -689,21 +1724,23 @@ pub(crate) async fn verifier_ws_handler(
     Query(query): Query<VerifierQuery>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
     let session_id = query.session_id;
+    let connection = query.connection;
 
-    // Look up the session and extract the prover socket sender.
+    // Look up the session and extract the connection's prover socket sender.
     // Don't remove the session — proxy mode needs it for the proxy WS routing.
     let prover_socket_tx = {
         let mut sessions = state.sessions.lock().await;
         sessions
             .get_mut(&session_id)
-            .and_then(|s| s.prover_socket_tx.take())
+            .and_then(|s| s.prover_socket_txs.get_mut(connection))
+            .and_then(Option::take)
     };
 
     match prover_socket_tx {
         Some(sender) => {
             info!(
-                "[{}] Prover WebSocket connection established, passing to verifier",
-                session_id
+                "[{}] Prover WebSocket connection {} established, passing to verifier",
+                session_id, connection
             );
             Ok(ws.on_upgrade(move |socket| async move {
                 // Send the WebSocket to the waiting verifier
@@ -721,10 +1758,16 @@ pub(crate) async fn verifier_ws_handler(
             }))
         }
         None => {
-            error!("[{}] Session not found or already connected", session_id);
+            error!(
+                "[{}] Session not found or connection {} already connected",
+                session_id, connection
+            );
             Err((
                 StatusCode::NOT_FOUND,
-                format!("Session not found or already connected: {}", session_id),
+                format!(
+                    "Session not found or connection {} already connected: {}",
+                    connection, session_id
+                ),
             ))
         }
     }
@@ -732,90 +1775,96 @@ pub(crate) async fn verifier_ws_handler(
 
 // WebSocket proxy handler - bridges WebSocket to TCP
 // Compatible with notary.pse.dev: /proxy?token=<host> or legacy /proxy?host=<host>
-// In proxy mode: /proxy?token=<host>&sessionId=<id> routes to verifier task
 pub(crate) async fn proxy_ws_handler(
     ws: WsUpgrade,
+    PeerAddr(peer): PeerAddr,
+    headers: HeaderMap,
     Query(query): Query<ProxyQuery>,
     State(state): State<Arc<AppState>>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
     let host = query.token;
-    let session_id = query.session_id;
+    info!("[Proxy] New proxy request for host: {}", host);
+
+    // Connected before the upgrade so refused targets get a proper HTTP error
+    let client_ip = state.config.proxy.client_ip(&headers, peer);
+    let bridge = match open_proxy_bridge(&state, &host, client_ip).await {
+        Ok(bridge) => bridge,
+        Err(rejection) => {
+            warn!(
+                "[Proxy] Refused proxy request for {} from {:?}: {:?}",
+                host, client_ip, rejection
+            );
+            state.metrics.proxy_rejected(rejection.reason());
+            return Err(rejection.into_http_error());
+        }
+    };
+    Ok(ws.on_upgrade(move |socket| handle_proxy_connection(socket, bridge, state)))
+}
 
-    info!(
-        "[Proxy] New proxy request for host: {}, sessionId: {:?}",
-        host, session_id
-    );
+// An upstream connection checked and opened before the /proxy upgrade
+struct ProxyBridge {
+    hostname: String,
+    stream: tokio::net::TcpStream,
+    /// Per-IP connection slot, released when the bridge closes
+    lease: Option<ProxyLease>,
+}
 
-    if let Some(sid) = session_id {
-        // Proxy mode: route this WebSocket to the verifier task
-        Ok(ws.on_upgrade(move |socket| async move {
-            let proxy_tx = {
-                let mut sessions = state.sessions.lock().await;
-                sessions
-                    .get_mut(&sid)
-                    .and_then(|s| s.proxy_socket_tx.take())
-            };
-            match proxy_tx {
-                Some(tx) => {
-                    info!("[Proxy] Routing proxy WS to verifier task for session {}", sid);
-                    if tx.send(socket).is_err() {
-                        error!("[Proxy] Verifier task dropped proxy channel for session {}", sid);
-                    }
-                }
-                None => {
-                    error!(
-                        "[Proxy] No proxy socket channel for session {} (not proxy mode or already connected)",
-                        sid
-                    );
-                }
-            }
-        }))
-    } else {
-        // MPC mode: standard TCP bridge
-        Ok(ws.on_upgrade(move |socket| handle_proxy_connection(socket, host)))
+// Checks a proxy target against the proxy config and connects to it
+async fn open_proxy_bridge(
+    state: &AppState,
+    target: &str,
+    client_ip: Option<IpAddr>,
+) -> Result<ProxyBridge, ProxyRejection> {
+    let proxy = &state.config.proxy;
+    let (hostname, port) = proxy_guard::parse_target(target)?;
+    proxy.check_target(&hostname, port)?;
+    let lease = client_ip
+        .map(|ip| {
+            state
+                .proxy_clients
+                .acquire(ip, proxy.max_connections_per_ip)
+        })
+        .transpose()?;
+
+    let stream = state
+        .connector
+        .connect(Route::Bridge, &hostname, port)
+        .await?;
+
+    Ok(ProxyBridge {
+        hostname,
+        stream,
+        lease,
+    })
+}
+
+// Waits until `bytes` fit within the client's proxy bandwidth limit, if any
+async fn throttle_proxy(lease: Option<&ProxyLease>, bytes_per_sec: Option<u64>, bytes: usize) {
+    if let (Some(lease), Some(rate)) = (lease, bytes_per_sec) {
+        let delay = lease
+            .clients()
+            .reserve(lease.ip(), rate, bytes, Instant::now());
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
     }
 }
 
 // Handle the proxy WebSocket connection by bridging to TCP
-async fn handle_proxy_connection(ws: TungsteniteStream, host: String) {
+async fn handle_proxy_connection(ws: TungsteniteStream, bridge: ProxyBridge, state: Arc<AppState>) {
     use futures_util::StreamExt;
+    use std::sync::atomic::{AtomicU64, Ordering};
 
     let proxy_id = Uuid::new_v4().to_string();
+    let ProxyBridge {
+        hostname,
+        stream: tcp_stream,
+        lease,
+    } = bridge;
     info!(
         "[{}] Proxy WebSocket connected for host: {}",
-        proxy_id, host
+        proxy_id, hostname
     );
-
-    // Parse host and port (default to 443 for HTTPS)
-    let (hostname, port) = if host.contains(':') {
-        let parts: Vec<&str> = host.split(':').collect();
-        (
-            parts[0].to_string(),
-            parts.get(1).and_then(|p| p.parse().ok()).unwrap_or(443),
-        )
-    } else {
-        (host.clone(), 443)
-    };
-
-    info!("[{}] Connecting to {}:{}", proxy_id, hostname, port);
-
-    // Connect to the remote TCP host
-    let tcp_stream = match tokio::net::TcpStream::connect((hostname.as_str(), port)).await {
-        Ok(stream) => {
-            info!(
-                "[{}] TCP connection established to {}:{}",
-                proxy_id, hostname, port
-            );
-            stream
-        }
-        Err(e) => {
-            error!(
-                "[{}] Failed to connect to {}:{} - {}",
-                proxy_id, hostname, port, e
-            );
-            return;
-        }
-    };
     if let Err(err) = tcp_stream.set_nodelay(true) {
         warn!(
             "[{}] failed to set TCP_NODELAY on outbound proxy connection: {}",
@@ -829,29 +1878,38 @@ async fn handle_proxy_connection(ws: TungsteniteStream, host: String) {
     // Split the TCP stream into read and write halves
     let (mut tcp_read, mut tcp_write) = tokio::io::split(tcp_stream);
 
+    // Byte counts are shared so the idle watchdog can see traffic and the
+    // totals survive the tasks being aborted
+    let ws_total = Arc::new(AtomicU64::new(0));
+    let tcp_total = Arc::new(AtomicU64::new(0));
+    let lease = lease.map(Arc::new);
+    let bandwidth = state.config.proxy.max_bytes_per_sec_per_ip;
+
     // Spawn task to forward WebSocket -> TCP
     // Read WebSocket Binary messages and write payload to TCP
     let proxy_id_clone = proxy_id.clone();
+    let total = ws_total.clone();
+    let task_lease = lease.clone();
     let ws_to_tcp = tokio::spawn(async move {
-        let mut total_bytes = 0u64;
-
         loop {
             match ws_stream.next().await {
                 Some(Ok(msg)) => {
                     match msg {
                         Message::Binary(data) => {
                             let len = data.len();
-                            total_bytes += len as u64;
+                            throttle_proxy(task_lease.as_deref(), bandwidth, len).await;
 
                             if let Err(e) = tcp_write.write_all(&data).await {
                                 error!("[{}] Failed to write to TCP: {}", proxy_id_clone, e);
                                 break;
                             }
+                            total.fetch_add(len as u64, Ordering::Relaxed);
                         }
                         Message::Close(_) => {
                             info!(
                                 "[{}] WebSocket close frame received, forwarded {} bytes total",
-                                proxy_id_clone, total_bytes
+                                proxy_id_clone,
+                                total.load(Ordering::Relaxed)
                             );
                             break;
                         }
@@ -867,23 +1925,23 @@ async fn handle_proxy_connection(ws: TungsteniteStream, host: String) {
                 None => {
                     info!(
                         "[{}] WebSocket stream ended, forwarded {} bytes total",
-                        proxy_id_clone, total_bytes
+                        proxy_id_clone,
+                        total.load(Ordering::Relaxed)
                     );
                     break;
                 }
             }
         }
-
-        total_bytes
     });
 
     // Spawn task to forward TCP -> WebSocket
     // Read from TCP and wrap in WebSocket Binary messages
     let proxy_id_clone = proxy_id.clone();
+    let total = tcp_total.clone();
+    let task_lease = lease.clone();
     let tcp_to_ws = tokio::spawn(async move {
         const CHUNK: usize = 8192;
         let mut buf = BytesMut::with_capacity(CHUNK);
-        let mut total_bytes = 0u64;
 
         loop {
             buf.reserve(CHUNK);
@@ -891,7 +1949,8 @@ async fn handle_proxy_connection(ws: TungsteniteStream, host: String) {
                 Ok(0) => {
                     info!(
                         "[{}] TCP read EOF (server closed), forwarded {} bytes to WebSocket",
-                        proxy_id_clone, total_bytes
+                        proxy_id_clone,
+                        total.load(Ordering::Relaxed)
                     );
                     // Send WebSocket close frame to signal EOF to client
                     if let Err(e) = ws_sink.send(Message::Close(None)).await {
@@ -900,13 +1959,14 @@ async fn handle_proxy_connection(ws: TungsteniteStream, host: String) {
                     break;
                 }
                 Ok(n) => {
-                    total_bytes += n as u64;
+                    throttle_proxy(task_lease.as_deref(), bandwidth, n).await;
                     let chunk = buf.split().freeze();
 
                     if let Err(e) = ws_sink.send(Message::Binary(chunk)).await {
                         error!("[{}] Failed to send to WebSocket: {}", proxy_id_clone, e);
                         break;
                     }
+                    total.fetch_add(n as u64, Ordering::Relaxed);
                 }
                 Err(e) => {
                     error!("[{}] TCP read error: {}", proxy_id_clone, e);
@@ -916,83 +1976,478 @@ async fn handle_proxy_connection(ws: TungsteniteStream, host: String) {
                 }
             }
         }
-
-        total_bytes
     });
 
-    // Wait for both tasks to complete
-    let (ws_result, tcp_result) = tokio::join!(ws_to_tcp, tcp_to_ws);
+    // Wait for both tasks to complete, or close the bridge once it has seen
+    // no traffic in either direction for the idle timeout
+    let idle_timeout = state.config.proxy.idle_timeout();
+    let ws_abort = ws_to_tcp.abort_handle();
+    let tcp_abort = tcp_to_ws.abort_handle();
+    let watchdog = async {
+        let mut seen = 0;
+        let mut idle_since = Instant::now();
+        loop {
+            tokio::time::sleep(idle_timeout.min(Duration::from_secs(1))).await;
+            let forwarded = ws_total.load(Ordering::Relaxed) + tcp_total.load(Ordering::Relaxed);
+            if forwarded != seen {
+                seen = forwarded;
+                idle_since = Instant::now();
+            } else if idle_since.elapsed() >= idle_timeout {
+                return;
+            }
+        }
+    };
+    tokio::select! {
+        _ = async { tokio::join!(ws_to_tcp, tcp_to_ws) } => {}
+        _ = watchdog => {
+            info!(
+                "[{}] Proxy idle for {:?}, closing",
+                proxy_id, idle_timeout
+            );
+            ws_abort.abort();
+            tcp_abort.abort();
+        }
+    }
 
-    let ws_total = ws_result.unwrap_or(0);
-    let tcp_total = tcp_result.unwrap_or(0);
+    let ws_total = ws_total.load(Ordering::Relaxed);
+    let tcp_total = tcp_total.load(Ordering::Relaxed);
 
     info!(
         "[{}] Proxy closed: WS→TCP {} bytes, TCP→WS {} bytes",
         proxy_id, ws_total, tcp_total
     );
+    state.metrics.proxy_bytes("to_server", &hostname, ws_total);
+    state
+        .metrics
+        .proxy_bytes("from_server", &hostname, tcp_total);
+}
+
+// Runs the verifier task, failing the session if it outlives the configured
+// maximum session lifetime or an operator cancels it
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn run_verifier_task_with_lifetime(
+    session_id: String,
+    config: SessionConfig,
+    session_data: HashMap<String, String>,
+    reveal_config_rx: oneshot::Receiver<RevealConfig>,
+    socket_rxs: Vec<oneshot::Receiver<TungsteniteStream>>,
+    result_tx: oneshot::Sender<VerificationResult>,
+    cancel_rx: oneshot::Receiver<()>,
+    state: Arc<AppState>,
+) {
+    // The task reports through its own channel so that `result_tx` outlives
+    // the task when it is dropped on expiry or cancellation
+    let (task_result_tx, task_result_rx) = oneshot::channel::<VerificationResult>();
+    let task = run_verifier_task(
+        session_id.clone(),
+        config.clone(),
+        session_data.clone(),
+        reveal_config_rx,
+        socket_rxs,
+        task_result_tx,
+        state.clone(),
+    );
+    let lifetime = state.config.limits.max_session_lifetime();
+    let expiry = async {
+        match lifetime {
+            Some(lifetime) => {
+                tokio::time::sleep(lifetime).await;
+                lifetime
+            }
+            None => std::future::pending().await,
+        }
+    };
+
+    let (reason, error) = tokio::select! {
+        () = task => {
+            if let Ok(result) = task_result_rx.await {
+                let _ = result_tx.send(result);
+            }
+            return;
+        }
+        lifetime = expiry => (
+            "session_lifetime",
+            SessionError::new(
+                ErrorCode::TimeoutSession,
+                format!("Session exceeded maximum lifetime of {:?}", lifetime),
+            ),
+        ),
+        // A dropped sender means the session was cleaned up, not cancelled
+        Ok(()) = cancel_rx => (
+            "cancelled",
+            SessionError::new(ErrorCode::Cancelled, "Session cancelled by an operator"),
+        ),
+    };
+
+    let server_name = config.server_name.clone();
+    fail_session(
+        &state,
+        &session_id,
+        &config,
+        &session_data,
+        server_name,
+        result_tx,
+        reason,
+        error,
+    )
+    .await;
 }
 
-// Verifier task that waits for WebSocket and runs verification
+// Verifier task that waits for the prover's WebSockets and runs verification
 async fn run_verifier_task(
     session_id: String,
     config: SessionConfig,
     session_data: HashMap<String, String>,
     reveal_config_rx: oneshot::Receiver<RevealConfig>,
-    socket_rx: oneshot::Receiver<TungsteniteStream>,
+    socket_rxs: Vec<oneshot::Receiver<TungsteniteStream>>,
     result_tx: oneshot::Sender<VerificationResult>,
     state: Arc<AppState>,
 ) {
     info!(
-        "[{}] Verifier task started, waiting for WebSocket connection...",
-        session_id
+        "[{}] Verifier task started, waiting for {} WebSocket connection(s)...",
+        session_id,
+        socket_rxs.len()
     );
     info!(
         "[{}] Configuration: maxRecvData={}, maxSentData={}",
         session_id, config.max_recv_data, config.max_sent_data
     );
 
+    // Verify the TLS connections in order; the first to fail fails the session
+    let mut connections = Vec::with_capacity(socket_rxs.len());
+    for (index, socket_rx) in socket_rxs.into_iter().enumerate() {
+        match verify_connection(&state, &session_id, &config, index, socket_rx).await {
+            Ok(connection) => {
+                record_server_name(&state, &session_id, &connection.server_name).await;
+                connections.push(connection);
+            }
+            Err((reason, server_name, error)) => {
+                fail_session(
+                    &state,
+                    &session_id,
+                    &config,
+                    &session_data,
+                    server_name,
+                    result_tx,
+                    reason,
+                    error,
+                )
+                .await;
+                return;
+            }
+        }
+    }
+    // The session's server name is the first connection's
+    let server_name_str = connections[0].server_name.clone();
+    let server_name = Some(server_name_str.clone());
+
+    // Wait for RevealConfig from the session handler (with timeout)
+    let reveal_config_wait_timeout = state.config.limits.reveal_config_timeout();
+    let wait_started = Instant::now();
+    let reveal_config_result = timeout(reveal_config_wait_timeout, reveal_config_rx).await;
+    state
+        .metrics
+        .reveal_config_wait(&server_name_str, wait_started.elapsed());
+    let reveal_config = match reveal_config_result {
+        Ok(Ok(config)) => {
+            info!("[{}] ✅ RevealConfig received, mapping results", session_id);
+            config
+        }
+        Ok(Err(_)) => {
+            // The session socket rejected the client's reveal config
+            fail_session(
+                &state,
+                &session_id,
+                &config,
+                &session_data,
+                server_name,
+                result_tx,
+                "reveal_config_closed",
+                SessionError::new(
+                    ErrorCode::InvalidMessage,
+                    "Reveal config channel closed before delivery",
+                ),
+            )
+            .await;
+            return;
+        }
+        Err(_) => {
+            fail_session(
+                &state,
+                &session_id,
+                &config,
+                &session_data,
+                server_name,
+                result_tx,
+                "reveal_config_timeout",
+                SessionError::new(
+                    ErrorCode::TimeoutRevealConfig,
+                    format!(
+                        "Timed out waiting for reveal config after {:?}",
+                        reveal_config_wait_timeout
+                    ),
+                ),
+            )
+            .await;
+            return;
+        }
+    };
+
+    // Every range must be in the transcript of one of the session's connections
+    let unknown_connection = reveal_config
+        .sent
+        .iter()
+        .map(|range| ("sent", range))
+        .chain(reveal_config.recv.iter().map(|range| ("recv", range)))
+        .find(|(_, range)| range.connection >= connections.len());
+    if let Some((direction, range)) = unknown_connection {
+        fail_session(
+            &state,
+            &session_id,
+            &config,
+            &session_data,
+            server_name,
+            result_tx,
+            "invalid_range",
+            SessionError::new(
+                ErrorCode::InvalidRevealRange,
+                format!(
+                    "Invalid {} range [{}, {}) - session has no connection {}",
+                    direction, range.start, range.end, range.connection
+                ),
+            )
+            .with_range(direction, range.start, range.end)
+            .with_connection(range.connection),
+        )
+        .await;
+        return;
+    }
+
+    // Check each connection's ranges and map them to handler results
+    let mut handler_results = Vec::new();
+    let mut assertions = Vec::new();
+    let mut hashes = Vec::new();
+    let mut commitments = Vec::new();
+    let mut transcripts = Vec::new();
+    for connection in &connections {
+        let revealed = reveal_connection(
+            &state,
+            &session_id,
+            &config,
+            &session_data,
+            connection,
+            &reveal_config.for_connection(connection.index),
+        );
+        match revealed {
+            Ok(revealed) => {
+                handler_results.extend(revealed.results);
+                assertions.extend(revealed.assertions);
+                hashes.extend(revealed.hashes);
+                commitments.extend(revealed.commitments);
+                transcripts.push(ConnectionTranscript {
+                    index: connection.index,
+                    server_name: connection.server_name.clone(),
+                    transcript: revealed.transcript,
+                });
+            }
+            Err((reason, error)) => {
+                fail_session(
+                    &state,
+                    &session_id,
+                    &config,
+                    &session_data,
+                    Some(connection.server_name.clone()),
+                    result_tx,
+                    reason,
+                    error,
+                )
+                .await;
+                return;
+            }
+        }
+    }
+    state
+        .resumable
+        .report(&session_id, Phase::RevealConfigValidated);
+
+    // Sign the results if an attestation key is configured
+    let attestation = state.attestor.as_ref().map(|attestor| {
+        attestor.sign(AttestationBody {
+            version: attestation::ATTESTATION_VERSION,
+            session_id: session_id.clone(),
+            server_name: server_name_str.clone(),
+            sent: reveal_config.sent.clone(),
+            recv: reveal_config.recv.clone(),
+            results: handler_results.clone(),
+            assertions: assertions.clone(),
+            hashes,
+            commitments: commitments.clone(),
+            timestamp: 0,
+        })
+    });
+
+    // The stored session's and the webhook's `transcript` are the first
+    // connection's; both list every connection's if there are more
+    let redacted_transcript = transcripts[0].transcript.clone();
+    if transcripts.len() == 1 {
+        transcripts.clear();
+    }
+
+    // Persist the result so it can be fetched by session id later
+    persist_session(
+        &state,
+        StoredSession {
+            id: session_id.clone(),
+            tenant: config.tenant.clone(),
+            server_name: Some(server_name_str.clone()),
+            results: handler_results.clone(),
+            assertions: assertions.clone(),
+            commitments: commitments.clone(),
+            transcript: Some(redacted_transcript.clone()),
+            connections: transcripts.clone(),
+            session_data: session_data.clone(),
+            registered_at: config.registered_at,
+            completed_at: unix_timestamp(),
+            attestation: attestation.clone(),
+            error: None,
+            error_code: None,
+        },
+    )
+    .await;
+
+    // Check if webhook is configured for this server_name
+    if let Some(webhook_config) = state.config.get_webhook(&server_name_str) {
+        info!(
+            "[{}] Webhook configured for {}, queueing POST to {}",
+            session_id, server_name_str, webhook_config.url
+        );
+
+        let payload = WebhookPayload {
+            server_name: server_name_str.clone(),
+            results: handler_results.clone(),
+            assertions: assertions.clone(),
+            commitments: commitments.clone(),
+            config: RevealConfigForWebhook {
+                sent: reveal_config.sent.clone(),
+                recv: reveal_config.recv.clone(),
+            },
+            session: SessionInfo::new(&session_id, config.tenant.clone(), &session_data),
+            transcript: redacted_transcript,
+            attestation: attestation.clone(),
+            connections: transcripts,
+        };
+
+        // Queued for background delivery with retries - don't block on webhook
+        match serde_json::to_value(&payload) {
+            Ok(payload) => {
+                state
+                    .webhooks
+                    .enqueue(&session_id, &server_name_str, payload)
+                    .await;
+                state
+                    .resumable
+                    .report(&session_id, Phase::WebhookDispatched);
+            }
+            Err(e) => error!(
+                "[{}] ❌ Failed to encode webhook payload: {}",
+                session_id, e
+            ),
+        }
+    }
+
+    state.metrics.session_completed(&server_name_str);
+
+    // Send result to extension via the result channel
+    let result = VerificationResult {
+        results: handler_results,
+        assertions,
+        commitments,
+        attestation,
+        error: None,
+    };
+
+    if result_tx.send(result).is_err() {
+        error!(
+            "[{}] ❌ Failed to send result to extension - channel closed",
+            session_id
+        );
+    } else {
+        info!("[{}] ✅ Result sent to extension successfully", session_id);
+    }
+
+    // Clean up session (if it still exists in the map)
+    cleanup_session(&state, &session_id).await;
+
+    info!("[{}] Verifier task completed and cleaned up", session_id);
+}
+
+/// A TLS connection of a session that passed verification
+struct VerifiedConnection {
+    index: usize,
+    server_name: String,
+    transcript: PartialTranscript,
+    commitments: Vec<tlsn::transcript::TranscriptCommitment>,
+    /// Ranges covered by the encoding commitment in `commitments`, if any
+    encoded: EncodedRanges,
+}
+
+/// Why a connection failed: the metrics reason, the server name to report
+/// the failure under, and the error
+type ConnectionFailure = (&'static str, Option<String>, SessionError);
+
+// Waits for the prover to open TLS connection `index` of a session on
+// /verifier, then verifies it and enforces the policy for its server name
+async fn verify_connection(
+    state: &Arc<AppState>,
+    session_id: &str,
+    config: &SessionConfig,
+    index: usize,
+    socket_rx: oneshot::Receiver<TungsteniteStream>,
+) -> Result<VerifiedConnection, ConnectionFailure> {
     // Wait for WebSocket connection with timeout
-    let connection_timeout = Duration::from_secs(30);
+    let connection_timeout = state.config.limits.prover_connect_timeout();
+    let wait_started = Instant::now();
     let socket_result = timeout(connection_timeout, socket_rx).await;
+    state.metrics.prover_socket_wait(wait_started.elapsed());
 
     let socket = match socket_result {
         Ok(Ok(socket)) => {
             info!(
-                "[{}] ✅ WebSocket received, starting verification",
-                session_id
+                "[{}] ✅ WebSocket {} received, starting verification",
+                session_id, index
             );
+            state
+                .resumable
+                .report_connection(session_id, index, Phase::ProverConnected);
             socket
         }
         Ok(Err(_)) => {
-            error!(
-                "[{}] ❌ Socket channel closed before connection",
-                session_id
+            let error = SessionError::new(
+                ErrorCode::ProverDisconnected,
+                "Prover socket channel closed before connection",
             );
-            cleanup_session(&state, &session_id).await;
-            return;
+            return Err((
+                "prover_disconnected",
+                config.server_name.clone(),
+                connection_error(config, index, error),
+            ));
         }
         Err(_) => {
-            error!(
-                "[{}] ⏱️  Timed out waiting for WebSocket connection after {:?}",
-                session_id, connection_timeout
+            let error = SessionError::new(
+                ErrorCode::TimeoutProverConnect,
+                format!(
+                    "Timed out waiting for prover connection after {:?}",
+                    connection_timeout
+                ),
             );
-            cleanup_session(&state, &session_id).await;
-            return;
+            return Err((
+                "prover_timeout",
+                config.server_name.clone(),
+                connection_error(config, index, error),
+            ));
         }
     };
 
-    // Set up a channel to receive the prover's proxy WebSocket.
-    // In universal mode the verifier accepts both MPC and Proxy — the prover decides.
-    // If the prover chooses proxy, it connects to /proxy?sessionId=... which routes here.
-    let (proxy_tx, proxy_rx) = oneshot::channel::<TungsteniteStream>();
-    {
-        let mut sessions = state.sessions.lock().await;
-        if let Some(session_data) = sessions.get_mut(&session_id) {
-            session_data.proxy_socket_tx = Some(proxy_tx);
-        }
-    }
-    let proxy_socket_rx = Some(proxy_rx);
-
     // Wrap the WebSocket stream in WsStream for AsyncRead/AsyncWrite compatibility
     let stream = WsStream::new(socket);
     info!("[{}] WebSocket converted to stream", session_id);
@@ -1001,183 +2456,369 @@ async fn run_verifier_task(
     let stream = stream.compat();
 
     // Run the verifier with timeout
-    let verification_timeout = Duration::from_secs(120);
+    let verification_timeout = state.config.limits.verification_timeout();
     info!(
-        "[{}] Starting verification with timeout of {:?}",
-        session_id, verification_timeout
+        "[{}] Starting verification of connection {} with timeout of {:?}",
+        session_id, index, verification_timeout
     );
 
+    // The server name is only proven once verification is done, so the roots
+    // are picked by the declared one; only the first connection has one
+    let trust = state.trust.current();
+    let declared_server_name = config.server_name.as_deref().filter(|_| index == 0);
+
+    // In Proxy mode the verifier dials the server itself
+    let connect_server = |host: String| async move {
+        connect_proxy_server(state, &host, proxy_server_port(config, &host)).await
+    };
+    let root_store = trust.roots_for(declared_server_name).clone();
+
+    let verification_started = Instant::now();
     let verification_result = timeout(
         verification_timeout,
-        verifier(stream, config.max_sent_data, config.max_recv_data, proxy_socket_rx),
+        verifier(
+            stream,
+            config.max_sent_data,
+            config.max_recv_data,
+            root_store,
+            connect_server,
+            |phase| state.resumable.report_connection(session_id, index, phase),
+            &state.metrics,
+        ),
     )
     .await;
+    state.metrics.verification_duration(
+        match &verification_result {
+            Ok(Ok((server_name, ..))) => Some(server_name.as_ref()),
+            _ => None,
+        },
+        verification_started.elapsed(),
+    );
 
-    // Handle the verification result
-    match verification_result {
-        Ok(Ok((server_name, transcript, transcript_commitments))) => {
-            info!("[{}] ✅ Verification completed successfully!", session_id);
-
-            // Extract sent and received data
-            let sent_bytes = transcript.sent_unsafe().to_vec();
-            let recv_bytes = transcript.received_unsafe().to_vec();
-
-            info!(
-                "[{}] Sent data length: {} bytes (authed: {} bytes)",
-                session_id,
-                sent_bytes.len(),
-                transcript.sent_authed().len(),
-            );
-            info!(
-                "[{}] Received data length: {} bytes (authed: {} bytes)",
-                session_id,
-                recv_bytes.len(),
-                transcript.received_authed().len()
+    let (server_name, transcript, commitments, encoded) = match verification_result {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => {
+            let error = SessionError {
+                message: format!("Verification failed: {}", e),
+                ..e
+            };
+            return Err((
+                "verification_failed",
+                config.server_name.clone(),
+                connection_error(config, index, error),
+            ));
+        }
+        Err(_) => {
+            let error = SessionError::new(
+                ErrorCode::TimeoutVerification,
+                format!("Verification timed out after {:?}", verification_timeout),
             );
+            return Err((
+                "verification_timeout",
+                config.server_name.clone(),
+                connection_error(config, index, error),
+            ));
+        }
+    };
+    info!(
+        "[{}] ✅ Verification of connection {} completed successfully!",
+        session_id, index
+    );
+    info!(
+        "[{}] Sent data length: {} bytes (authed: {} bytes)",
+        session_id,
+        transcript.sent_unsafe().len(),
+        transcript.sent_authed().len(),
+    );
+    info!(
+        "[{}] Received data length: {} bytes (authed: {} bytes)",
+        session_id,
+        transcript.received_unsafe().len(),
+        transcript.received_authed().len()
+    );
 
-            // Wait for RevealConfig from the session handler (with timeout)
-            let reveal_config_wait_timeout = Duration::from_secs(30);
-            let reveal_config = match timeout(reveal_config_wait_timeout, reveal_config_rx).await {
-                Ok(Ok(config)) => {
-                    info!("[{}] ✅ RevealConfig received, mapping results", session_id);
-                    config
-                }
-                Ok(Err(_)) => {
-                    error!(
-                        "[{}] ❌ RevealConfig channel closed before delivery",
-                        session_id
-                    );
-                    cleanup_session(&state, &session_id).await;
-                    return;
-                }
-                Err(_) => {
-                    error!(
-                        "[{}] ❌ Timed out waiting for RevealConfig after verification",
-                        session_id
-                    );
-                    cleanup_session(&state, &session_id).await;
-                    return;
-                }
-            };
+    // Enforce the policy for the verified server name. A declared server
+    // name is the first connection's.
+    let policy = state.config.get_policy(server_name.as_ref());
+    let policy_check = match (declared_server_name, policy) {
+        (Some(declared), _) if declared != server_name.as_ref() => Err(format!(
+            "Verified server name {} does not match declared {}",
+            server_name.as_ref(),
+            declared
+        )),
+        (_, Some(policy)) => policy.check_allowed(server_name.as_ref()).and_then(|()| {
+            policy.check_limits(
+                transcript.sent_unsafe().len(),
+                transcript.received_unsafe().len(),
+            )
+        }),
+        (_, None) => Ok(()),
+    };
+    if let Err(msg) = policy_check {
+        let error = SessionError::new(ErrorCode::PolicyDenied, msg);
+        return Err((
+            "policy",
+            Some(server_name.as_ref().to_string()),
+            connection_error(config, index, error),
+        ));
+    }
 
-            // Validate that reveal_config ranges match authenticated transcript
-            // ranges. Hash-committed ranges aren't in `sent_authed`/`received_authed`
-            // (those hold revealed plaintext), so we union the commitment ranges in.
-            if let Err((direction, start, end)) = verify_reveal_config(
-                &reveal_config,
-                &transcript,
-                &transcript_commitments,
-            ) {
-                error!(
-                    "[{}] ❌ Invalid {} range [{}, {}) - not fully within authenticated ranges",
-                    session_id, direction, start, end
-                );
-                cleanup_session(&state, &session_id).await;
-                return;
-            }
+    // A server with roots of its own must not pass on the default ones
+    if trust.scope(Some(server_name.as_ref())) != trust.scope(declared_server_name) {
+        let error = SessionError::new(
+            ErrorCode::VerificationFailed,
+            format!(
+                "Server {} has its own trust roots and must be the session's declared serverName",
+                server_name.as_ref()
+            ),
+        );
+        return Err((
+            "trust",
+            Some(server_name.as_ref().to_string()),
+            connection_error(config, index, error),
+        ));
+    }
 
-            info!(
-                "[{}] ✅ All reveal_config ranges validated against authenticated transcript",
-                session_id
-            );
+    Ok(VerifiedConnection {
+        index,
+        server_name: server_name.as_ref().to_string(),
+        transcript,
+        commitments,
+        encoded,
+    })
+}
 
-            // Map revealed ranges to handler results using raw transcript bytes.
-            // For HASH handlers, substitute the hex-encoded hash digest (the
-            // plaintext was never revealed, so `bytes[..]` is zeroed).
+/// Rejects a `serverPort` that is 0 or comes without a `serverName`.
+pub(crate) fn check_server_port(
+    server_name: Option<&str>,
+    server_port: Option<u16>,
+) -> Result<(), String> {
+    match (server_name, server_port) {
+        (_, Some(0)) => Err("serverPort must not be 0".to_string()),
+        (None, Some(_)) => Err("serverPort requires serverName".to_string()),
+        _ => Ok(()),
+    }
+}
 
-            let mut handler_results = Vec::new();
+/// Port the verifier dials `host` on in Proxy mode: the declared port for the
+/// declared server, whichever connection goes to it, and 443 otherwise.
+pub(crate) fn proxy_server_port(config: &SessionConfig, host: &str) -> u16 {
+    match (config.server_name.as_deref(), config.server_port) {
+        (Some(declared), Some(port)) if declared == host => port,
+        _ => proxy_guard::DEFAULT_PORT,
+    }
+}
 
-            handler_results.extend(process_ranges(
-                &reveal_config.sent,
-                &sent_bytes,
-                tlsn::transcript::Direction::Sent,
-                &transcript_commitments,
-                "SENT",
-                &session_id,
-            ));
-            handler_results.extend(process_ranges(
-                &reveal_config.recv,
-                &recv_bytes,
-                tlsn::transcript::Direction::Received,
-                &transcript_commitments,
-                "RECV",
-                &session_id,
-            ));
+// Connects to the server of a Proxy-mode session once its policy allows the
+// host and port, through the same connector as the /proxy bridge
+pub(crate) async fn connect_proxy_server(
+    state: &AppState,
+    host: &str,
+    port: u16,
+) -> Result<tokio::net::TcpStream, SessionError> {
+    if let Some(policy) = state.config.get_policy(host) {
+        policy
+            .check_allowed(host)
+            .and_then(|()| policy.check_port(port))
+            .map_err(|msg| SessionError::new(ErrorCode::PolicyDenied, msg))?;
+    }
 
-            // Check if webhook is configured for this server_name
-            let server_name_str = server_name.as_ref();
-            if let Some(webhook_config) = state.config.get_webhook(server_name_str) {
-                info!(
-                    "[{}] Webhook configured for {}, sending POST to {}",
-                    session_id, server_name_str, webhook_config.url
-                );
+    state
+        .connector
+        .connect(Route::Verifier, host, port)
+        .await
+        .map_err(|rejection| match rejection {
+            ProxyRejection::Forbidden(msg) => SessionError::new(ErrorCode::PolicyDenied, msg),
+            rejection => {
+                let (_, msg) = rejection.into_http_error();
+                SessionError::new(ErrorCode::ServerUnreachable, msg)
+            }
+        })
+}
 
-                // Create redacted transcript - only revealed ranges are visible
-                let redacted_transcript = RedactedTranscript::from_transcript(
-                    &sent_bytes,
-                    &recv_bytes,
-                    &reveal_config,
-                );
+/// What a verified connection contributes to the session result
+struct RevealedConnection {
+    results: Vec<HandlerResult>,
+    assertions: Vec<AssertionResult>,
+    hashes: Vec<AttestedHash>,
+    commitments: Vec<CommitmentInfo>,
+    transcript: RedactedTranscript,
+}
 
-                let payload = WebhookPayload {
-                    server_name: server_name_str.to_string(),
-                    results: handler_results.clone(),
-                    config: RevealConfigForWebhook {
-                        sent: reveal_config.sent.clone(),
-                        recv: reveal_config.recv.clone(),
-                    },
-                    session: SessionInfo {
-                        id: session_id.clone(),
-                        data: session_data.clone(),
-                    },
-                    transcript: redacted_transcript,
-                };
-
-                // Fire and forget - don't block on webhook
-                let webhook_config = webhook_config.clone();
-                let session_id_for_webhook = session_id.clone();
-                tokio::spawn(async move {
-                    send_webhook(&webhook_config, &payload, &session_id_for_webhook).await;
-                });
-            }
+// Checks the ranges a reveal config has for a verified connection against
+// its transcript, their labels and the policy, then maps them to handler
+// results. Errors come with the metrics reason.
+fn reveal_connection(
+    state: &AppState,
+    session_id: &str,
+    config: &SessionConfig,
+    session_data: &HashMap<String, String>,
+    connection: &VerifiedConnection,
+    reveal_config: &RevealConfig,
+) -> Result<RevealedConnection, (&'static str, SessionError)> {
+    let VerifiedConnection {
+        index,
+        server_name,
+        transcript,
+        commitments,
+        encoded,
+    } = connection;
+    let sent_bytes = transcript.sent_unsafe();
+    let recv_bytes = transcript.received_unsafe();
+    let policy = state.config.get_policy(server_name);
+
+    // Validate that reveal_config ranges match authenticated transcript
+    // ranges. Hash-committed ranges aren't in `sent_authed`/`received_authed`
+    // (those hold revealed plaintext), so we union the commitment ranges in;
+    // HASH ranges must match a commitment made with their algorithm.
+    if let Err((direction, start, end)) =
+        verify_reveal_config(reveal_config, transcript, commitments, encoded)
+    {
+        let error = SessionError::new(
+            ErrorCode::InvalidRevealRange,
+            format!(
+                "Invalid {} range [{}, {}) - not fully within authenticated ranges",
+                direction, start, end
+            ),
+        )
+        .with_range(&direction, start, end);
+        return Err(("invalid_range", connection_error(config, *index, error)));
+    }
 
-            // Send result to extension via the result channel
-            let result = VerificationResult {
-                results: handler_results,
-                error: None,
-            };
+    info!(
+        "[{}] ✅ All reveal_config ranges of connection {} validated against authenticated transcript",
+        session_id, index
+    );
 
-            if result_tx.send(result).is_err() {
-                error!(
-                    "[{}] ❌ Failed to send result to extension - channel closed",
-                    session_id
-                );
-            } else {
-                info!("[{}] ✅ Result sent to extension successfully", session_id);
-            }
+    // Check that every range lies within the HTTP part it is labelled with
+    let label_check = http_layout::validate_labels(
+        reveal_config,
+        (sent_bytes, |i| transcript.sent_authed().contains(&i)),
+        (recv_bytes, |i| transcript.received_authed().contains(&i)),
+    )
+    .and_then(|unverified| {
+        for (direction, start, end) in &unverified {
+            warn!(
+                "[{}] ⚠️ Could not verify label of {} range [{}, {}) - surrounding bytes are hidden",
+                session_id, direction, start, end
+            );
         }
-        Ok(Err(e)) => {
-            let msg = format!("Verification failed: {}", e);
-            error!("[{}] ❌ {}", session_id, msg);
-            let _ = result_tx.send(VerificationResult {
-                results: vec![],
-                error: Some(msg),
-            });
+        let allowed = state.config.allow_unverified_labels
+            && !policy.is_some_and(|policy| policy.require_verified_labels);
+        if allowed || unverified.is_empty() {
+            return Ok(unverified);
         }
-        Err(_) => {
-            let msg = format!("Verification timed out after {:?}", verification_timeout);
-            error!("[{}] ⏱️  {}", session_id, msg);
-            let _ = result_tx.send(VerificationResult {
-                results: vec![],
-                error: Some(msg),
-            });
+        let (direction, start, end) = unverified[0];
+        Err(format!(
+            "Label of {} range [{}, {}) cannot be verified",
+            direction, start, end
+        ))
+    });
+    let unverified = match label_check {
+        Ok(unverified) => unverified,
+        Err(msg) => {
+            let error = SessionError::new(ErrorCode::MislabelledRange, msg);
+            return Err(("mislabelled_range", connection_error(config, *index, error)));
         }
+    };
+
+    if let Some(Err(msg)) = policy.map(|policy| {
+        policy
+            .check_reveal(
+                (sent_bytes, |i| transcript.sent_authed().contains(&i)),
+                (recv_bytes, |i| transcript.received_authed().contains(&i)),
+            )
+            .and_then(|()| policy.check_hash_algorithms(&commitment::hash_algorithms(commitments)))
+    }) {
+        let error = SessionError::new(ErrorCode::PolicyDenied, msg);
+        return Err(("policy", connection_error(config, *index, error)));
     }
 
-    // Clean up session (if it still exists in the map)
-    cleanup_session(&state, &session_id).await;
+    // Map revealed ranges to handler results using raw transcript bytes.
+    // For HASH handlers, substitute the hex-encoded hash digest (the
+    // plaintext was never revealed, so `bytes[..]` is zeroed).
 
-    info!("[{}] Verifier task completed and cleaned up", session_id);
+    let mut results = Vec::new();
+
+    results.extend(process_ranges(
+        &reveal_config.sent,
+        sent_bytes,
+        tlsn::transcript::Direction::Sent,
+        commitments,
+        server_name,
+        "SENT",
+        session_id,
+    ));
+    results.extend(process_ranges(
+        &reveal_config.recv,
+        recv_bytes,
+        tlsn::transcript::Direction::Received,
+        commitments,
+        server_name,
+        "RECV",
+        session_id,
+    ));
+
+    // Flag the results of ranges accepted under `allow_unverified_labels`
+    let ranges = reveal_config
+        .sent
+        .iter()
+        .map(|range| ("sent", range))
+        .chain(reveal_config.recv.iter().map(|range| ("recv", range)));
+    for (result, (direction, range)) in results.iter_mut().zip(ranges) {
+        result.label_verified = !unverified.contains(&(direction, range.start, range.end));
+    }
+
+    // Evaluate the policy's assertions over the revealed values that span
+    // their whole part
+    let assertions = policy
+        .map(|policy| {
+            let whole_parts: Vec<bool> = http_layout::spans_whole_part(
+                sent_bytes,
+                |i| transcript.sent_authed().contains(&i),
+                &reveal_config.sent,
+                true,
+            )
+            .into_iter()
+            .chain(http_layout::spans_whole_part(
+                recv_bytes,
+                |i| transcript.received_authed().contains(&i),
+                &reveal_config.recv,
+                false,
+            ))
+            .collect();
+            assertion::evaluate(&policy.assertions, &results, &whole_parts, session_data)
+        })
+        .unwrap_or_default();
+    for outcome in &assertions {
+        info!(
+            "[{}] Assertion {}: {}",
+            session_id,
+            outcome.name,
+            if outcome.passed { "passed" } else { "failed" }
+        );
+    }
+
+    Ok(RevealedConnection {
+        results,
+        assertions,
+        hashes: collect_attested_hashes(reveal_config, commitments),
+        commitments: commitments
+            .iter()
+            .filter_map(|commitment| CommitmentInfo::new(commitment, *index))
+            .collect(),
+        // Create redacted transcript - only revealed ranges are visible
+        transcript: RedactedTranscript::from_transcript(sent_bytes, recv_bytes, reveal_config),
+    })
+}
+
+// Names the connection an error is about, in sessions with more than one
+fn connection_error(config: &SessionConfig, index: usize, error: SessionError) -> SessionError {
+    if config.connections > 1 {
+        error.with_connection(index)
+    } else {
+        error
+    }
 }
 
 /// Validates that all ranges in reveal config are fully within authenticated transcript ranges.
@@ -1185,13 +2826,16 @@ async fn run_verifier_task(
 /// "Authenticated" means either:
 /// - Revealed as plaintext (in `transcript.sent_authed()` / `received_authed()`), or
 /// - Hash-committed via `TranscriptCommitment::Hash` (the prover proved knowledge of
-///   plaintext whose hash matches the commitment; the range itself is bound).
+///   plaintext whose hash matches the commitment; the range itself is bound), or
+/// - For ENCODING handlers, within the `encoded` ranges of a
+///   `TranscriptCommitment::Encoding`, as named in the prover's commit request.
 ///
 /// Returns error with (direction, start, end) if any range contains unauthenticated data.
 fn verify_reveal_config(
     reveal_config: &RevealConfig,
     transcript: &PartialTranscript,
     transcript_commitments: &[tlsn::transcript::TranscriptCommitment],
+    encoded: &EncodedRanges,
 ) -> Result<(), (String, usize, usize)> {
     use tlsn::transcript::{Direction, TranscriptCommitment};
 
@@ -1207,30 +2851,220 @@ fn verify_reveal_config(
         }
     }
 
+    // Encoding-committed ranges, if the prover made an encoding commitment
+    let no_encoding = EncodedRanges::default();
+    let encoded = if commitment::has_encoding(transcript_commitments) {
+        encoded
+    } else {
+        &no_encoding
+    };
+
+    // A HASH range must be covered by one hash commitment made with the
+    // algorithm it claims, so the digest it reports is the one for that range
     fn validate_ranges_against_auth_set(
         ranges: &[RangeWithHandler],
         auth_set: &RangeSet<usize>,
-        direction: &str,
+        encoded_set: &RangeSet<usize>,
+        commitments: &[TranscriptCommitment],
+        direction: Direction,
     ) -> Result<(), (String, usize, usize)> {
+        let direction_label = match direction {
+            Direction::Sent => "sent",
+            Direction::Received => "recv",
+        };
         for range in ranges {
-            if !(range.start..range.end).all(|i| auth_set.contains(&i)) {
-                return Err((direction.to_string(), range.start, range.end));
+            let authenticated = match range.handler.action {
+                HandlerAction::Hash { algorithm } => {
+                    find_hash_digest(commitments, direction, range.start, range.end, algorithm)
+                        .is_some()
+                }
+                HandlerAction::Encoding => {
+                    (range.start..range.end).all(|i| encoded_set.contains(&i))
+                }
+                HandlerAction::Reveal => (range.start..range.end).all(|i| auth_set.contains(&i)),
+            };
+            if !authenticated {
+                return Err((direction_label.to_string(), range.start, range.end));
             }
 
             debug!(
                 "✅ {} range [{}, {}) validated - fully within authenticated ranges",
-                direction, range.start, range.end
+                direction_label, range.start, range.end
             );
         }
         Ok(())
     }
 
-    validate_ranges_against_auth_set(&reveal_config.sent, &sent_auth, "sent")?;
-    validate_ranges_against_auth_set(&reveal_config.recv, &recv_auth, "recv")?;
+    validate_ranges_against_auth_set(
+        &reveal_config.sent,
+        &sent_auth,
+        &encoded.sent,
+        transcript_commitments,
+        Direction::Sent,
+    )?;
+    validate_ranges_against_auth_set(
+        &reveal_config.recv,
+        &recv_auth,
+        &encoded.recv,
+        transcript_commitments,
+        Direction::Received,
+    )?;
+
+    Ok(())
+}
+
+// Helper function to persist a finished session, if a store is configured
+async fn persist_session(state: &Arc<AppState>, session: StoredSession) {
+    let Some(store) = state.store.clone() else {
+        return;
+    };
+    let session_id = session.id.clone();
+    match tokio::task::spawn_blocking(move || store.put(&session)).await {
+        Ok(Ok(())) => debug!("[{}] Session result persisted", session_id),
+        Ok(Err(e)) => error!(
+            "[{}] ❌ Failed to persist session result: {}",
+            session_id, e
+        ),
+        Err(e) => error!("[{}] ❌ Persist task failed: {}", session_id, e),
+    }
+}
+
+// Helper function to report a failed session to the client and, if its
+// webhook asks for failures, to the webhook, then persist it and clean it
+// up. `reason` is the error class recorded in metrics.
+#[allow(clippy::too_many_arguments)]
+async fn fail_session(
+    state: &Arc<AppState>,
+    session_id: &str,
+    config: &SessionConfig,
+    session_data: &HashMap<String, String>,
+    server_name: Option<String>,
+    result_tx: oneshot::Sender<VerificationResult>,
+    reason: &str,
+    error: SessionError,
+) {
+    error!("[{}] ❌ {}", session_id, error);
+    state.metrics.session_failed(server_name.as_deref(), reason);
+    if let Some(server_name) = server_name.as_deref() {
+        if state
+            .config
+            .get_webhook(server_name)
+            .is_some_and(|webhook| webhook.notify_failures)
+        {
+            let payload = FailedSessionWebhookPayload {
+                server_name: server_name.to_string(),
+                session: SessionInfo::new(session_id, config.tenant.clone(), session_data),
+                error: error.clone(),
+            };
+            match serde_json::to_value(&payload) {
+                Ok(payload) => {
+                    state
+                        .webhooks
+                        .enqueue(session_id, server_name, payload)
+                        .await
+                }
+                Err(e) => error!(
+                    "[{}] ❌ Failed to encode webhook payload: {}",
+                    session_id, e
+                ),
+            }
+        }
+    }
+    persist_session(
+        state,
+        StoredSession::failed(
+            session_id,
+            config.tenant.clone(),
+            config.registered_at,
+            session_data,
+            server_name,
+            error.clone(),
+        ),
+    )
+    .await;
+    let _ = result_tx.send(VerificationResult {
+        results: vec![],
+        assertions: vec![],
+        commitments: vec![],
+        attestation: None,
+        error: Some(error),
+    });
+    cleanup_session(state, session_id).await;
+}
+
+// Checks the server-wide and tenant session quotas for a new registration,
+// returning the metrics reason and message of the first one used up. Called
+// with the sessions lock held, so concurrent registrations cannot overshoot
+// and none is admitted after a shutdown drain has begun.
+fn check_session_quotas(
+    state: &AppState,
+    sessions: &HashMap<String, SessionData>,
+    tenant: Option<&str>,
+) -> Result<(), (&'static str, String)> {
+    if state.shutdown.is_draining() {
+        return Err(("draining", shutdown::DRAINING_MESSAGE.to_string()));
+    }
+    if let Some(max) = state
+        .config
+        .limits
+        .max_concurrent_sessions
+        .filter(|&max| sessions.len() >= max)
+    {
+        return Err((
+            "capacity",
+            format!("Server is at capacity ({} concurrent sessions)", max),
+        ));
+    }
 
+    let Some((tenant, quota)) = tenant.and_then(|tenant| {
+        let quota = state.config.auth.as_ref()?.quota(tenant)?;
+        Some((tenant, quota))
+    }) else {
+        return Ok(());
+    };
+    if let Some(max) = quota.max_concurrent_sessions {
+        let active = sessions
+            .values()
+            .filter(|session| session.tenant.as_deref() == Some(tenant))
+            .count();
+        if active >= max {
+            return Err((
+                "tenant_quota",
+                format!(
+                    "Tenant {} is at its limit of {} concurrent sessions",
+                    tenant, max
+                ),
+            ));
+        }
+    }
+    if let Some(per_minute) = quota.sessions_per_minute {
+        if !state
+            .session_rates
+            .try_acquire(tenant, per_minute, Instant::now())
+        {
+            return Err((
+                "tenant_quota",
+                format!(
+                    "Tenant {} exceeded its limit of {} sessions per minute",
+                    tenant, per_minute
+                ),
+            ));
+        }
+    }
     Ok(())
 }
 
+// Shows a verified server name in the admin session list, unless the session
+// declared one
+async fn record_server_name(state: &Arc<AppState>, session_id: &str, server_name: &str) {
+    if let Some(session) = state.sessions.lock().await.get_mut(session_id) {
+        session
+            .overview
+            .server_name
+            .get_or_insert_with(|| server_name.to_string());
+    }
+}
+
 // Helper function to clean up session from state
 async fn cleanup_session(state: &Arc<AppState>, session_id: &str) {
     let mut sessions = state.sessions.lock().await;
@@ -1244,11 +3078,16 @@ async fn cleanup_session(state: &Arc<AppState>, session_id: &str) {
 /// - For REVEAL handlers, returns the revealed plaintext bytes as UTF-8.
 /// - For HASH handlers, returns the hex-encoded hash digest from the matching
 ///   `TranscriptCommitment::Hash` (plaintext was never revealed).
+/// - For ENCODING handlers, returns the hex-encoded Merkle root of the
+///   `TranscriptCommitment::Encoding`.
+///
+/// Results are tagged with the range's connection and its `server_name`.
 fn process_ranges(
     ranges: &[RangeWithHandler],
     bytes: &[u8],
     direction: tlsn::transcript::Direction,
     transcript_commitments: &[tlsn::transcript::TranscriptCommitment],
+    server_name: &str,
     direction_label: &str,
     session_id: &str,
 ) -> Vec<HandlerResult> {
@@ -1256,11 +3095,12 @@ fn process_ranges(
         .iter()
         .map(|range_with_handler| {
             let value = match range_with_handler.handler.action {
-                HandlerAction::Hash { .. } => find_hash_digest(
+                HandlerAction::Hash { algorithm } => find_hash_digest(
                     transcript_commitments,
                     direction,
                     range_with_handler.start,
                     range_with_handler.end,
+                    algorithm,
                 )
                 .unwrap_or_else(|| {
                     format!(
@@ -1268,6 +3108,8 @@ fn process_ranges(
                         range_with_handler.start, range_with_handler.end
                     )
                 }),
+                HandlerAction::Encoding => commitment::encoding_root(transcript_commitments)
+                    .unwrap_or_else(|| "ERROR: No encoding commitment".to_string()),
                 HandlerAction::Reveal => {
                     if range_with_handler.start < bytes.len()
                         && range_with_handler.end <= bytes.len()
@@ -1295,22 +3137,83 @@ fn process_ranges(
                 value.len()
             );
 
+            let (parsed, key) = match (
+                &range_with_handler.handler.action,
+                &range_with_handler.handler.params,
+            ) {
+                (HandlerAction::Reveal, Some(params)) if params.path.is_some() => {
+                    decode_json_field(&value, params)
+                }
+                _ => (None, None),
+            };
+
             HandlerResult {
                 handler: range_with_handler.handler.clone(),
                 value,
+                parsed,
+                key,
+                connection: range_with_handler.connection,
+                server_name: server_name.to_string(),
+                label_verified: true,
             }
         })
         .collect()
 }
 
-/// Finds the hash digest (hex) for a given range+direction from the list of
-/// transcript commitments. Returns `None` if no `Hash` commitment covers the
-/// exact range for the requested direction.
+/// Decodes the revealed bytes of a JSON `path` handler into a typed value and
+/// the key it was found under.
+///
+/// Depending on `hideKey` / `hideValue` the revealed bytes are `"key": value`,
+/// just the value, or just the key. The key is only reported when it is in
+/// the revealed bytes; the prover-supplied `path` is never taken as proof of
+/// it.
+pub(crate) fn decode_json_field(
+    revealed: &str,
+    params: &HandlerParams,
+) -> (Option<serde_json::Value>, Option<String>) {
+    let revealed = revealed.trim().trim_end_matches(',').trim_end();
+
+    if params.hide_value == Some(true) {
+        return (None, serde_json::from_str::<String>(revealed).ok());
+    }
+
+    if let Some((key, value)) = split_json_member(revealed) {
+        return (Some(value), Some(key));
+    }
+
+    (serde_json::from_str(revealed).ok(), None)
+}
+
+/// Splits a `"key": value` object member into its key and value.
+fn split_json_member(member: &str) -> Option<(String, serde_json::Value)> {
+    if !member.starts_with('"') {
+        return None;
+    }
+    let mut escaped = false;
+    let key_end = member
+        .char_indices()
+        .skip(1)
+        .find(|&(_, c)| {
+            let closes = c == '"' && !escaped;
+            escaped = c == '\\' && !escaped;
+            closes
+        })?
+        .0
+        + 1;
+    let key = serde_json::from_str(&member[..key_end]).ok()?;
+    let value = member[key_end..].trim_start().strip_prefix(':')?;
+    Some((key, serde_json::from_str(value).ok()?))
+}
+
+/// Finds the hash digest (hex) for a given range+direction+algorithm from
+/// the list of transcript commitments. Returns `None` if no `Hash` commitment
+/// with that algorithm covers the exact range for the requested direction.
 fn find_hash_digest(
     commitments: &[tlsn::transcript::TranscriptCommitment],
     direction: tlsn::transcript::Direction,
     start: usize,
     end: usize,
+    algorithm: HashAlgorithm,
 ) -> Option<String> {
     use tlsn::transcript::TranscriptCommitment;
 
@@ -1318,6 +3221,7 @@ fn find_hash_digest(
         if let TranscriptCommitment::Hash(hash) = commitment {
             if hash.direction == direction
                 && (start..end).all(|i| hash.idx.contains(&i))
+                && hash.hash.alg.to_string().to_uppercase() == policy::label(&algorithm)
             {
                 let bytes = hash.hash.value.as_bytes();
                 let mut hex_str = String::with_capacity(bytes.len() * 2);
@@ -1332,40 +3236,35 @@ fn find_hash_digest(
     None
 }
 
+/// Collects the hash digests for every HASH handler in the reveal config so
+/// they can be covered by an attestation.
+fn collect_attested_hashes(
+    reveal_config: &RevealConfig,
+    commitments: &[tlsn::transcript::TranscriptCommitment],
+) -> Vec<AttestedHash> {
+    use tlsn::transcript::Direction;
 
-/// Send webhook POST request to configured endpoint
-async fn send_webhook(config: &WebhookConfig, payload: &WebhookPayload, session_id: &str) {
-    let client = reqwest::Client::new();
-
-    let mut request = client.post(&config.url).json(payload);
-
-    // Add custom headers from config
-    for (key, value) in &config.headers {
-        request = request.header(key, value);
-    }
+    let sent = reveal_config.sent.iter().map(|r| (Direction::Sent, r));
+    let recv = reveal_config.recv.iter().map(|r| (Direction::Received, r));
 
-    match request.send().await {
-        Ok(response) => {
-            if response.status().is_success() {
-                info!(
-                    "[{}] ✅ Webhook POST successful: {}",
-                    session_id, config.url
-                );
-            } else {
-                error!(
-                    "[{}] ❌ Webhook POST failed with status {}: {}",
-                    session_id,
-                    response.status(),
-                    config.url
-                );
-            }
-        }
-        Err(e) => {
-            // Log error but don't fail the verification
-            error!(
-                "[{}] ❌ Webhook POST error: {} - {}",
-                session_id, config.url, e
-            );
-        }
-    }
+    sent.chain(recv)
+        .filter_map(|(direction, range)| {
+            let HandlerAction::Hash { algorithm } = range.handler.action else {
+                return None;
+            };
+            let digest =
+                find_hash_digest(commitments, direction, range.start, range.end, algorithm)?;
+            Some(AttestedHash {
+                connection: range.connection,
+                direction: match direction {
+                    Direction::Sent => "sent".to_string(),
+                    Direction::Received => "recv".to_string(),
+                },
+                start: range.start,
+                end: range.end,
+                algorithm: policy::label(&algorithm),
+                digest,
+            })
+        })
+        .collect()
 }
@@ -0,0 +1,341 @@
+//! Prometheus metrics exposed on `GET /metrics`.
+//!
+//! Every metric lives in the registry of its [`Metrics`] instance rather than
+//! the process-wide default registry, so tests can build independent states.
+//!
+//! Server names are chosen by provers, so a name only becomes a `server_name`
+//! label value once a policy or webhook entry names it or a session verifies
+//! it; until then it is reported as `unknown`, as is a name that is not known
+//! yet. Verified names are capped at [`MAX_SERVER_NAME_LABELS`] distinct
+//! values; later ones are reported as `other`.
+
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+
+/// Distinct `server_name` label values before falling back to `other`
+pub(crate) const MAX_SERVER_NAME_LABELS: usize = 200;
+
+/// Label for metrics recorded before the server name is known, or with a
+/// name that is neither configured nor verified
+pub(crate) const UNKNOWN_SERVER_NAME: &str = "unknown";
+
+const OTHER_SERVER_NAME: &str = "other";
+
+/// Buckets (seconds) for waits and verification runs, up to the 120s timeout
+const DURATION_BUCKETS: &[f64] = &[0.1, 0.5, 1.0, 2.5, 5.0, 10.0, 20.0, 30.0, 60.0, 120.0];
+
+/// Buckets (seconds) for webhook request latency
+const WEBHOOK_BUCKETS: &[f64] = &[0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// Buckets (seconds) for upstream connects, up to the default 10s timeout
+const CONNECT_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 10.0];
+
+pub(crate) struct Metrics {
+    registry: Registry,
+    server_names: Mutex<HashSet<String>>,
+    sessions_registered: IntCounterVec,
+    sessions_completed: IntCounterVec,
+    sessions_failed: IntCounterVec,
+    verifier_mode: IntCounterVec,
+    verification_duration: HistogramVec,
+    prover_socket_wait: Histogram,
+    reveal_config_wait: HistogramVec,
+    proxy_bytes: IntCounterVec,
+    proxy_rejections: IntCounterVec,
+    upstream_connections: IntCounterVec,
+    upstream_connect_duration: HistogramVec,
+    dns_lookups: IntCounterVec,
+    webhook_deliveries: IntCounterVec,
+    webhook_latency: HistogramVec,
+    webhook_evictions: IntCounterVec,
+    active_sessions: IntGauge,
+}
+
+impl Metrics {
+    pub(crate) fn new() -> Self {
+        let registry = Registry::new();
+
+        let counter = |name: &str, help: &str, labels: &[&str]| {
+            let counter = IntCounterVec::new(Opts::new(name, help), labels)
+                .expect("valid counter definition");
+            registry
+                .register(Box::new(counter.clone()))
+                .expect("metric registered once");
+            counter
+        };
+        let histogram = |name: &str, help: &str, buckets: &[f64], labels: &[&str]| {
+            let histogram = HistogramVec::new(
+                HistogramOpts::new(name, help).buckets(buckets.to_vec()),
+                labels,
+            )
+            .expect("valid histogram definition");
+            registry
+                .register(Box::new(histogram.clone()))
+                .expect("metric registered once");
+            histogram
+        };
+
+        let sessions_registered = counter(
+            "tlsn_sessions_registered_total",
+            "Sessions registered on /session",
+            &["server_name"],
+        );
+        let sessions_completed = counter(
+            "tlsn_sessions_completed_total",
+            "Sessions that completed verification",
+            &["server_name"],
+        );
+        let sessions_failed = counter(
+            "tlsn_sessions_failed_total",
+            "Sessions that failed, by error class",
+            &["server_name", "reason"],
+        );
+        let verifier_mode = counter(
+            "tlsn_verifier_mode_total",
+            "Commitments started, by mode chosen by the prover",
+            &["mode", "server_name"],
+        );
+        let verification_duration = histogram(
+            "tlsn_verification_duration_seconds",
+            "Time spent in the TLS verification protocol",
+            DURATION_BUCKETS,
+            &["server_name"],
+        );
+        let prover_socket_wait = histogram(
+            "tlsn_prover_socket_wait_seconds",
+            "Time from registration until the prover connected to /verifier",
+            DURATION_BUCKETS,
+            &[],
+        )
+        .with_label_values(&[] as &[&str]);
+        let reveal_config_wait = histogram(
+            "tlsn_reveal_config_wait_seconds",
+            "Time from the end of verification until the reveal config arrived",
+            DURATION_BUCKETS,
+            &["server_name"],
+        );
+        let proxy_bytes = counter(
+            "tlsn_proxy_bytes_total",
+            "Bytes forwarded by the /proxy WebSocket-to-TCP bridge",
+            &["direction", "server_name"],
+        );
+        let proxy_rejections = counter(
+            "tlsn_proxy_rejections_total",
+            "/proxy requests refused before the upgrade, by reason",
+            &["reason"],
+        );
+        let upstream_connections = counter(
+            "tlsn_upstream_connections_total",
+            "Outbound connections to servers, by route and outcome",
+            &["route", "outcome"],
+        );
+        let upstream_connect_duration = histogram(
+            "tlsn_upstream_connect_seconds",
+            "Time to check, resolve and connect to a server",
+            CONNECT_BUCKETS,
+            &["route"],
+        );
+        let dns_lookups = counter(
+            "tlsn_dns_lookups_total",
+            "Server name lookups, by whether the cache answered them",
+            &["result"],
+        );
+        let webhook_deliveries = counter(
+            "tlsn_webhook_deliveries_total",
+            "Webhook delivery attempts, by outcome",
+            &["server_name", "outcome"],
+        );
+        let webhook_latency = histogram(
+            "tlsn_webhook_latency_seconds",
+            "Latency of webhook delivery attempts",
+            WEBHOOK_BUCKETS,
+            &["server_name"],
+        );
+        let webhook_evictions = counter(
+            "tlsn_webhook_evictions_total",
+            "Queued webhook deliveries dropped to stay within the queue caps, by status",
+            &["status"],
+        );
+
+        let active_sessions =
+            IntGauge::new("tlsn_active_sessions", "Sessions currently held in memory")
+                .expect("valid gauge definition");
+        registry
+            .register(Box::new(active_sessions.clone()))
+            .expect("metric registered once");
+
+        Self {
+            registry,
+            server_names: Mutex::new(HashSet::new()),
+            sessions_registered,
+            sessions_completed,
+            sessions_failed,
+            verifier_mode,
+            verification_duration,
+            prover_socket_wait,
+            reveal_config_wait,
+            proxy_bytes,
+            proxy_rejections,
+            upstream_connections,
+            upstream_connect_duration,
+            dns_lookups,
+            webhook_deliveries,
+            webhook_latency,
+            webhook_evictions,
+            active_sessions,
+        }
+    }
+
+    /// Labels the server names of the config's policy and webhook entries,
+    /// however many there are.
+    pub(crate) fn with_server_names<'a>(self, names: impl IntoIterator<Item = &'a str>) -> Self {
+        self.server_names
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .extend(names.into_iter().map(str::to_string));
+        self
+    }
+
+    /// Label value for a server name that may be unverified: the name if it
+    /// is configured or was verified before, `unknown` otherwise.
+    fn server_label(&self, server_name: Option<&str>) -> String {
+        let seen = self.server_names.lock().unwrap_or_else(|e| e.into_inner());
+        match server_name {
+            Some(server_name) if seen.contains(server_name) => server_name.to_string(),
+            _ => UNKNOWN_SERVER_NAME.to_string(),
+        }
+    }
+
+    /// Label value for a verified server name, bounded to
+    /// [`MAX_SERVER_NAME_LABELS`].
+    fn verified_server_label(&self, server_name: &str) -> String {
+        let mut seen = self.server_names.lock().unwrap_or_else(|e| e.into_inner());
+        if seen.contains(server_name) {
+            return server_name.to_string();
+        }
+        if seen.len() >= MAX_SERVER_NAME_LABELS {
+            return OTHER_SERVER_NAME.to_string();
+        }
+        seen.insert(server_name.to_string());
+        server_name.to_string()
+    }
+
+    pub(crate) fn session_registered(&self, server_name: Option<&str>) {
+        let label = self.server_label(server_name);
+        self.sessions_registered.with_label_values(&[&label]).inc();
+    }
+
+    pub(crate) fn session_completed(&self, server_name: &str) {
+        let label = self.verified_server_label(server_name);
+        self.sessions_completed.with_label_values(&[&label]).inc();
+    }
+
+    /// Records a failed session. `reason` is a fixed error class such as
+    /// `verification_timeout` or `policy`, never a free-form message.
+    pub(crate) fn session_failed(&self, server_name: Option<&str>, reason: &str) {
+        let label = self.server_label(server_name);
+        self.sessions_failed
+            .with_label_values(&[&label, reason])
+            .inc();
+    }
+
+    pub(crate) fn verifier_mode(&self, mode: &str, server_name: Option<&str>) {
+        let label = self.server_label(server_name);
+        self.verifier_mode.with_label_values(&[mode, &label]).inc();
+    }
+
+    /// Records a verification run; `server_name` is the verified name, or
+    /// `None` when the verification failed.
+    pub(crate) fn verification_duration(&self, server_name: Option<&str>, elapsed: Duration) {
+        let label = match server_name {
+            Some(server_name) => self.verified_server_label(server_name),
+            None => UNKNOWN_SERVER_NAME.to_string(),
+        };
+        self.verification_duration
+            .with_label_values(&[&label])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub(crate) fn prover_socket_wait(&self, elapsed: Duration) {
+        self.prover_socket_wait.observe(elapsed.as_secs_f64());
+    }
+
+    pub(crate) fn reveal_config_wait(&self, server_name: &str, elapsed: Duration) {
+        let label = self.server_label(Some(server_name));
+        self.reveal_config_wait
+            .with_label_values(&[&label])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    /// Records bytes forwarded by the proxy; `direction` is `to_server` or
+    /// `from_server`.
+    pub(crate) fn proxy_bytes(&self, direction: &str, server_name: &str, bytes: u64) {
+        let label = self.server_label(Some(server_name));
+        self.proxy_bytes
+            .with_label_values(&[direction, &label])
+            .inc_by(bytes);
+    }
+
+    /// Records a refused `/proxy` request; `reason` is a fixed error class.
+    pub(crate) fn proxy_rejected(&self, reason: &str) {
+        self.proxy_rejections.with_label_values(&[reason]).inc();
+    }
+
+    /// Records an outbound connection attempt; `route` is `proxy` or
+    /// `verifier`, `outcome` is `connected` or a proxy rejection reason.
+    pub(crate) fn upstream_connect(&self, route: &str, outcome: &str, elapsed: Duration) {
+        self.upstream_connections
+            .with_label_values(&[route, outcome])
+            .inc();
+        self.upstream_connect_duration
+            .with_label_values(&[route])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    /// Records a server name lookup; `result` is `hit` or `miss`.
+    pub(crate) fn dns_lookup(&self, result: &str) {
+        self.dns_lookups.with_label_values(&[result]).inc();
+    }
+
+    /// Records one webhook attempt; `outcome` is `success` or `failure`.
+    pub(crate) fn webhook_attempt(&self, server_name: &str, outcome: &str, elapsed: Duration) {
+        let label = self.server_label(Some(server_name));
+        self.webhook_deliveries
+            .with_label_values(&[&label, outcome])
+            .inc();
+        self.webhook_latency
+            .with_label_values(&[&label])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    /// Records deliveries dropped from the queue; `status` is `pending` or
+    /// `dead`.
+    pub(crate) fn webhook_evicted(&self, status: &str, count: usize) {
+        self.webhook_evictions
+            .with_label_values(&[status])
+            .inc_by(count as u64);
+    }
+
+    pub(crate) fn set_active_sessions(&self, count: usize) {
+        self.active_sessions.set(count as i64);
+    }
+
+    /// Renders every metric in the Prometheus text exposition format.
+    pub(crate) fn render(&self) -> Result<String, eyre::ErrReport> {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(String::from_utf8(buffer)?)
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,120 @@
+//! Openings of hash commitments.
+//!
+//! A HASH handler commits to a range as `H(plaintext || blinder)`, where the
+//! blinder is a random 16-byte salt that only the prover keeps. Low-entropy
+//! values such as a zip code or a yes/no flag therefore cannot be recovered
+//! from the digest by brute force. To disclose a committed field to a relying
+//! party later, the prover hands over the plaintext and the blinder, and
+//! `POST /sessions/{id}/openings` checks them against the commitment stored
+//! with the session.
+
+use axum::http::StatusCode;
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
+
+use crate::commitment::{CommitmentInfo, CommitmentKind};
+
+/// Length of a hash commitment blinder in bytes
+pub(crate) const BLINDER_LEN: usize = 16;
+
+/// Body of `POST /sessions/{id}/openings`
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct OpeningRequest {
+    /// Index of the session's TLS connection the range is in
+    #[serde(default)]
+    pub(crate) connection: usize,
+    /// `sent` or `recv`
+    pub(crate) direction: String,
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+    /// Claimed plaintext of the range, as UTF-8
+    pub(crate) plaintext: String,
+    /// Hex-encoded blinder the prover kept
+    pub(crate) blinder: String,
+}
+
+/// Outcome of checking an opening
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct OpeningOutcome {
+    /// Whether plaintext and blinder hash to the committed digest
+    pub(crate) valid: bool,
+    /// Hash algorithm of the commitment, e.g. `SHA256`
+    pub(crate) algorithm: String,
+    /// Hex-encoded committed digest
+    pub(crate) digest: String,
+}
+
+/// Checks an opening against the hash commitment to exactly its range among
+/// a session's `commitments`.
+pub(crate) fn verify(
+    commitments: &[CommitmentInfo],
+    opening: &OpeningRequest,
+) -> Result<OpeningOutcome, (StatusCode, String)> {
+    let blinder = hex::decode(&opening.blinder)
+        .ok()
+        .filter(|blinder| blinder.len() == BLINDER_LEN)
+        .ok_or((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!("blinder must be {} hex-encoded bytes", BLINDER_LEN),
+        ))?;
+
+    let commitment = commitments
+        .iter()
+        .find(|commitment| {
+            commitment.kind == CommitmentKind::Hash
+                && commitment.connection == opening.connection
+                && commitment.direction.as_deref() == Some(opening.direction.as_str())
+                && commitment.ranges == [[opening.start, opening.end]]
+        })
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                format!(
+                    "No hash commitment to {} range [{}, {}) of connection {}",
+                    opening.direction, opening.start, opening.end, opening.connection
+                ),
+            )
+        })?;
+
+    let digest = blinded_digest(
+        &commitment.algorithm,
+        opening.plaintext.as_bytes(),
+        &blinder,
+    )
+    .ok_or_else(|| {
+        (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!("Unsupported hash algorithm {}", commitment.algorithm),
+        )
+    })?;
+
+    Ok(OpeningOutcome {
+        valid: hex::encode(digest) == commitment.value,
+        algorithm: commitment.algorithm.clone(),
+        digest: commitment.value.clone(),
+    })
+}
+
+/// `H(plaintext || blinder)` with the named algorithm, or `None` if it is
+/// not one HASH handlers may use.
+pub(crate) fn blinded_digest(algorithm: &str, plaintext: &[u8], blinder: &[u8]) -> Option<Vec<u8>> {
+    fn digest<D: Digest>(plaintext: &[u8], blinder: &[u8]) -> Vec<u8> {
+        D::new()
+            .chain_update(plaintext)
+            .chain_update(blinder)
+            .finalize()
+            .to_vec()
+    }
+
+    match algorithm {
+        "SHA256" => Some(digest::<sha2::Sha256>(plaintext, blinder)),
+        "KECCAK256" => Some(digest::<sha3::Keccak256>(plaintext, blinder)),
+        "BLAKE3" => {
+            let mut hasher = blake3::Hasher::new();
+            hasher.update(plaintext);
+            hasher.update(blinder);
+            Some(hasher.finalize().as_bytes().to_vec())
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,289 @@
+//! Per-server-name verification policies.
+//!
+//! The `policies` section of `config.yaml` is keyed by server name like
+//! `webhooks` (see [`crate::match_server_name`]). A server name without a
+//! matching policy is unrestricted.
+//!
+//! Policies are enforced as early as the server name is known:
+//! - at `register`, when the client declares `serverName` (host allowed,
+//!   requested data limits within the caps),
+//! - in Proxy mode, before the verifier connects to the server (host allowed,
+//!   port among `ports`),
+//! - after verification, against the verified server name, the transcript
+//!   lengths and finally the reveal config (including, with
+//!   `require_verified_labels`, that every range label could be checked
+//!   against the parsed HTTP transcript even where `allow_unverified_labels`
+//!   accepts unverifiable ones). Required and forbidden parts are located in
+//!   the parsed transcript, so they are judged by the revealed bytes
+//!   whatever the prover labelled them.
+//!
+//! A policy can also carry assertions over the revealed values; those are
+//! reported rather than enforced (see `assertion.rs`).
+
+use eyre::eyre;
+use serde::{Deserialize, Serialize};
+
+use crate::assertion::AssertionConfig;
+use crate::http_layout;
+use crate::{HandlerPart, HandlerType, HashAlgorithm};
+
+/// Policy for one server name.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct PolicyConfig {
+    /// Whether verifications for this server name are allowed at all
+    #[serde(default = "default_allow")]
+    pub(crate) allow: bool,
+    /// Upper bound on `maxSentData` and on the sent transcript length
+    #[serde(default)]
+    pub(crate) max_sent_data: Option<usize>,
+    /// Upper bound on `maxRecvData` and on the received transcript length
+    #[serde(default)]
+    pub(crate) max_recv_data: Option<usize>,
+    /// Ports the verifier may dial in Proxy mode; any port when empty
+    #[serde(default)]
+    pub(crate) ports: Vec<u16>,
+    /// Parts that must be revealed in plaintext
+    #[serde(default)]
+    pub(crate) require_reveal: Vec<PartRule>,
+    /// Parts that must not be revealed in plaintext (hashing is allowed)
+    #[serde(default)]
+    pub(crate) forbid_reveal: Vec<PartRule>,
+    /// Allowed algorithms for HASH handlers; any algorithm when absent
+    #[serde(default)]
+    pub(crate) hash_algorithms: Option<Vec<HashAlgorithm>>,
+    /// Reject ranges whose HTTP part label cannot be verified because the
+    /// surrounding transcript bytes are hidden (see `http_layout.rs`), even
+    /// when `allow_unverified_labels` is set
+    #[serde(default)]
+    pub(crate) require_verified_labels: bool,
+    /// Assertions evaluated over the revealed values
+    #[serde(default)]
+    pub(crate) assertions: Vec<AssertionConfig>,
+}
+
+fn default_allow() -> bool {
+    true
+}
+
+/// A transcript part referenced by a policy.
+///
+/// An `ALL` range covers every part. For `HEADERS`, `header` narrows the
+/// rule to that header's lines.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct PartRule {
+    #[serde(rename = "type")]
+    pub(crate) handler_type: HandlerType,
+    pub(crate) part: HandlerPart,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) header: Option<String>,
+}
+
+impl PartRule {
+    /// Whether the revealed bytes disclose this part in full, located in the
+    /// parsed transcript rather than by range labels.
+    fn fully_revealed(
+        &self,
+        bytes: &[u8],
+        is_known: impl Fn(usize) -> bool,
+        is_request: bool,
+    ) -> bool {
+        http_layout::part_revealed(
+            bytes,
+            is_known,
+            &self.part,
+            self.header.as_deref(),
+            is_request,
+        )
+    }
+
+    /// First run of revealed bytes that may belong to this part, located in
+    /// the parsed transcript rather than by range labels.
+    fn revealed_in(
+        &self,
+        bytes: &[u8],
+        is_known: impl Fn(usize) -> bool,
+        is_request: bool,
+    ) -> Option<(usize, usize)> {
+        let regions = http_layout::part_regions(
+            bytes,
+            &is_known,
+            &self.part,
+            self.header.as_deref(),
+            is_request,
+        );
+        regions.into_iter().find_map(|(start, end)| {
+            let first = (start..end).find(|&i| is_known(i))?;
+            let last = (first..end).find(|&i| !is_known(i)).unwrap_or(end);
+            Some((first, last))
+        })
+    }
+
+    fn describe(&self) -> String {
+        let mut description = format!("{} {}", label(&self.handler_type), label(&self.part));
+        if let Some(header) = &self.header {
+            description.push_str(&format!(" ({})", header));
+        }
+        description
+    }
+}
+
+/// Wire name of an enum value, e.g. `STATUS_CODE`.
+pub(crate) fn label<T: Serialize>(value: &T) -> String {
+    serde_json::to_value(value)
+        .ok()
+        .and_then(|value| value.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+/// The enforced parts of a policy, as published on `/info` so clients can
+/// check a request against it before registering.
+#[derive(Debug, Serialize)]
+pub(crate) struct PolicySummary<'a> {
+    pub(crate) server_name: &'a str,
+    pub(crate) allow: bool,
+    pub(crate) max_sent_data: Option<usize>,
+    pub(crate) max_recv_data: Option<usize>,
+    pub(crate) ports: &'a [u16],
+    pub(crate) require_reveal: &'a [PartRule],
+    pub(crate) forbid_reveal: &'a [PartRule],
+    pub(crate) hash_algorithms: Option<&'a [HashAlgorithm]>,
+    pub(crate) require_verified_labels: bool,
+}
+
+impl PolicyConfig {
+    /// Summary of this policy for the server name (pattern) it is keyed by.
+    pub(crate) fn summary<'a>(&'a self, server_name: &'a str) -> PolicySummary<'a> {
+        PolicySummary {
+            server_name,
+            allow: self.allow,
+            max_sent_data: self.max_sent_data,
+            max_recv_data: self.max_recv_data,
+            ports: &self.ports,
+            require_reveal: &self.require_reveal,
+            forbid_reveal: &self.forbid_reveal,
+            hash_algorithms: self.hash_algorithms.as_deref(),
+            require_verified_labels: self.require_verified_labels,
+        }
+    }
+
+    /// Rejects server names the policy denies.
+    pub(crate) fn check_allowed(&self, server_name: &str) -> Result<(), String> {
+        if self.allow {
+            Ok(())
+        } else {
+            Err(format!(
+                "Policy violation: verification for {} is not allowed",
+                server_name
+            ))
+        }
+    }
+
+    /// Rejects server ports outside `ports`, if the policy lists any.
+    pub(crate) fn check_port(&self, port: u16) -> Result<(), String> {
+        if self.ports.is_empty() || self.ports.contains(&port) {
+            Ok(())
+        } else {
+            Err(format!(
+                "Policy violation: server port {} is not allowed",
+                port
+            ))
+        }
+    }
+
+    /// Rejects sent/recv sizes above the policy caps.
+    ///
+    /// Used both for the limits requested at `register` and for the lengths of
+    /// the verified transcript.
+    pub(crate) fn check_limits(&self, sent: usize, recv: usize) -> Result<(), String> {
+        if let Some(max) = self.max_sent_data.filter(|&max| sent > max) {
+            return Err(format!(
+                "Policy violation: sent data {} exceeds limit {}",
+                sent, max
+            ));
+        }
+        if let Some(max) = self.max_recv_data.filter(|&max| recv > max) {
+            return Err(format!(
+                "Policy violation: recv data {} exceeds limit {}",
+                recv, max
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks the policy's assertions, failing on the first that cannot be
+    /// evaluated.
+    pub(crate) fn validate(&self, server_name: &str) -> Result<(), eyre::ErrReport> {
+        for assertion in &self.assertions {
+            assertion.validate().map_err(|e| {
+                eyre!(
+                    "policies.{}: assertion `{}`: {}",
+                    server_name,
+                    assertion.name,
+                    e
+                )
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Checks the revealed transcript against the reveal rules.
+    ///
+    /// `sent` / `recv` are the verified transcript bytes and whether each byte
+    /// was revealed. Required parts must be revealed in full and forbidden
+    /// parts not at all, both judged by the revealed bytes themselves.
+    pub(crate) fn check_reveal(
+        &self,
+        sent: (&[u8], impl Fn(usize) -> bool),
+        recv: (&[u8], impl Fn(usize) -> bool),
+    ) -> Result<(), String> {
+        for rule in &self.require_reveal {
+            let revealed = match rule.handler_type {
+                HandlerType::Sent => rule.fully_revealed(sent.0, &sent.1, true),
+                HandlerType::Recv => rule.fully_revealed(recv.0, &recv.1, false),
+            };
+            if !revealed {
+                return Err(format!(
+                    "Policy violation: {} must be revealed",
+                    rule.describe()
+                ));
+            }
+        }
+
+        for rule in &self.forbid_reveal {
+            let revealed = match rule.handler_type {
+                HandlerType::Sent => rule.revealed_in(sent.0, &sent.1, true),
+                HandlerType::Recv => rule.revealed_in(recv.0, &recv.1, false),
+            };
+            if let Some((start, end)) = revealed {
+                return Err(format!(
+                    "Policy violation: {} must not be revealed (bytes [{}, {}))",
+                    rule.describe(),
+                    start,
+                    end
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks the algorithms of the prover's hash commitments, e.g. `SHA256`,
+    /// against `hash_algorithms`.
+    ///
+    /// These are the algorithms the commitments were made with, not the ones
+    /// HASH handlers claim.
+    pub(crate) fn check_hash_algorithms(&self, algorithms: &[String]) -> Result<(), String> {
+        let Some(allowed) = &self.hash_algorithms else {
+            return Ok(());
+        };
+        for algorithm in algorithms {
+            if !allowed.iter().any(|allowed| label(allowed) == *algorithm) {
+                return Err(format!(
+                    "Policy violation: hash algorithm {} is not allowed",
+                    algorithm
+                ));
+            }
+        }
+        Ok(())
+    }
+}
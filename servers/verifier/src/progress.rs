@@ -0,0 +1,78 @@
+//! Session progress events.
+//!
+//! The verifier task reports each phase a session passes through, from the
+//! prover connecting to the webhook being queued. Session sockets receive
+//! them as `progress` messages while they wait for the result, and a resumed
+//! socket first gets the ones it missed, so clients can show how far a
+//! session got and where it stalled. Phases up to `transcript_verified` are
+//! reported for each of the session's TLS connections and name it.
+
+use serde::Serialize;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// A phase of a session, in the order they are reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "phase", rename_all = "snake_case")]
+pub(crate) enum Phase {
+    /// The prover opened a `/verifier` socket
+    ProverConnected,
+    /// The prover picked a commit mode, `Mpc` or `Proxy`
+    ModeSelected { mode: &'static str },
+    /// The verifier accepted the prover's commitment; in Proxy mode this is
+    /// after connecting to the server
+    CommitmentAccepted,
+    /// The TLS connection with the server is complete
+    TlsComplete,
+    /// The prover's transcript proof verified
+    TranscriptVerified,
+    /// The reveal config passed range, label and policy checks
+    RevealConfigValidated,
+    /// The result was queued for webhook delivery
+    WebhookDispatched,
+}
+
+impl Phase {
+    /// Name of the phase as serialized, e.g. `tls_complete`
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Self::ProverConnected => "prover_connected",
+            Self::ModeSelected { .. } => "mode_selected",
+            Self::CommitmentAccepted => "commitment_accepted",
+            Self::TlsComplete => "tls_complete",
+            Self::TranscriptVerified => "transcript_verified",
+            Self::RevealConfigValidated => "reveal_config_validated",
+            Self::WebhookDispatched => "webhook_dispatched",
+        }
+    }
+}
+
+/// A phase with the time it was reached
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct ProgressEvent {
+    #[serde(flatten)]
+    pub(crate) phase: Phase,
+    /// Index of the TLS connection the phase is about, for per-connection
+    /// phases
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) connection: Option<usize>,
+    /// Unix timestamp in milliseconds
+    #[serde(rename = "timestampMs")]
+    pub(crate) timestamp_ms: u64,
+    /// Milliseconds since the session was registered
+    #[serde(rename = "elapsedMs")]
+    pub(crate) elapsed_ms: u64,
+}
+
+impl ProgressEvent {
+    pub(crate) fn new(phase: Phase, connection: Option<usize>, registered: Instant) -> Self {
+        Self {
+            phase,
+            connection,
+            timestamp_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or_default(),
+            elapsed_ms: registered.elapsed().as_millis() as u64,
+        }
+    }
+}
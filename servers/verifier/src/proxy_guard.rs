@@ -0,0 +1,500 @@
+//! Access control and abuse limits for the `/proxy` TCP bridge.
+//!
+//! Configured in the `proxy` section of `config.yaml`. Every target is
+//! checked before the WebSocket upgrade: its port, its hostname against the
+//! allow/deny rules, and every address it resolves to against the CIDR rules
+//! and (by default) private, loopback and link-local ranges. The bridge then
+//! connects to the checked addresses only, so DNS cannot be re-pointed between
+//! the check and the connection.
+//!
+//! The checks are applied by the [`Connector`](crate::connector::Connector),
+//! which also dials the server of a Proxy-mode session.
+
+use axum::{
+    extract::{ConnectInfo, FromRequestParts},
+    http::{request::Parts, HeaderMap, StatusCode},
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Port used when the target names none
+pub(crate) const DEFAULT_PORT: u16 = 443;
+
+/// Traffic a client may burst above its bandwidth limit, in seconds of quota
+const BANDWIDTH_BURST: Duration = Duration::from_secs(1);
+
+/// Domains, besides single-label names, that only resolve on local networks
+const LOCAL_DOMAINS: &[&str] = &["localhost", "local", "localdomain", "internal", "home.arpa"];
+
+/// A hostname pattern, IP address or CIDR range in `allow` / `deny`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub(crate) enum TargetRule {
+    /// Exact hostname, or `*.domain` for any subdomain
+    Host(String),
+    /// Address range; a plain IP is a full-length prefix
+    Net { addr: IpAddr, prefix: u8 },
+}
+
+impl TryFrom<String> for TargetRule {
+    type Error = String;
+
+    fn try_from(rule: String) -> Result<Self, Self::Error> {
+        let (addr, prefix) = match rule.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (rule.as_str(), None),
+        };
+        let Ok(addr) = addr.parse::<IpAddr>() else {
+            if prefix.is_some() || rule.is_empty() {
+                return Err(format!("invalid proxy rule `{}`", rule));
+            }
+            return Ok(Self::Host(rule.to_ascii_lowercase()));
+        };
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .parse::<u8>()
+                .ok()
+                .filter(|&prefix| prefix <= max)
+                .ok_or_else(|| format!("invalid prefix length in proxy rule `{}`", rule))?,
+            None => max,
+        };
+        Ok(Self::Net { addr, prefix })
+    }
+}
+
+impl TargetRule {
+    pub(crate) fn matches_host(&self, host: &str) -> bool {
+        match self {
+            Self::Host(pattern) => match pattern.strip_prefix("*.") {
+                Some(domain) => host
+                    .strip_suffix(domain)
+                    .is_some_and(|sub| sub.len() > 1 && sub.ends_with('.')),
+                None => pattern == host,
+            },
+            Self::Net { .. } => false,
+        }
+    }
+
+    pub(crate) fn matches_ip(&self, ip: IpAddr) -> bool {
+        let Self::Net { addr, prefix } = *self else {
+            return false;
+        };
+        match (addr, canonical_ip(ip)) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// `proxy` section of `config.yaml`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub(crate) struct ProxyConfig {
+    /// When non-empty, only targets matching one of these rules are allowed
+    pub(crate) allow: Vec<TargetRule>,
+    /// Targets matching any of these rules are refused
+    pub(crate) deny: Vec<TargetRule>,
+    /// Allowed target ports; any port when empty
+    pub(crate) ports: Vec<u16>,
+    /// Refuse private, loopback, link-local and other non-public addresses
+    /// unless an `allow` CIDR covers them
+    pub(crate) block_private: bool,
+    /// Open bridges per client IP
+    pub(crate) max_connections_per_ip: Option<usize>,
+    /// Bytes per second per client IP, both directions and all bridges combined
+    pub(crate) max_bytes_per_sec_per_ip: Option<u64>,
+    /// Close a bridge after this long without traffic in either direction
+    pub(crate) idle_timeout_secs: u64,
+    /// Time allowed for the TCP connection to the target
+    pub(crate) connect_timeout_secs: u64,
+    /// How long resolved addresses are reused; 0 disables the cache
+    pub(crate) dns_cache_secs: u64,
+    /// Header carrying the client IP when behind a reverse proxy, e.g.
+    /// `x-real-ip`; the socket peer address is used when unset
+    pub(crate) client_ip_header: Option<String>,
+    /// `host:port` to dial instead of resolving a server name, keyed like
+    /// policies; for split-horizon DNS or a local stand-in
+    pub(crate) upstreams: HashMap<String, String>,
+}
+
+impl Default for ProxyConfig {
+    fn default() -> Self {
+        Self {
+            allow: vec![],
+            deny: vec![],
+            ports: vec![],
+            block_private: true,
+            max_connections_per_ip: None,
+            max_bytes_per_sec_per_ip: None,
+            idle_timeout_secs: 300,
+            connect_timeout_secs: 10,
+            dns_cache_secs: 60,
+            client_ip_header: None,
+            upstreams: HashMap::new(),
+        }
+    }
+}
+
+/// Why a proxy request was refused; rendered as an HTTP error before upgrade.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ProxyRejection {
+    InvalidTarget(String),
+    Forbidden(String),
+    TooManyConnections(IpAddr),
+    Unresolvable(String),
+    ConnectFailed(String),
+    ConnectTimeout(String),
+}
+
+impl ProxyRejection {
+    /// Fixed error class recorded in metrics
+    pub(crate) fn reason(&self) -> &'static str {
+        match self {
+            Self::InvalidTarget(_) => "invalid_target",
+            Self::Forbidden(_) => "forbidden",
+            Self::TooManyConnections(_) => "too_many_connections",
+            Self::Unresolvable(_) => "unresolvable",
+            Self::ConnectFailed(_) => "connect_failed",
+            Self::ConnectTimeout(_) => "connect_timeout",
+        }
+    }
+
+    pub(crate) fn into_http_error(self) -> (StatusCode, String) {
+        match self {
+            Self::InvalidTarget(msg) => (StatusCode::BAD_REQUEST, msg),
+            Self::Forbidden(msg) => (StatusCode::FORBIDDEN, msg),
+            Self::TooManyConnections(ip) => (
+                StatusCode::TOO_MANY_REQUESTS,
+                format!("Too many proxy connections from {}", ip),
+            ),
+            Self::Unresolvable(msg) | Self::ConnectFailed(msg) => (StatusCode::BAD_GATEWAY, msg),
+            Self::ConnectTimeout(msg) => (StatusCode::GATEWAY_TIMEOUT, msg),
+        }
+    }
+}
+
+/// Splits `host`, `host:port`, `[v6]:port` or a bare IPv6 address.
+pub(crate) fn parse_target(target: &str) -> Result<(String, u16), ProxyRejection> {
+    let invalid = || ProxyRejection::InvalidTarget(format!("Invalid proxy target: {}", target));
+
+    let (host, port) = if let Some(rest) = target.strip_prefix('[') {
+        let (host, rest) = rest.split_once(']').ok_or_else(invalid)?;
+        match rest {
+            "" => (host, None),
+            _ => (host, Some(rest.strip_prefix(':').ok_or_else(invalid)?)),
+        }
+    } else if target.parse::<Ipv6Addr>().is_ok() {
+        (target, None)
+    } else {
+        match target.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (target, None),
+        }
+    };
+
+    let port = match port {
+        Some(port) => port.parse().ok().filter(|&p| p != 0).ok_or_else(invalid)?,
+        None => DEFAULT_PORT,
+    };
+    if host.is_empty() {
+        return Err(invalid());
+    }
+    Ok((host.trim_end_matches('.').to_ascii_lowercase(), port))
+}
+
+/// Maps IPv4-mapped IPv6 addresses to IPv4 so rules apply to both forms.
+fn canonical_ip(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6
+            .to_ipv4_mapped()
+            .map(IpAddr::V4)
+            .unwrap_or(IpAddr::V6(v6)),
+        v4 => v4,
+    }
+}
+
+/// Whether an address is outside the public unicast space.
+pub(crate) fn is_non_public(ip: IpAddr) -> bool {
+    match canonical_ip(ip) {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                || a == 0
+                // Carrier-grade NAT 100.64.0.0/10
+                || (a == 100 && (b & 0xc0) == 64)
+                // Reserved 240.0.0.0/4
+                || a >= 240
+        }
+        IpAddr::V6(ip) => {
+            let first = ip.segments()[0];
+            ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // Unique local fc00::/7
+                || (first & 0xfe00) == 0xfc00
+                // Link-local fe80::/10
+                || (first & 0xffc0) == 0xfe80
+                // Documentation 2001:db8::/32
+                || (first == 0x2001 && ip.segments()[1] == 0x0db8)
+        }
+    }
+}
+
+/// Whether a hostname can only name a host on the local network, such as
+/// `localhost`, `metadata.internal` or a single-label intranet name.
+pub(crate) fn is_local_name(host: &str) -> bool {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    !host.contains('.')
+        || LOCAL_DOMAINS.iter().any(|domain| {
+            host.strip_suffix(domain)
+                .is_some_and(|sub| sub.ends_with('.'))
+        })
+}
+
+impl ProxyConfig {
+    /// Checks the port and hostname of a target before it is resolved.
+    pub(crate) fn check_target(&self, host: &str, port: u16) -> Result<(), ProxyRejection> {
+        if !self.ports.is_empty() && !self.ports.contains(&port) {
+            return Err(ProxyRejection::Forbidden(format!(
+                "Proxy target port {} is not allowed",
+                port
+            )));
+        }
+        if self.deny.iter().any(|rule| rule.matches_host(host)) {
+            return Err(ProxyRejection::Forbidden(format!(
+                "Proxy target {} is denied",
+                host
+            )));
+        }
+        Ok(())
+    }
+
+    /// Checks a target name that is dialled without being resolved here, as
+    /// through an egress proxy with remote DNS. With no addresses to check,
+    /// the name must match an `allow` hostname rule when there are any, and
+    /// local names are refused under `block_private` unless allowed.
+    pub(crate) fn check_name(&self, host: &str) -> Result<(), ProxyRejection> {
+        let permitted = if self.allow.is_empty() {
+            !(self.block_private && is_local_name(host))
+        } else {
+            self.allow.iter().any(|rule| rule.matches_host(host))
+        };
+        if !permitted {
+            return Err(ProxyRejection::Forbidden(format!(
+                "Proxy target {} is not allowed",
+                host
+            )));
+        }
+        Ok(())
+    }
+
+    /// Filters the resolved addresses of `host` down to the permitted ones.
+    pub(crate) fn check_addrs(
+        &self,
+        host: &str,
+        addrs: &[SocketAddr],
+    ) -> Result<Vec<SocketAddr>, ProxyRejection> {
+        let host_allowed = self.allow.iter().any(|rule| rule.matches_host(host));
+        let permitted: Vec<SocketAddr> = addrs
+            .iter()
+            .copied()
+            .filter(|addr| {
+                let ip = addr.ip();
+                let ip_allowed = self.allow.iter().any(|rule| rule.matches_ip(ip));
+                !self.deny.iter().any(|rule| rule.matches_ip(ip))
+                    && (self.allow.is_empty() || host_allowed || ip_allowed)
+                    && !(self.block_private && is_non_public(ip) && !ip_allowed)
+            })
+            .collect();
+
+        if permitted.is_empty() {
+            return Err(ProxyRejection::Forbidden(format!(
+                "Proxy target {} is not allowed",
+                host
+            )));
+        }
+        Ok(permitted)
+    }
+
+    pub(crate) fn idle_timeout(&self) -> Duration {
+        Duration::from_secs(self.idle_timeout_secs)
+    }
+
+    pub(crate) fn connect_timeout(&self) -> Duration {
+        Duration::from_secs(self.connect_timeout_secs)
+    }
+
+    pub(crate) fn dns_cache_ttl(&self) -> Duration {
+        Duration::from_secs(self.dns_cache_secs)
+    }
+
+    /// Client IP from the configured header, falling back to the peer address.
+    pub(crate) fn client_ip(
+        &self,
+        headers: &HeaderMap,
+        peer: Option<SocketAddr>,
+    ) -> Option<IpAddr> {
+        let forwarded = self.client_ip_header.as_ref().and_then(|name| {
+            headers
+                .get(name.as_str())?
+                .to_str()
+                .ok()?
+                .split(',')
+                .next()?
+                .trim()
+                .parse()
+                .ok()
+        });
+        forwarded.or(peer.map(|peer| peer.ip()))
+    }
+
+    /// Rejects zero timeouts and limits.
+    pub(crate) fn validate(&self) -> Result<(), eyre::ErrReport> {
+        if self.idle_timeout_secs == 0 || self.connect_timeout_secs == 0 {
+            return Err(eyre::eyre!("proxy: timeouts must be greater than zero"));
+        }
+        if self.max_connections_per_ip == Some(0) || self.max_bytes_per_sec_per_ip == Some(0) {
+            return Err(eyre::eyre!(
+                "proxy: per-IP limits must be greater than zero"
+            ));
+        }
+        if self.ports.contains(&0) {
+            return Err(eyre::eyre!("proxy: port 0 cannot be allowed"));
+        }
+        for (server_name, upstream) in &self.upstreams {
+            if parse_target(upstream).is_err() {
+                return Err(eyre::eyre!(
+                    "proxy: invalid upstream `{}` for {}",
+                    upstream,
+                    server_name
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Peer address of the connection, when the server records it.
+pub(crate) struct PeerAddr(pub(crate) Option<SocketAddr>);
+
+impl<S: Send + Sync> FromRequestParts<S> for PeerAddr {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self(
+            parts
+                .extensions
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| *addr),
+        ))
+    }
+}
+
+#[derive(Debug, Default)]
+struct ClientUsage {
+    connections: usize,
+    /// Theoretical arrival time of the client's next byte under its bandwidth
+    /// limit (GCRA); traffic is delayed while this runs ahead of the clock
+    bandwidth_tat: Option<Instant>,
+}
+
+/// Per-client-IP connection counts and bandwidth state.
+#[derive(Debug, Default)]
+pub(crate) struct ProxyClients {
+    usage: Mutex<HashMap<IpAddr, ClientUsage>>,
+}
+
+impl ProxyClients {
+    /// Registers a bridge for `ip`, or refuses it when `ip` already has
+    /// `max` bridges open. The slot is released when the lease is dropped.
+    pub(crate) fn acquire(
+        self: &Arc<Self>,
+        ip: IpAddr,
+        max: Option<usize>,
+    ) -> Result<ProxyLease, ProxyRejection> {
+        let mut usage = self.usage.lock().unwrap_or_else(|e| e.into_inner());
+        let client = usage.entry(ip).or_default();
+        if max.is_some_and(|max| client.connections >= max) {
+            return Err(ProxyRejection::TooManyConnections(ip));
+        }
+        client.connections += 1;
+        Ok(ProxyLease {
+            clients: self.clone(),
+            ip,
+        })
+    }
+
+    /// Accounts `bytes` against `ip`'s bandwidth and returns how long to wait
+    /// before forwarding them.
+    pub(crate) fn reserve(
+        &self,
+        ip: IpAddr,
+        bytes_per_sec: u64,
+        bytes: usize,
+        now: Instant,
+    ) -> Duration {
+        let mut usage = self.usage.lock().unwrap_or_else(|e| e.into_inner());
+        let client = usage.entry(ip).or_default();
+        let cost = Duration::from_secs_f64(bytes as f64 / bytes_per_sec as f64);
+        let tat = client.bandwidth_tat.filter(|&tat| tat > now).unwrap_or(now) + cost;
+        client.bandwidth_tat = Some(tat);
+        (tat - now).saturating_sub(BANDWIDTH_BURST)
+    }
+
+    /// Number of open bridges for `ip`.
+    pub(crate) fn connections(&self, ip: IpAddr) -> usize {
+        let usage = self.usage.lock().unwrap_or_else(|e| e.into_inner());
+        usage.get(&ip).map_or(0, |client| client.connections)
+    }
+
+    fn release(&self, ip: IpAddr) {
+        let mut usage = self.usage.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(client) = usage.get_mut(&ip) {
+            client.connections = client.connections.saturating_sub(1);
+            if client.connections == 0 {
+                usage.remove(&ip);
+            }
+        }
+    }
+}
+
+/// An open bridge slot of one client IP.
+#[derive(Debug)]
+pub(crate) struct ProxyLease {
+    clients: Arc<ProxyClients>,
+    ip: IpAddr,
+}
+
+impl ProxyLease {
+    pub(crate) fn ip(&self) -> IpAddr {
+        self.ip
+    }
+
+    pub(crate) fn clients(&self) -> &ProxyClients {
+        &self.clients
+    }
+}
+
+impl Drop for ProxyLease {
+    fn drop(&mut self) {
+        self.clients.release(self.ip);
+    }
+}
@@ -0,0 +1,219 @@
+//! Resumable session WebSockets.
+//!
+//! `session_registered` carries a `resumeToken`. If the session WebSocket
+//! drops, the client can open a new one and send `resume` with the session id
+//! and token instead of `register`. The new socket takes over where the old
+//! one left off: it forwards the `reveal_config` unless that already reached
+//! the verifier task, then receives the result. A result that arrives while no
+//! socket is attached is kept for `limits.resume_buffer_secs`.
+//!
+//! The link also keeps the session's progress events, so a resumed socket
+//! can catch up on the ones sent while no socket was attached.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use subtle::ConstantTimeEq;
+use tokio::sync::{oneshot, watch};
+use uuid::Uuid;
+
+use crate::progress::{Phase, ProgressEvent};
+use crate::{RevealConfig, VerificationResult};
+
+/// Client side of a registered session, shared by the sockets attached to it.
+pub(crate) struct SessionLink {
+    token: String,
+    tenant: Option<String>,
+    reveal_config_tx: Mutex<Option<oneshot::Sender<RevealConfig>>>,
+    result: watch::Sender<Option<VerificationResult>>,
+    registered: Instant,
+    /// Every progress event reported so far
+    progress: watch::Sender<Vec<ProgressEvent>>,
+    /// Incremented on every attach, so earlier sockets know to stop
+    generation: watch::Sender<u64>,
+}
+
+impl SessionLink {
+    pub(crate) fn new(
+        tenant: Option<String>,
+        reveal_config_tx: oneshot::Sender<RevealConfig>,
+    ) -> Self {
+        Self {
+            token: Uuid::new_v4().simple().to_string(),
+            tenant,
+            reveal_config_tx: Mutex::new(Some(reveal_config_tx)),
+            result: watch::Sender::new(None),
+            registered: Instant::now(),
+            progress: watch::Sender::new(Vec::new()),
+            generation: watch::Sender::new(0),
+        }
+    }
+
+    /// Token a client presents to resume the session
+    pub(crate) fn token(&self) -> &str {
+        &self.token
+    }
+
+    /// Whether `token` resumes this session for a client of `tenant`.
+    fn authorize(&self, token: &str, tenant: Option<&str>) -> bool {
+        let token_matches = bool::from(token.as_bytes().ct_eq(self.token.as_bytes()));
+        token_matches && self.tenant.as_deref() == tenant
+    }
+
+    /// Makes the caller's socket the attached one, superseding earlier ones.
+    pub(crate) fn attach(&self) -> Attachment {
+        self.generation.send_modify(|generation| *generation += 1);
+        let generation = self.generation.subscribe();
+        let current = *generation.borrow();
+        Attachment {
+            current,
+            generation,
+            progress: self.progress.subscribe(),
+            sent: 0,
+        }
+    }
+
+    /// Records that the session reached `phase`.
+    pub(crate) fn report(&self, phase: Phase) {
+        let event = ProgressEvent::new(phase, None, self.registered);
+        self.progress.send_modify(|events| events.push(event));
+    }
+
+    /// Records that TLS connection `connection` of the session reached `phase`.
+    pub(crate) fn report_connection(&self, connection: usize, phase: Phase) {
+        let event = ProgressEvent::new(phase, Some(connection), self.registered);
+        self.progress.send_modify(|events| events.push(event));
+    }
+
+    /// The last progress event reported, if any.
+    pub(crate) fn latest_progress(&self) -> Option<ProgressEvent> {
+        self.progress.borrow().last().cloned()
+    }
+
+    /// Whether the verifier task still waits for the reveal config.
+    pub(crate) fn awaiting_reveal_config(&self) -> bool {
+        self.lock_reveal_config_tx().is_some()
+    }
+
+    /// Takes the reveal config sender; `None` once a socket has used it.
+    pub(crate) fn take_reveal_config_tx(&self) -> Option<oneshot::Sender<RevealConfig>> {
+        self.lock_reveal_config_tx().take()
+    }
+
+    fn lock_reveal_config_tx(
+        &self,
+    ) -> std::sync::MutexGuard<'_, Option<oneshot::Sender<RevealConfig>>> {
+        self.reveal_config_tx
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Records the session result for the attached and any later socket.
+    pub(crate) fn complete(&self, result: VerificationResult) {
+        // A finished session takes no reveal config anymore
+        drop(self.take_reveal_config_tx());
+        self.result.send_replace(Some(result));
+    }
+
+    /// Waits for the session result.
+    pub(crate) async fn result(&self) -> VerificationResult {
+        let mut result = self.result.subscribe();
+        // The sender lives in `self`, so the channel cannot close here
+        let result = result
+            .wait_for(Option::is_some)
+            .await
+            .expect("result sender outlives its receivers");
+        result.clone().expect("waited for a result")
+    }
+}
+
+/// A socket's claim on a [`SessionLink`], see [`SessionLink::attach`].
+pub(crate) struct Attachment {
+    current: u64,
+    generation: watch::Receiver<u64>,
+    progress: watch::Receiver<Vec<ProgressEvent>>,
+    /// Progress events already handed to the socket
+    sent: usize,
+}
+
+impl Attachment {
+    /// Resolves once another socket has attached to the session.
+    pub(crate) async fn superseded(&mut self) {
+        let current = self.current;
+        let _ = self
+            .generation
+            .wait_for(|&generation| generation != current)
+            .await;
+    }
+
+    /// Waits for progress events this socket has not sent yet, starting
+    /// from the first one reported for the session.
+    pub(crate) async fn progress(&mut self) -> Vec<ProgressEvent> {
+        let sent = self.sent;
+        // The sender lives in the link, which outlives its sockets
+        let _ = self.progress.wait_for(|events| events.len() > sent).await;
+        self.unsent_progress()
+    }
+
+    /// Progress events this socket has not sent yet, without waiting.
+    pub(crate) fn unsent_progress(&mut self) -> Vec<ProgressEvent> {
+        let events = self.progress.borrow_and_update()[self.sent..].to_vec();
+        self.sent += events.len();
+        events
+    }
+}
+
+/// Sessions that can be resumed, by session id.
+#[derive(Default)]
+pub(crate) struct ResumableSessions {
+    links: Mutex<HashMap<String, Arc<SessionLink>>>,
+}
+
+impl ResumableSessions {
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Arc<SessionLink>>> {
+        self.links.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn insert(&self, session_id: &str, link: Arc<SessionLink>) {
+        self.lock().insert(session_id.to_string(), link);
+    }
+
+    /// The session's link, if `token` is its resume token and the client
+    /// authenticated as the tenant that registered it.
+    pub(crate) fn resume(
+        &self,
+        session_id: &str,
+        token: &str,
+        tenant: Option<&str>,
+    ) -> Option<Arc<SessionLink>> {
+        self.lock()
+            .get(session_id)
+            .filter(|link| link.authorize(token, tenant))
+            .cloned()
+    }
+
+    /// Records that the session reached `phase`, if it is still linked.
+    pub(crate) fn report(&self, session_id: &str, phase: Phase) {
+        if let Some(link) = self.lock().get(session_id) {
+            link.report(phase);
+        }
+    }
+
+    /// Records that TLS connection `connection` of the session reached
+    /// `phase`, if the session is still linked.
+    pub(crate) fn report_connection(&self, session_id: &str, connection: usize, phase: Phase) {
+        if let Some(link) = self.lock().get(session_id) {
+            link.report_connection(connection, phase);
+        }
+    }
+
+    /// The last progress event of the session, if it is still linked.
+    pub(crate) fn latest_progress(&self, session_id: &str) -> Option<ProgressEvent> {
+        self.lock().get(session_id)?.latest_progress()
+    }
+
+    /// Stops the session from being resumed.
+    pub(crate) fn remove(&self, session_id: &str) {
+        self.lock().remove(session_id);
+    }
+}
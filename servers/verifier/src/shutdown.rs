@@ -0,0 +1,94 @@
+//! Graceful shutdown and session draining.
+//!
+//! On SIGTERM or SIGINT the server starts draining: `/health` answers
+//! `503 draining` so load balancers move traffic away, and new `/session`
+//! connections and registrations are refused with a retryable error. Sessions
+//! already running get up to `limits.drain_timeout_secs` to finish, pending
+//! webhook deliveries are flushed within what is left of that deadline, and
+//! then the server exits. A second signal skips the remaining wait.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::watch;
+
+/// Error sent to clients that try to start a session while draining
+pub(crate) const DRAINING_MESSAGE: &str = "Server is shutting down, retry the session";
+
+/// Draining flag and the number of sessions still in flight.
+#[derive(Debug)]
+pub(crate) struct Shutdown {
+    draining: AtomicBool,
+    active: watch::Sender<usize>,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self {
+            draining: AtomicBool::new(false),
+            active: watch::Sender::new(0),
+        }
+    }
+}
+
+impl Shutdown {
+    pub(crate) fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    /// Stops admitting sessions; returns `false` if draining had already begun.
+    pub(crate) fn start_draining(&self) -> bool {
+        !self.draining.swap(true, Ordering::SeqCst)
+    }
+
+    /// Sessions currently in flight.
+    pub(crate) fn active(&self) -> usize {
+        *self.active.borrow()
+    }
+
+    /// Counts a session as in flight until the returned guard is dropped.
+    pub(crate) fn track(self: &Arc<Self>) -> SessionGuard {
+        self.active.send_modify(|active| *active += 1);
+        SessionGuard(self.clone())
+    }
+
+    /// Resolves once no session is in flight.
+    pub(crate) async fn drained(&self) {
+        let mut active = self.active.subscribe();
+        // The sender lives in `self`, so the channel cannot close here
+        let _ = active.wait_for(|&active| active == 0).await;
+    }
+}
+
+/// Keeps a session counted as in flight, see [`Shutdown::track`].
+#[derive(Debug)]
+pub(crate) struct SessionGuard(Arc<Shutdown>);
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        self.0.active.send_modify(|active| *active -= 1);
+    }
+}
+
+/// Resolves on SIGINT (Ctrl+C) or, on Unix, SIGTERM, returning its name.
+pub(crate) async fn signal() -> &'static str {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to listen for Ctrl+C");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => "SIGINT",
+        _ = terminate => "SIGTERM",
+    }
+}
@@ -0,0 +1,397 @@
+//! Persistent store for completed verification sessions.
+//!
+//! Once a verifier task finishes, its outcome is written here so it can be
+//! fetched later via `GET /sessions/{id}` even if the extension disconnected
+//! before `session_completed` arrived. Two embedded backends are supported,
+//! selected by the `store` section of `config.yaml`:
+//!
+//! - `sqlite`: a single table keyed by session id, indexed by server name and
+//!   completion time, with the session's tenant in its own column.
+//! - `sled`: one JSON record per session id.
+//!
+//! Queued webhook deliveries (see `webhook.rs`) are kept alongside sessions
+//! in their own table / tree so they survive restarts.
+//!
+//! The store API is synchronous; async callers go through
+//! `tokio::task::spawn_blocking`.
+
+use eyre::eyre;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::assertion::AssertionResult;
+use crate::attestation::Attestation;
+use crate::commitment::CommitmentInfo;
+use crate::error::{ErrorCode, SessionError};
+use crate::webhook::WebhookDelivery;
+use crate::{ConnectionTranscript, HandlerResult, RedactedTranscript};
+
+/// Default page size for `GET /sessions`
+pub(crate) const DEFAULT_PAGE_SIZE: usize = 50;
+/// Upper bound on the page size for `GET /sessions`
+pub(crate) const MAX_PAGE_SIZE: usize = 500;
+
+/// Embedded database used to persist results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum StoreBackend {
+    Sqlite,
+    Sled,
+}
+
+/// `store` section of `config.yaml`.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct StoreConfig {
+    pub(crate) backend: StoreBackend,
+    /// Database file (sqlite) or directory (sled)
+    pub(crate) path: PathBuf,
+}
+
+/// A completed (or failed) verification session as persisted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct StoredSession {
+    pub(crate) id: String,
+    /// Tenant of the API key the session registered with; absent when no
+    /// `auth` section is configured
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) tenant: Option<String>,
+    /// Verified server name, or for a session that failed before the TLS run
+    /// the one declared at registration, if any
+    pub(crate) server_name: Option<String>,
+    pub(crate) results: Vec<HandlerResult>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) assertions: Vec<AssertionResult>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) commitments: Vec<CommitmentInfo>,
+    pub(crate) transcript: Option<RedactedTranscript>,
+    /// Every connection's redacted transcript, for sessions with more than
+    /// one; `transcript` is the first connection's
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) connections: Vec<ConnectionTranscript>,
+    pub(crate) session_data: HashMap<String, String>,
+    /// Unix timestamp (seconds) of the `register` message
+    pub(crate) registered_at: u64,
+    /// Unix timestamp (seconds) at which the verifier task finished
+    pub(crate) completed_at: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) attestation: Option<Attestation>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) error: Option<String>,
+    /// Code of `error`; absent on records written before codes existed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) error_code: Option<ErrorCode>,
+}
+
+impl StoredSession {
+    /// Record for a session that ended without handler results.
+    pub(crate) fn failed(
+        id: &str,
+        tenant: Option<String>,
+        registered_at: u64,
+        session_data: &HashMap<String, String>,
+        server_name: Option<String>,
+        error: SessionError,
+    ) -> Self {
+        Self {
+            id: id.to_string(),
+            tenant,
+            server_name,
+            results: vec![],
+            assertions: vec![],
+            commitments: vec![],
+            transcript: None,
+            connections: vec![],
+            session_data: session_data.clone(),
+            registered_at,
+            completed_at: crate::unix_timestamp(),
+            attestation: None,
+            error: Some(error.message),
+            error_code: Some(error.code),
+        }
+    }
+}
+
+/// Filters and pagination for listing stored sessions.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SessionQuery {
+    /// Only sessions of this tenant
+    pub(crate) tenant: Option<String>,
+    pub(crate) server_name: Option<String>,
+    /// Only sessions completed at or after this unix timestamp
+    pub(crate) since: Option<u64>,
+    pub(crate) limit: usize,
+    pub(crate) offset: usize,
+}
+
+pub(crate) enum ResultStore {
+    Sqlite(Mutex<rusqlite::Connection>),
+    Sled(sled::Db),
+}
+
+impl ResultStore {
+    /// Opens (and if needed creates) the configured store.
+    pub(crate) fn open(config: &StoreConfig) -> Result<Self, eyre::ErrReport> {
+        match config.backend {
+            StoreBackend::Sqlite => {
+                let conn = rusqlite::Connection::open(&config.path)
+                    .map_err(|e| eyre!("Failed to open sqlite store {:?}: {}", config.path, e))?;
+                conn.execute_batch(
+                    "CREATE TABLE IF NOT EXISTS sessions (
+                         id TEXT PRIMARY KEY,
+                         tenant TEXT,
+                         server_name TEXT,
+                         completed_at INTEGER NOT NULL,
+                         record TEXT NOT NULL
+                     );
+                     CREATE INDEX IF NOT EXISTS sessions_server_name_completed_at
+                         ON sessions (server_name, completed_at);
+                     CREATE INDEX IF NOT EXISTS sessions_completed_at
+                         ON sessions (completed_at);
+                     CREATE TABLE IF NOT EXISTS webhook_deliveries (
+                         id TEXT PRIMARY KEY,
+                         record TEXT NOT NULL
+                     );",
+                )
+                .map_err(|e| eyre!("Failed to initialize sqlite store: {}", e))?;
+
+                // Stores created before sessions were scoped by tenant lack
+                // the column; their sessions keep a NULL tenant
+                let has_tenant: i64 = conn
+                    .query_row(
+                        "SELECT COUNT(*) FROM pragma_table_info('sessions') WHERE name = 'tenant'",
+                        [],
+                        |row| row.get(0),
+                    )
+                    .map_err(|e| eyre!("Failed to initialize sqlite store: {}", e))?;
+                if has_tenant == 0 {
+                    conn.execute("ALTER TABLE sessions ADD COLUMN tenant TEXT", [])
+                        .map_err(|e| eyre!("Failed to initialize sqlite store: {}", e))?;
+                }
+                Ok(Self::Sqlite(Mutex::new(conn)))
+            }
+            StoreBackend::Sled => {
+                let db = sled::open(&config.path)
+                    .map_err(|e| eyre!("Failed to open sled store {:?}: {}", config.path, e))?;
+                Ok(Self::Sled(db))
+            }
+        }
+    }
+
+    /// Inserts or replaces the record for `session.id`.
+    pub(crate) fn put(&self, session: &StoredSession) -> Result<(), eyre::ErrReport> {
+        let record = serde_json::to_string(session)?;
+        match self {
+            Self::Sqlite(conn) => {
+                conn.lock()
+                    .map_err(|_| eyre!("sqlite store lock poisoned"))?
+                    .execute(
+                        "INSERT OR REPLACE INTO sessions
+                             (id, tenant, server_name, completed_at, record)
+                         VALUES (?1, ?2, ?3, ?4, ?5)",
+                        rusqlite::params![
+                            session.id,
+                            session.tenant,
+                            session.server_name,
+                            session.completed_at as i64,
+                            record
+                        ],
+                    )
+                    .map_err(|e| eyre!("Failed to write session {}: {}", session.id, e))?;
+            }
+            Self::Sled(db) => {
+                db.insert(session.id.as_bytes(), record.as_bytes())
+                    .map_err(|e| eyre!("Failed to write session {}: {}", session.id, e))?;
+                db.flush()
+                    .map_err(|e| eyre!("Failed to flush session {}: {}", session.id, e))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn get(&self, id: &str) -> Result<Option<StoredSession>, eyre::ErrReport> {
+        let record: Option<Vec<u8>> = match self {
+            Self::Sqlite(conn) => {
+                use rusqlite::OptionalExtension;
+                conn.lock()
+                    .map_err(|_| eyre!("sqlite store lock poisoned"))?
+                    .query_row("SELECT record FROM sessions WHERE id = ?1", [id], |row| {
+                        row.get::<_, String>(0)
+                    })
+                    .optional()
+                    .map_err(|e| eyre!("Failed to read session {}: {}", id, e))?
+                    .map(String::into_bytes)
+            }
+            Self::Sled(db) => db
+                .get(id.as_bytes())
+                .map_err(|e| eyre!("Failed to read session {}: {}", id, e))?
+                .map(|v| v.to_vec()),
+        };
+
+        record
+            .map(|r| serde_json::from_slice(&r).map_err(|e| eyre!("Corrupt session {}: {}", id, e)))
+            .transpose()
+    }
+
+    /// Lists sessions matching the query, most recently completed first.
+    pub(crate) fn list(&self, query: &SessionQuery) -> Result<Vec<StoredSession>, eyre::ErrReport> {
+        match self {
+            Self::Sqlite(conn) => {
+                let conn = conn
+                    .lock()
+                    .map_err(|_| eyre!("sqlite store lock poisoned"))?;
+                let mut stmt = conn
+                    .prepare(
+                        "SELECT record FROM sessions
+                         WHERE (?1 IS NULL OR server_name = ?1)
+                           AND (?2 IS NULL OR completed_at >= ?2)
+                           AND (?5 IS NULL OR tenant = ?5)
+                         ORDER BY completed_at DESC, id
+                         LIMIT ?3 OFFSET ?4",
+                    )
+                    .map_err(|e| eyre!("Failed to query sessions: {}", e))?;
+                let rows = stmt
+                    .query_map(
+                        rusqlite::params![
+                            query.server_name,
+                            query.since.map(|s| s as i64),
+                            query.limit as i64,
+                            query.offset as i64,
+                            query.tenant
+                        ],
+                        |row| row.get::<_, String>(0),
+                    )
+                    .map_err(|e| eyre!("Failed to query sessions: {}", e))?;
+
+                rows.map(|row| {
+                    let record = row.map_err(|e| eyre!("Failed to read session row: {}", e))?;
+                    serde_json::from_str(&record).map_err(|e| eyre!("Corrupt session row: {}", e))
+                })
+                .collect()
+            }
+            Self::Sled(db) => {
+                let mut sessions = Vec::new();
+                for entry in db.iter() {
+                    let (_, value) = entry.map_err(|e| eyre!("Failed to scan sessions: {}", e))?;
+                    let session: StoredSession = serde_json::from_slice(&value)
+                        .map_err(|e| eyre!("Corrupt session record: {}", e))?;
+                    if query
+                        .tenant
+                        .as_ref()
+                        .is_some_and(|tenant| session.tenant.as_ref() != Some(tenant))
+                    {
+                        continue;
+                    }
+                    if query
+                        .server_name
+                        .as_ref()
+                        .is_some_and(|name| session.server_name.as_ref() != Some(name))
+                    {
+                        continue;
+                    }
+                    if query
+                        .since
+                        .is_some_and(|since| session.completed_at < since)
+                    {
+                        continue;
+                    }
+                    sessions.push(session);
+                }
+                sessions.sort_by(|a, b| {
+                    b.completed_at
+                        .cmp(&a.completed_at)
+                        .then_with(|| a.id.cmp(&b.id))
+                });
+                Ok(sessions
+                    .into_iter()
+                    .skip(query.offset)
+                    .take(query.limit)
+                    .collect())
+            }
+        }
+    }
+
+    /// Inserts or replaces a queued webhook delivery.
+    pub(crate) fn put_delivery(&self, delivery: &WebhookDelivery) -> Result<(), eyre::ErrReport> {
+        let record = serde_json::to_string(delivery)?;
+        match self {
+            Self::Sqlite(conn) => {
+                conn.lock()
+                    .map_err(|_| eyre!("sqlite store lock poisoned"))?
+                    .execute(
+                        "INSERT OR REPLACE INTO webhook_deliveries (id, record) VALUES (?1, ?2)",
+                        rusqlite::params![delivery.id, record],
+                    )
+                    .map_err(|e| eyre!("Failed to write delivery {}: {}", delivery.id, e))?;
+            }
+            Self::Sled(db) => {
+                let tree = deliveries_tree(db)?;
+                tree.insert(delivery.id.as_bytes(), record.as_bytes())
+                    .map_err(|e| eyre!("Failed to write delivery {}: {}", delivery.id, e))?;
+                tree.flush()
+                    .map_err(|e| eyre!("Failed to flush delivery {}: {}", delivery.id, e))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn delete_delivery(&self, id: &str) -> Result<(), eyre::ErrReport> {
+        match self {
+            Self::Sqlite(conn) => {
+                conn.lock()
+                    .map_err(|_| eyre!("sqlite store lock poisoned"))?
+                    .execute("DELETE FROM webhook_deliveries WHERE id = ?1", [id])
+                    .map_err(|e| eyre!("Failed to delete delivery {}: {}", id, e))?;
+            }
+            Self::Sled(db) => {
+                let tree = deliveries_tree(db)?;
+                tree.remove(id.as_bytes())
+                    .map_err(|e| eyre!("Failed to delete delivery {}: {}", id, e))?;
+                tree.flush()
+                    .map_err(|e| eyre!("Failed to flush delivery {}: {}", id, e))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Lists all queued webhook deliveries, pending and dead-lettered.
+    pub(crate) fn list_deliveries(&self) -> Result<Vec<WebhookDelivery>, eyre::ErrReport> {
+        let records: Vec<Vec<u8>> = match self {
+            Self::Sqlite(conn) => {
+                let conn = conn
+                    .lock()
+                    .map_err(|_| eyre!("sqlite store lock poisoned"))?;
+                let mut stmt = conn
+                    .prepare("SELECT record FROM webhook_deliveries")
+                    .map_err(|e| eyre!("Failed to query deliveries: {}", e))?;
+                let rows = stmt
+                    .query_map([], |row| row.get::<_, String>(0))
+                    .map_err(|e| eyre!("Failed to query deliveries: {}", e))?;
+                rows.map(|row| {
+                    row.map(String::into_bytes)
+                        .map_err(|e| eyre!("Failed to read delivery row: {}", e))
+                })
+                .collect::<Result<_, _>>()?
+            }
+            Self::Sled(db) => deliveries_tree(db)?
+                .iter()
+                .map(|entry| {
+                    entry
+                        .map(|(_, value)| value.to_vec())
+                        .map_err(|e| eyre!("Failed to scan deliveries: {}", e))
+                })
+                .collect::<Result<_, _>>()?,
+        };
+
+        records
+            .iter()
+            .map(|r| serde_json::from_slice(r).map_err(|e| eyre!("Corrupt delivery record: {}", e)))
+            .collect()
+    }
+}
+
+fn deliveries_tree(db: &sled::Db) -> Result<sled::Tree, eyre::ErrReport> {
+    db.open_tree("webhook_deliveries")
+        .map_err(|e| eyre!("Failed to open delivery tree: {}", e))
+}
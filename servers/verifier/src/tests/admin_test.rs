@@ -0,0 +1,140 @@
+//! Tests for the admin session list and cancellation.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use serde_json::json;
+use tokio::sync::oneshot;
+use tokio::time::timeout;
+
+use super::{app_state, session_config};
+use crate::admin::{cancel_session_handler, list_sessions_handler, SessionOverview};
+use crate::error::ErrorCode;
+use crate::progress::Phase;
+use crate::resume::SessionLink;
+use crate::{run_verifier_task_with_lifetime, AppState, Config, SessionData};
+
+#[test]
+fn empty_admin_token_fails_startup() {
+    let config: Config = serde_yaml_ng::from_str("admin: { token: \"\" }").unwrap();
+    assert!(AppState::new(config).is_err());
+}
+
+#[tokio::test]
+async fn lists_sessions_with_their_phase() {
+    let state = app_state("{}");
+    let overview = |registered_at, server_name: Option<&str>| SessionOverview {
+        registered_at,
+        remote_ip: Some("203.0.113.7".parse().unwrap()),
+        server_name: server_name.map(str::to_string),
+        max_sent_data: 4096,
+        max_recv_data: 16384,
+        connections: 1,
+        session_data_keys: vec!["user".to_string()],
+    };
+    {
+        let mut sessions = state.sessions.lock().await;
+        sessions.insert(
+            "late".to_string(),
+            SessionData {
+                overview: overview(20, None),
+                ..Default::default()
+            },
+        );
+        sessions.insert(
+            "early".to_string(),
+            SessionData {
+                tenant: Some("acme".to_string()),
+                overview: overview(10, Some("api.example.com")),
+                ..Default::default()
+            },
+        );
+    }
+    let (reveal_config_tx, _reveal_config_rx) = oneshot::channel();
+    let link = Arc::new(SessionLink::new(None, reveal_config_tx));
+    state.resumable.insert("early", link);
+    state
+        .resumable
+        .report_connection("early", 0, Phase::ProverConnected);
+
+    let axum::Json(sessions) = list_sessions_handler(State(state.clone())).await;
+    assert_eq!(
+        serde_json::to_value(&sessions).unwrap(),
+        json!([
+            {
+                "id": "early",
+                "tenant": "acme",
+                "phase": "prover_connected",
+                "phase_connection": 0,
+                "registered_at": 10,
+                "remote_ip": "203.0.113.7",
+                "server_name": "api.example.com",
+                "max_sent_data": 4096,
+                "max_recv_data": 16384,
+                "connections": 1,
+                "session_data_keys": ["user"],
+            },
+            {
+                "id": "late",
+                "tenant": null,
+                "phase": "registered",
+                "registered_at": 20,
+                "remote_ip": "203.0.113.7",
+                "server_name": null,
+                "max_sent_data": 4096,
+                "max_recv_data": 16384,
+                "connections": 1,
+                "session_data_keys": ["user"],
+            },
+        ])
+    );
+}
+
+#[tokio::test]
+async fn cancelling_fails_the_session_and_cleans_it_up() {
+    let state = app_state("{}");
+    let (prover_socket_tx, prover_socket_rx) = oneshot::channel();
+    let (_reveal_config_tx, reveal_config_rx) = oneshot::channel();
+    let (result_tx, result_rx) = oneshot::channel();
+    let (cancel_tx, cancel_rx) = oneshot::channel();
+    state.sessions.lock().await.insert(
+        "s1".to_string(),
+        SessionData {
+            prover_socket_txs: vec![Some(prover_socket_tx)],
+            cancel_tx: Some(cancel_tx),
+            ..Default::default()
+        },
+    );
+
+    // The task waits for a prover that never connects
+    let config = session_config(1);
+    let task = tokio::spawn(run_verifier_task_with_lifetime(
+        "s1".to_string(),
+        config,
+        HashMap::new(),
+        reveal_config_rx,
+        vec![prover_socket_rx],
+        result_tx,
+        cancel_rx,
+        state.clone(),
+    ));
+
+    let status = cancel_session_handler(State(state.clone()), Path("s1".to_string())).await;
+    assert_eq!(status, Ok(StatusCode::ACCEPTED));
+
+    let result = timeout(Duration::from_secs(5), result_rx)
+        .await
+        .expect("cancelled session finishes")
+        .unwrap();
+    assert_eq!(result.error.unwrap().code, ErrorCode::Cancelled);
+    task.await.unwrap();
+    assert!(state.sessions.lock().await.is_empty());
+
+    let (status, _) = cancel_session_handler(State(state), Path("s1".to_string()))
+        .await
+        .unwrap_err();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
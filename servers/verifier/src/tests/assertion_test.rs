@@ -0,0 +1,171 @@
+//! Tests for policy assertions over revealed values.
+
+use std::collections::HashMap;
+
+use serde_json::json;
+
+use crate::assertion::{evaluate, json_path, AssertionConfig};
+use crate::{AppState, Config, Handler, HandlerAction, HandlerPart, HandlerResult, HandlerType};
+
+const BODY: &str = r#"{"user":{"id":"u-42"},"accounts":[{"balance":"1500.25"},{"balance":10}]}"#;
+
+fn result(part: HandlerPart, value: &str) -> HandlerResult {
+    HandlerResult {
+        handler: Handler {
+            handler_type: HandlerType::Recv,
+            part,
+            action: HandlerAction::Reveal,
+            params: None,
+        },
+        value: value.to_string(),
+        parsed: None,
+        key: None,
+        connection: 0,
+        server_name: "api.example.com".to_string(),
+        label_verified: true,
+    }
+}
+
+fn assertions(yaml: &str) -> Vec<AssertionConfig> {
+    serde_yaml_ng::from_str(yaml).expect("valid assertions")
+}
+
+#[test]
+fn json_path_subset() {
+    let document: serde_json::Value = serde_json::from_str(BODY).unwrap();
+    assert_eq!(
+        json_path(&document, "$.user.id").unwrap(),
+        Some(&json!("u-42"))
+    );
+    assert_eq!(
+        json_path(&document, "$['accounts'][1].balance").unwrap(),
+        Some(&json!(10))
+    );
+    assert_eq!(json_path(&document, "$").unwrap(), Some(&document));
+    assert_eq!(json_path(&document, "$.accounts[5]").unwrap(), None);
+    assert!(json_path(&document, "user.id").is_err());
+    assert!(json_path(&document, "$.accounts[x]").is_err());
+}
+
+#[test]
+fn evaluates_comparisons() {
+    let results = vec![
+        result(HandlerPart::StatusCode, "200"),
+        result(HandlerPart::Body, BODY),
+    ];
+    let session_data = HashMap::from([("userId".to_string(), "u-42".to_string())]);
+
+    let outcomes = evaluate(
+        &assertions(
+            r#"
+- { name: ok, type: RECV, part: STATUS_CODE, op: matches, value: "^2\\d\\d$" }
+- { name: rich, type: RECV, part: BODY, path: "$.accounts[0].balance", op: gte, value: 1000 }
+- { name: poor, type: RECV, part: BODY, path: "$.accounts[1].balance", op: gt, value: 1000 }
+- { name: same_user, type: RECV, part: BODY, path: $.user.id, op: eq_session, value: userId }
+- { name: has_user, type: RECV, part: BODY, path: $.user, op: exists }
+- { name: no_email, type: RECV, part: BODY, path: $.user.email, op: eq, value: x }
+- { name: no_headers, type: RECV, part: HEADERS, op: exists }
+"#,
+        ),
+        &results,
+        &[true, true],
+        &session_data,
+    );
+
+    let passed: Vec<_> = outcomes
+        .iter()
+        .map(|o| (o.name.as_str(), o.passed))
+        .collect();
+    assert_eq!(
+        passed,
+        [
+            ("ok", true),
+            ("rich", true),
+            ("poor", false),
+            ("same_user", true),
+            ("has_user", true),
+            ("no_email", false),
+            ("no_headers", false),
+        ]
+    );
+    assert_eq!(outcomes[1].actual, Some(json!("1500.25")));
+    assert_eq!(outcomes[5].error.as_deref(), Some("Path not found"));
+    assert!(outcomes[6].error.is_some());
+}
+
+#[test]
+fn only_whole_parts_count_and_all_must_pass() {
+    let balance_above = assertions(
+        r#"
+- { name: rich, type: RECV, part: BODY, path: "$.accounts[0].balance", op: gte, value: 1000 }
+"#,
+    );
+    let no_session_data = HashMap::new();
+
+    // A nested object revealed on its own is not read as the body root
+    let nested = r#"{"accounts":[{"balance":5000}]}"#;
+    let outcomes = evaluate(
+        &balance_above,
+        &[result(HandlerPart::Body, nested)],
+        &[false],
+        &no_session_data,
+    );
+    assert!(!outcomes[0].passed);
+    assert_eq!(
+        outcomes[0].error.as_deref(),
+        Some("No revealed value spans this whole part")
+    );
+
+    // One failing body is enough to fail, whatever the order
+    let poor = r#"{"accounts":[{"balance":10}]}"#;
+    for results in [
+        [
+            result(HandlerPart::Body, nested),
+            result(HandlerPart::Body, poor),
+        ],
+        [
+            result(HandlerPart::Body, poor),
+            result(HandlerPart::Body, nested),
+        ],
+    ] {
+        let outcomes = evaluate(&balance_above, &results, &[true, true], &no_session_data);
+        assert!(!outcomes[0].passed);
+        assert_eq!(outcomes[0].actual, Some(json!(10)));
+    }
+}
+
+#[test]
+fn broken_assertions_fail_startup() {
+    let valid = assertions(
+        r#"
+- { name: a, type: RECV, part: BODY, path: "$['accounts'][0].balance", op: gte, value: "1000" }
+- { name: b, type: RECV, part: BODY, op: matches, value: "^[0-9]+$" }
+- { name: c, type: RECV, part: BODY, op: eq_session, value: user_id }
+"#,
+    );
+    for assertion in &valid {
+        assertion.validate().expect(&assertion.name);
+    }
+
+    for (fields, expected) in [
+        ("path: 'accounts[0]', op: exists", "Unsupported JSONPath"),
+        ("path: '$.accounts[', op: exists", "Unsupported JSONPath"),
+        ("op: matches, value: '([0-9]'", "Invalid regex"),
+        ("op: matches, value: 5", "Operand is not a regex string"),
+        ("op: gt, value: many", "Operand is not a number"),
+        ("op: eq_session, value: 5", "not a session data key"),
+    ] {
+        let config: Config = serde_yaml_ng::from_str(&format!(
+            "policies:\n  \"api.example.com\":\n    assertions:\n      - {{ name: broken, type: RECV, part: BODY, {} }}\n",
+            fields
+        ))
+        .unwrap();
+        let err = AppState::new(config).err().expect(fields).to_string();
+        assert!(
+            err.contains("policies.api.example.com: assertion `broken`"),
+            "{}",
+            err
+        );
+        assert!(err.contains(expected), "{}", err);
+    }
+}
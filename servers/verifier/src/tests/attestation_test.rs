@@ -0,0 +1,92 @@
+//! Tests for attestation signing and offline verification.
+
+use base64::Engine;
+use tlsn_verifier_server::attestation::verify_attestation;
+
+use crate::attestation::{AttestationBody, AttestationConfig, Attestor, SigningAlgorithm};
+use crate::{Handler, HandlerAction, HandlerPart, HandlerResult, HandlerType};
+
+const TEST_KEY: &str = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+
+fn attestor(algorithm: SigningAlgorithm) -> Attestor {
+    Attestor::from_config(&AttestationConfig {
+        algorithm,
+        key: Some(TEST_KEY.to_string()),
+        key_file: None,
+    })
+    .expect("valid test key")
+}
+
+fn body() -> AttestationBody {
+    AttestationBody {
+        version: 0,
+        session_id: "session-1".to_string(),
+        server_name: "api.example.com".to_string(),
+        sent: vec![],
+        recv: vec![],
+        results: vec![HandlerResult {
+            handler: Handler {
+                handler_type: HandlerType::Recv,
+                part: HandlerPart::StatusCode,
+                action: HandlerAction::Reveal,
+                params: None,
+            },
+            value: "200".to_string(),
+            parsed: None,
+            key: None,
+            connection: 0,
+            server_name: "api.example.com".to_string(),
+            label_verified: true,
+        }],
+        assertions: vec![],
+        hashes: vec![],
+        commitments: vec![],
+        timestamp: 0,
+    }
+}
+
+#[test]
+fn sign_and_verify_roundtrip() {
+    for algorithm in [SigningAlgorithm::Secp256k1, SigningAlgorithm::Ed25519] {
+        let attestor = attestor(algorithm);
+        let public_key = attestor.public_key();
+        let attestation = attestor.sign(body());
+
+        assert_eq!(attestation.key_id, public_key.key_id);
+        let verified: AttestationBody = verify_attestation(&attestation, &public_key.public_key)
+            .expect("attestation should verify");
+        assert_eq!(verified.server_name, "api.example.com");
+        assert_eq!(verified.results[0].value, "200");
+        assert!(verified.timestamp > 0);
+    }
+}
+
+#[test]
+fn tampered_payload_is_rejected() {
+    for algorithm in [SigningAlgorithm::Secp256k1, SigningAlgorithm::Ed25519] {
+        let attestor = attestor(algorithm);
+        let mut attestation = attestor.sign(body());
+
+        let payload = base64::engine::general_purpose::STANDARD
+            .decode(&attestation.payload)
+            .unwrap();
+        let tampered = String::from_utf8(payload)
+            .unwrap()
+            .replace("api.example.com", "api.evil.com");
+        attestation.payload = base64::engine::general_purpose::STANDARD.encode(tampered);
+
+        let verified =
+            verify_attestation::<AttestationBody>(&attestation, &attestor.public_key().public_key);
+        assert!(verified.is_err());
+    }
+}
+
+#[test]
+fn missing_key_is_rejected() {
+    let result = Attestor::from_config(&AttestationConfig {
+        algorithm: SigningAlgorithm::Ed25519,
+        key: None,
+        key_file: None,
+    });
+    assert!(result.is_err());
+}
@@ -0,0 +1,147 @@
+//! Tests for API-key authentication and per-tenant quotas.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use axum::http::{header, HeaderMap, HeaderValue};
+
+use crate::auth::{request_api_key, AuthConfig, SessionRateLimiter, API_KEY_HEADER};
+use crate::{check_session_quotas, AppState, Config, SessionData, SessionInfo};
+
+const AUTH: &str = r#"
+api_keys:
+  - key: "key-acme-1"
+    tenant: acme
+  - key: "key-acme-2"
+    tenant: acme
+  - key: "key-globex"
+    tenant: globex
+tenants:
+  acme:
+    max_concurrent_sessions: 2
+    sessions_per_minute: 3
+"#;
+
+fn auth(yaml: &str) -> AuthConfig {
+    serde_yaml_ng::from_str(yaml).expect("valid auth config")
+}
+
+fn session(tenant: Option<&str>) -> SessionData {
+    SessionData {
+        prover_socket_txs: vec![],
+        tenant: tenant.map(str::to_string),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn keys_resolve_to_tenants() {
+    let config = auth(AUTH);
+    assert!(config.validate().is_ok());
+    assert_eq!(config.authenticate("key-acme-2"), Some("acme"));
+    assert_eq!(config.authenticate("key-globex"), Some("globex"));
+    assert_eq!(config.authenticate("key-acme"), None);
+    assert_eq!(config.authenticate(""), None);
+
+    for (yaml, error) in [
+        ("api_keys: []", "at least one"),
+        (
+            "api_keys: [{key: a, tenant: x}, {key: a, tenant: y}]",
+            "duplicate",
+        ),
+        (
+            "api_keys: [{key: a, tenant: x}]\ntenants: {y: {sessions_per_minute: 1}}",
+            "unknown tenant `y`",
+        ),
+        (
+            "api_keys: [{key: a, tenant: x}]\ntenants: {x: {max_concurrent_sessions: 0}}",
+            "greater than zero",
+        ),
+    ] {
+        let err = auth(yaml).validate().unwrap_err();
+        assert!(err.to_string().contains(error), "{}: {}", yaml, err);
+    }
+}
+
+#[test]
+fn api_key_from_header_bearer_or_query() {
+    let mut headers = HeaderMap::new();
+    assert_eq!(request_api_key(&headers, None), None);
+    assert_eq!(request_api_key(&headers, Some("q")), Some("q"));
+
+    headers.insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_static("Bearer bearer-key"),
+    );
+    assert_eq!(request_api_key(&headers, Some("q")), Some("bearer-key"));
+
+    headers.insert(API_KEY_HEADER, HeaderValue::from_static("header-key"));
+    assert_eq!(request_api_key(&headers, Some("q")), Some("header-key"));
+}
+
+#[test]
+fn rate_limiter_uses_rolling_minute() {
+    let limiter = SessionRateLimiter::default();
+    let start = Instant::now();
+
+    assert!(limiter.try_acquire("acme", 2, start));
+    assert!(limiter.try_acquire("acme", 2, start + Duration::from_secs(30)));
+    assert!(!limiter.try_acquire("acme", 2, start + Duration::from_secs(59)));
+    // Other tenants have their own window
+    assert!(limiter.try_acquire("globex", 2, start + Duration::from_secs(59)));
+    // The first registration leaves the window after a minute
+    assert!(limiter.try_acquire("acme", 2, start + Duration::from_secs(60)));
+    assert!(!limiter.try_acquire("acme", 2, start + Duration::from_secs(61)));
+}
+
+#[test]
+fn tenant_quotas_limit_registrations() {
+    let config = Config {
+        auth: Some(auth(AUTH)),
+        ..Config::default()
+    };
+    let state = AppState::new(config).unwrap();
+
+    let mut sessions = HashMap::new();
+    sessions.insert("a".to_string(), session(Some("acme")));
+    sessions.insert("b".to_string(), session(Some("globex")));
+    sessions.insert("c".to_string(), session(None));
+    assert!(check_session_quotas(&state, &sessions, Some("acme")).is_ok());
+
+    // Concurrent sessions count only the tenant's own
+    sessions.insert("d".to_string(), session(Some("acme")));
+    let (reason, msg) = check_session_quotas(&state, &sessions, Some("acme")).unwrap_err();
+    assert_eq!(reason, "tenant_quota");
+    assert!(msg.contains("2 concurrent sessions"), "{}", msg);
+    // Tenants without a quota are unlimited
+    assert!(check_session_quotas(&state, &sessions, Some("globex")).is_ok());
+
+    // One registration was admitted above; two more fit in the minute
+    sessions.clear();
+    assert!(check_session_quotas(&state, &sessions, Some("acme")).is_ok());
+    assert!(check_session_quotas(&state, &sessions, Some("acme")).is_ok());
+    let (_, msg) = check_session_quotas(&state, &sessions, Some("acme")).unwrap_err();
+    assert!(msg.contains("3 sessions per minute"), "{}", msg);
+}
+
+#[test]
+fn webhook_session_info_carries_tenant() {
+    let data = HashMap::from([
+        ("user".to_string(), "alice".to_string()),
+        ("tenant".to_string(), "spoofed".to_string()),
+    ]);
+
+    let info =
+        serde_json::to_value(SessionInfo::new("s1", Some("acme".to_string()), &data)).unwrap();
+    assert_eq!(
+        info,
+        serde_json::json!({ "id": "s1", "tenant": "acme", "user": "alice" })
+    );
+
+    // Without auth the session data is passed through untouched
+    let info = serde_json::to_value(SessionInfo::new("s1", None, &data)).unwrap();
+    assert_eq!(
+        info,
+        serde_json::json!({ "id": "s1", "tenant": "spoofed", "user": "alice" })
+    );
+}
@@ -0,0 +1,60 @@
+//! Tests for transcript commitments in session results.
+
+use serde_json::json;
+
+use crate::commitment::{CommitmentInfo, CommitmentKind};
+use crate::{Handler, HandlerAction, HandlerPart, HandlerType};
+
+#[test]
+fn commitments_serialize_with_kind_and_ranges() {
+    let hash = CommitmentInfo {
+        kind: CommitmentKind::Hash,
+        connection: 0,
+        direction: Some("recv".to_string()),
+        ranges: vec![[120, 140]],
+        algorithm: "SHA256".to_string(),
+        value: "ab".repeat(32),
+    };
+    assert_eq!(
+        serde_json::to_value(&hash).unwrap(),
+        json!({
+            "kind": "HASH",
+            "connection": 0,
+            "direction": "recv",
+            "ranges": [[120, 140]],
+            "algorithm": "SHA256",
+            "value": "ab".repeat(32),
+        })
+    );
+
+    // Encoding commitments name neither a direction nor ranges
+    let encoding = CommitmentInfo {
+        kind: CommitmentKind::Encoding,
+        connection: 1,
+        direction: None,
+        ranges: vec![],
+        algorithm: "BLAKE3".to_string(),
+        value: "cd".repeat(32),
+    };
+    let value = serde_json::to_value(&encoding).unwrap();
+    assert_eq!(value["kind"], "ENCODING");
+    assert!(value.get("direction").is_none());
+    assert!(value.get("ranges").is_none());
+    assert_eq!(
+        serde_json::from_value::<CommitmentInfo>(value).unwrap(),
+        encoding
+    );
+}
+
+#[test]
+fn encoding_handlers_parse() {
+    let handler: Handler = serde_json::from_value(json!({
+        "type": "RECV",
+        "part": "BODY",
+        "action": { "kind": "ENCODING" },
+    }))
+    .unwrap();
+    assert_eq!(handler.handler_type, HandlerType::Recv);
+    assert_eq!(handler.part, HandlerPart::Body);
+    assert!(matches!(handler.action, HandlerAction::Encoding));
+}
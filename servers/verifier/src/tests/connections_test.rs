@@ -0,0 +1,114 @@
+//! Tests for sessions with several TLS connections.
+
+use serde_json::json;
+use tokio::net::TcpListener;
+
+use super::session_config;
+use crate::error::{ErrorCode, SessionError};
+use crate::{
+    connect_proxy_server, connection_error, proxy_server_port, AppState, Config, Handler,
+    HandlerAction, HandlerPart, HandlerType, RangeWithHandler, RevealConfig, SessionConfig,
+};
+
+fn range(start: usize, end: usize, connection: usize) -> RangeWithHandler {
+    RangeWithHandler {
+        start,
+        end,
+        handler: Handler {
+            handler_type: HandlerType::Recv,
+            part: HandlerPart::Body,
+            action: HandlerAction::Reveal,
+            params: None,
+        },
+        connection,
+    }
+}
+
+#[test]
+fn ranges_default_to_the_first_connection() {
+    let range: RangeWithHandler = serde_json::from_value(json!({
+        "start": 0,
+        "end": 4,
+        "handler": { "type": "RECV", "part": "BODY" },
+    }))
+    .unwrap();
+    assert_eq!(range.connection, 0);
+}
+
+#[test]
+fn reveal_config_is_split_per_connection() {
+    let reveal_config = RevealConfig {
+        sent: vec![range(0, 4, 0), range(0, 8, 1)],
+        recv: vec![range(10, 20, 1), range(20, 30, 0), range(30, 40, 1)],
+    };
+
+    let first = reveal_config.for_connection(0);
+    assert_eq!(first.sent.len(), 1);
+    assert_eq!(first.recv.len(), 1);
+    assert_eq!((first.recv[0].start, first.recv[0].end), (20, 30));
+
+    let second = reveal_config.for_connection(1);
+    assert_eq!(second.sent.len(), 1);
+    assert_eq!(
+        second.recv.iter().map(|r| r.start).collect::<Vec<_>>(),
+        [10, 30]
+    );
+
+    let none = reveal_config.for_connection(2);
+    assert!(none.sent.is_empty() && none.recv.is_empty());
+}
+
+#[test]
+fn errors_name_the_connection_when_there_are_several() {
+    let error = || SessionError::new(ErrorCode::TimeoutProverConnect, "Timed out");
+
+    // Single-connection sessions keep their errors as they were
+    assert_eq!(
+        connection_error(&session_config(1), 0, error()).details,
+        None
+    );
+
+    let error = connection_error(&session_config(3), 2, error());
+    assert_eq!(
+        serde_json::to_value(&error).unwrap(),
+        json!({
+            "code": "TIMEOUT_PROVER_CONNECT",
+            "message": "Timed out",
+            "details": { "connection": 2 },
+        })
+    );
+}
+
+#[tokio::test]
+async fn every_connection_is_dialled_in_proxy_mode() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let config: Config = serde_yaml_ng::from_str(&format!(
+        r#"
+proxy:
+  upstreams:
+    "api.test": "{}"
+policies:
+  "api.test":
+    ports: [8443]
+"#,
+        listener.local_addr().unwrap()
+    ))
+    .unwrap();
+    let state = AppState::new(config).unwrap();
+    let session = SessionConfig {
+        server_name: Some("api.test".to_string()),
+        server_port: Some(8443),
+        ..session_config(2)
+    };
+
+    // Each connection dials the declared server on the declared port
+    for index in 0..2 {
+        let port = proxy_server_port(&session, "api.test");
+        assert_eq!(port, 8443, "{}", index);
+        connect_proxy_server(&state, "api.test", port)
+            .await
+            .unwrap();
+        listener.accept().await.unwrap();
+    }
+    assert_eq!(proxy_server_port(&session, "other.test"), 443);
+}
@@ -0,0 +1,89 @@
+//! Tests for outbound connections to servers.
+
+use std::sync::Arc;
+
+use tokio::net::TcpListener;
+
+use crate::connector::{Connector, Route};
+use crate::metrics::Metrics;
+use crate::proxy_guard::{ProxyConfig, ProxyRejection};
+
+fn connector(yaml: &str) -> (Connector, Arc<Metrics>) {
+    let config: ProxyConfig = serde_yaml_ng::from_str(yaml).expect("valid proxy config");
+    let metrics = Arc::new(Metrics::new());
+    (Connector::new(config, None, metrics.clone()), metrics)
+}
+
+#[tokio::test]
+async fn connects_to_permitted_targets_and_records_outcomes() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    // Loopback is refused by default, on both routes
+    let (blocked, metrics) = connector("{}");
+    let rejection = blocked
+        .connect(Route::Bridge, "127.0.0.1", port)
+        .await
+        .unwrap_err();
+    assert!(matches!(rejection, ProxyRejection::Forbidden(_)));
+    assert!(blocked
+        .connect(Route::Verifier, "127.0.0.1", port)
+        .await
+        .is_err());
+
+    let (open, _) = connector("block_private: false");
+    open.connect(Route::Bridge, "127.0.0.1", port)
+        .await
+        .unwrap();
+
+    let text = metrics.render().unwrap();
+    for line in [
+        r#"tlsn_upstream_connections_total{outcome="forbidden",route="proxy"} 1"#,
+        r#"tlsn_upstream_connections_total{outcome="forbidden",route="verifier"} 1"#,
+        r#"tlsn_upstream_connect_seconds_count{route="proxy"} 1"#,
+    ] {
+        assert!(text.contains(line), "missing {:?} in:\n{}", line, text);
+    }
+}
+
+#[tokio::test]
+async fn ipv6_literals_are_checked_by_address() {
+    let (connector, _) = connector("{}");
+    let rejection = connector
+        .connect(Route::Bridge, "::1", 443)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        rejection,
+        ProxyRejection::Forbidden("Proxy target ::1 is not allowed".to_string())
+    );
+}
+
+#[tokio::test]
+async fn caches_lookups_for_dns_cache_secs() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let upstream = format!("upstreams: {{ \"api.test\": \"localhost:{}\" }}", port);
+
+    let (cached, metrics) = connector(&upstream);
+    for _ in 0..2 {
+        cached
+            .connect(Route::Verifier, "api.test", 443)
+            .await
+            .unwrap();
+    }
+    let text = metrics.render().unwrap();
+    assert!(text.contains(r#"tlsn_dns_lookups_total{result="hit"} 1"#));
+    assert!(text.contains(r#"tlsn_dns_lookups_total{result="miss"} 1"#));
+
+    let (uncached, metrics) = connector(&format!("dns_cache_secs: 0\n{}", upstream));
+    for _ in 0..2 {
+        uncached
+            .connect(Route::Verifier, "api.test", 443)
+            .await
+            .unwrap();
+    }
+    let text = metrics.render().unwrap();
+    assert!(text.contains(r#"tlsn_dns_lookups_total{result="miss"} 2"#));
+    assert!(!text.contains(r#"result="hit""#));
+}
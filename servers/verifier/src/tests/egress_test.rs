@@ -0,0 +1,319 @@
+//! Tests for upstream connections through an egress proxy.
+
+use std::sync::Arc;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+
+use crate::connector::{Connector, Route};
+use crate::egress::{EgressConfig, EgressScheme};
+use crate::metrics::Metrics;
+use crate::proxy_guard::ProxyRejection;
+
+fn connector(yaml: &str) -> Connector {
+    let config: crate::Config = serde_yaml_ng::from_str(yaml).expect("valid config");
+    let egress = config.egress.clone();
+    if let Some(egress) = &egress {
+        egress.validate().expect("valid egress config");
+    }
+    Connector::new(config.proxy, egress, Arc::new(Metrics::new()))
+}
+
+/// A server that answers `ping` with `pong`.
+async fn echo_server() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let mut ping = [0u8; 4];
+            if stream.read_exact(&mut ping).await.is_ok() && &ping == b"ping" {
+                let _ = stream.write_all(b"pong").await;
+            }
+        }
+    });
+    port
+}
+
+/// A stand-in HTTP CONNECT proxy that tunnels to 127.0.0.1 and reports the
+/// request header of every tunnel it is asked for.
+async fn http_proxy(password: Option<&'static str>) -> (u16, mpsc::UnboundedReceiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (requests, received) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        while let Ok((mut client, _)) = listener.accept().await {
+            let mut header = Vec::new();
+            while !header.ends_with(b"\r\n\r\n") {
+                header.push(client.read_u8().await.unwrap());
+            }
+            let header = String::from_utf8(header).unwrap();
+            let _ = requests.send(header.clone());
+
+            // alice:<password>
+            let authorized = password.is_none_or(|password| {
+                use base64::Engine;
+                let credentials = format!("alice:{}", password);
+                let expected = base64::engine::general_purpose::STANDARD.encode(credentials);
+                header.contains(&format!("Proxy-Authorization: Basic {}\r\n", expected))
+            });
+            if !authorized {
+                let _ = client
+                    .write_all(b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n")
+                    .await;
+                continue;
+            }
+            let target_port: u16 = header
+                .split_whitespace()
+                .nth(1)
+                .and_then(|authority| authority.rsplit_once(':'))
+                .and_then(|(_, port)| port.parse().ok())
+                .unwrap();
+            let mut server = TcpStream::connect(("127.0.0.1", target_port))
+                .await
+                .unwrap();
+            client
+                .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")
+                .await
+                .unwrap();
+            tokio::spawn(async move {
+                let _ = tokio::io::copy_bidirectional(&mut client, &mut server).await;
+            });
+        }
+    });
+    (port, received)
+}
+
+/// A stand-in SOCKS5 proxy requiring alice/secret that tunnels to 127.0.0.1
+/// and reports the requested host of every tunnel.
+async fn socks5_proxy() -> (u16, mpsc::UnboundedReceiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (requests, received) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        while let Ok((mut client, _)) = listener.accept().await {
+            let mut greeting = [0u8; 2];
+            client.read_exact(&mut greeting).await.unwrap();
+            let mut methods = vec![0u8; greeting[1] as usize];
+            client.read_exact(&mut methods).await.unwrap();
+            assert!(methods.contains(&2));
+            client.write_all(&[5, 2]).await.unwrap();
+
+            let mut auth = vec![0u8; 2];
+            client.read_exact(&mut auth).await.unwrap();
+            let mut username = vec![0u8; auth[1] as usize];
+            client.read_exact(&mut username).await.unwrap();
+            let mut password = vec![0u8; client.read_u8().await.unwrap() as usize];
+            client.read_exact(&mut password).await.unwrap();
+            let valid = username == b"alice" && password == b"secret";
+            client.write_all(&[1, u8::from(!valid)]).await.unwrap();
+            if !valid {
+                continue;
+            }
+
+            let mut request = [0u8; 4];
+            client.read_exact(&mut request).await.unwrap();
+            let host = match request[3] {
+                1 => {
+                    let mut ip = [0u8; 4];
+                    client.read_exact(&mut ip).await.unwrap();
+                    std::net::Ipv4Addr::from(ip).to_string()
+                }
+                3 => {
+                    let mut name = vec![0u8; client.read_u8().await.unwrap() as usize];
+                    client.read_exact(&mut name).await.unwrap();
+                    String::from_utf8(name).unwrap()
+                }
+                _ => panic!("unexpected address type"),
+            };
+            let target_port = client.read_u16().await.unwrap();
+            let _ = requests.send(host);
+
+            let mut server = TcpStream::connect(("127.0.0.1", target_port))
+                .await
+                .unwrap();
+            client
+                .write_all(&[5, 0, 0, 1, 127, 0, 0, 1, 0, 0])
+                .await
+                .unwrap();
+            tokio::spawn(async move {
+                let _ = tokio::io::copy_bidirectional(&mut client, &mut server).await;
+            });
+        }
+    });
+    (port, received)
+}
+
+async fn ping(stream: &mut TcpStream) {
+    stream.write_all(b"ping").await.unwrap();
+    let mut pong = [0u8; 4];
+    stream.read_exact(&mut pong).await.unwrap();
+    assert_eq!(&pong, b"pong");
+}
+
+#[test]
+fn parses_proxy_urls() {
+    let egress: EgressConfig = serde_yaml_ng::from_str("url: socks5://proxy.corp").unwrap();
+    assert_eq!(egress.url.scheme, EgressScheme::Socks5);
+    assert_eq!(
+        (egress.url.host.as_str(), egress.url.port),
+        ("proxy.corp", 1080)
+    );
+
+    let egress: EgressConfig = serde_yaml_ng::from_str("url: http://[::1]:3128/").unwrap();
+    assert_eq!(egress.url.scheme, EgressScheme::Http);
+    assert_eq!((egress.url.host.as_str(), egress.url.port), ("::1", 3128));
+
+    for url in [
+        "ftp://proxy.corp",
+        "proxy.corp:3128",
+        "http://alice:pw@proxy.corp",
+    ] {
+        let yaml = format!("url: {:?}", url);
+        assert!(
+            serde_yaml_ng::from_str::<EgressConfig>(&yaml).is_err(),
+            "{}",
+            url
+        );
+    }
+
+    let egress: EgressConfig =
+        serde_yaml_ng::from_str("{ url: 'http://proxy.corp', password: pw }").unwrap();
+    assert!(egress.validate().is_err());
+}
+
+#[tokio::test]
+async fn tunnels_through_an_http_connect_proxy() {
+    let server = echo_server().await;
+    let (proxy, mut requests) = http_proxy(Some("secret")).await;
+    let tunnelled = connector(&format!(
+        "{{ proxy: {{ block_private: false }}, egress: {{ url: 'http://127.0.0.1:{}', \
+         username: alice, password: secret }} }}",
+        proxy
+    ));
+
+    let mut stream = tunnelled
+        .connect(Route::Bridge, "127.0.0.1", server)
+        .await
+        .unwrap();
+    ping(&mut stream).await;
+    let request = requests.recv().await.unwrap();
+    assert!(request.starts_with(&format!("CONNECT 127.0.0.1:{} HTTP/1.1\r\n", server)));
+
+    // Wrong credentials are reported as a failed connection
+    let unauthorized = connector(&format!(
+        "{{ proxy: {{ block_private: false }}, egress: {{ url: 'http://127.0.0.1:{}', \
+         username: alice, password: wrong }} }}",
+        proxy
+    ));
+    let rejection = unauthorized
+        .connect(Route::Verifier, "127.0.0.1", server)
+        .await
+        .unwrap_err();
+    let ProxyRejection::ConnectFailed(msg) = rejection else {
+        panic!("unexpected rejection: {:?}", rejection);
+    };
+    assert!(msg.contains("407"), "{}", msg);
+}
+
+#[tokio::test]
+async fn socks5_proxy_resolves_names_with_remote_dns() {
+    let server = echo_server().await;
+    let (proxy, mut requests) = socks5_proxy().await;
+    let remote = connector(&format!(
+        "{{ proxy: {{ block_private: false }}, egress: {{ url: 'socks5://127.0.0.1:{}', \
+         username: alice, password: secret, remote_dns: true }} }}",
+        proxy
+    ));
+
+    // Sent by name, unresolved
+    let mut stream = remote
+        .connect(Route::Verifier, "server.invalid", server)
+        .await
+        .unwrap();
+    ping(&mut stream).await;
+    assert_eq!(requests.recv().await.unwrap(), "server.invalid");
+
+    // IP targets are still checked locally
+    let blocked = connector(&format!(
+        "egress: {{ url: 'socks5://127.0.0.1:{}', username: alice, password: secret, \
+         remote_dns: true }}",
+        proxy
+    ));
+    let rejection = blocked
+        .connect(Route::Verifier, "127.0.0.1", server)
+        .await
+        .unwrap_err();
+    assert!(matches!(rejection, ProxyRejection::Forbidden(_)));
+}
+
+#[tokio::test]
+async fn names_resolved_by_the_egress_proxy_are_still_checked() {
+    let server = echo_server().await;
+    let (proxy, mut requests) = socks5_proxy().await;
+    let egress = format!(
+        "egress: {{ url: 'socks5://127.0.0.1:{}', username: alice, password: secret, \
+         remote_dns: true }}",
+        proxy
+    );
+
+    // Only names matching an `allow` hostname rule are sent
+    let allowlisted = connector(&format!(
+        "{{ proxy: {{ allow: ['api.example.com'] }}, {} }}",
+        egress
+    ));
+    let mut stream = allowlisted
+        .connect(Route::Verifier, "api.example.com", server)
+        .await
+        .unwrap();
+    ping(&mut stream).await;
+    assert_eq!(requests.recv().await.unwrap(), "api.example.com");
+    let rejection = allowlisted
+        .connect(Route::Verifier, "other.example.com", server)
+        .await
+        .unwrap_err();
+    assert!(matches!(rejection, ProxyRejection::Forbidden(_)));
+
+    // Local names are refused without an allow list
+    let open = connector(&format!("{{ {} }}", egress));
+    for host in [
+        "localhost",
+        "metadata.internal",
+        "printer.local",
+        "intranet",
+    ] {
+        let rejection = open
+            .connect(Route::Verifier, host, server)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(rejection, ProxyRejection::Forbidden(_)),
+            "{}",
+            host
+        );
+    }
+    assert!(requests.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn no_proxy_targets_are_dialled_directly() {
+    let server = echo_server().await;
+    let (proxy, mut requests) = http_proxy(None).await;
+    let direct = connector(&format!(
+        "{{ proxy: {{ block_private: false, upstreams: {{ api.internal: 'localhost:{}' }} }}, \
+         egress: {{ url: 'http://127.0.0.1:{}', no_proxy: ['localhost', '127.0.0.0/8'] }} }}",
+        server, proxy
+    ));
+
+    let mut stream = direct
+        .connect(Route::Bridge, "127.0.0.1", server)
+        .await
+        .unwrap();
+    ping(&mut stream).await;
+    let mut stream = direct
+        .connect(Route::Verifier, "api.internal", 443)
+        .await
+        .unwrap();
+    ping(&mut stream).await;
+    assert!(requests.try_recv().is_err());
+}
@@ -0,0 +1,106 @@
+//! Tests for machine-readable session errors.
+
+use std::collections::HashMap;
+
+use serde_json::json;
+use tokio::sync::oneshot;
+
+use super::{app_state, session_config};
+use crate::error::{ErrorCode, SessionError};
+use crate::{fail_session, ServerMessage, SessionConfig};
+
+fn declared_session(server_name: &str) -> SessionConfig {
+    SessionConfig {
+        server_name: Some(server_name.to_string()),
+        ..session_config(1)
+    }
+}
+
+#[test]
+fn error_messages_carry_code_and_details() {
+    let error = SessionError::new(ErrorCode::InvalidRevealRange, "Invalid recv range [10, 20)")
+        .with_range("recv", 10, 20);
+    assert_eq!(
+        serde_json::to_value(ServerMessage::Error { error }).unwrap(),
+        json!({
+            "type": "error",
+            "code": "INVALID_REVEAL_RANGE",
+            "message": "Invalid recv range [10, 20)",
+            "details": { "direction": "recv", "range": [10, 20] },
+        })
+    );
+
+    // Details are left out when a code has none
+    let error = SessionError::new(ErrorCode::TimeoutProverConnect, "Timed out");
+    assert_eq!(
+        serde_json::to_value(ServerMessage::Error { error }).unwrap(),
+        json!({ "type": "error", "code": "TIMEOUT_PROVER_CONNECT", "message": "Timed out" })
+    );
+
+    let error = SessionError::new(ErrorCode::LimitExceeded, "Too much")
+        .with_direction("sent")
+        .with_limit(8192, 4096);
+    assert_eq!(
+        serde_json::to_value(&error.details).unwrap(),
+        json!({ "direction": "sent", "requested": 8192, "limit": 4096 })
+    );
+}
+
+#[tokio::test]
+async fn failed_sessions_report_codes_to_client_and_webhook() {
+    let state = app_state(
+        r#"
+webhooks:
+  "api.example.com":
+    url: "http://127.0.0.1:9/hook"
+    notify_failures: true
+  "quiet.example.com":
+    url: "http://127.0.0.1:9/hook"
+"#,
+    );
+    let data = HashMap::from([("user".to_string(), "alice".to_string())]);
+
+    let (result_tx, result_rx) = oneshot::channel();
+    fail_session(
+        &state,
+        "s1",
+        &declared_session("api.example.com"),
+        &data,
+        Some("api.example.com".to_string()),
+        result_tx,
+        "invalid_range",
+        SessionError::new(ErrorCode::InvalidRevealRange, "Invalid sent range [0, 5)")
+            .with_range("sent", 0, 5),
+    )
+    .await;
+
+    let error = result_rx.await.unwrap().error.expect("failed session");
+    assert_eq!(error.code, ErrorCode::InvalidRevealRange);
+
+    let pending = state.webhooks.pending().await;
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].session_id, "s1");
+    assert_eq!(pending[0].payload["session"]["user"], "alice");
+    assert_eq!(pending[0].payload["error"]["code"], "INVALID_REVEAL_RANGE");
+    assert_eq!(
+        pending[0].payload["error"]["details"]["range"],
+        json!([0, 5])
+    );
+
+    // Webhooks get failures only if they ask for them
+    let (result_tx, result_rx) = oneshot::channel();
+    fail_session(
+        &state,
+        "s2",
+        &declared_session("quiet.example.com"),
+        &data,
+        Some("quiet.example.com".to_string()),
+        result_tx,
+        "policy",
+        SessionError::new(ErrorCode::PolicyDenied, "Denied"),
+    )
+    .await;
+    let error = result_rx.await.unwrap().error.expect("failed session");
+    assert_eq!(error.code, ErrorCode::PolicyDenied);
+    assert_eq!(state.webhooks.pending().await.len(), 1);
+}
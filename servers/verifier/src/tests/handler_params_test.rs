@@ -0,0 +1,107 @@
+//! Tests for handler params in `reveal_config` and typed JSON extraction.
+
+use serde_json::json;
+
+use crate::{decode_json_field, HandlerParams, RangeWithHandler};
+
+fn params(path: &str) -> HandlerParams {
+    HandlerParams {
+        content_type: Some("json".to_string()),
+        path: Some(path.to_string()),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn params_travel_through_reveal_config() {
+    let range: RangeWithHandler = serde_json::from_value(json!({
+        "start": 10,
+        "end": 20,
+        "handler": {
+            "type": "RECV",
+            "part": "BODY",
+            "action": { "kind": "REVEAL" },
+            "params": { "type": "json", "path": "accounts.EUR", "hideKey": true }
+        }
+    }))
+    .expect("range with params");
+
+    let params = range.handler.params.as_ref().expect("params kept");
+    assert_eq!(params.content_type.as_deref(), Some("json"));
+    assert_eq!(params.path.as_deref(), Some("accounts.EUR"));
+    assert_eq!(params.hide_key, Some(true));
+
+    let serialized = serde_json::to_value(&range).unwrap();
+    assert_eq!(serialized["handler"]["params"]["hideKey"], true);
+    assert!(serialized["handler"]["params"].get("key").is_none());
+
+    // Handlers without params are unchanged on the wire
+    let bare: RangeWithHandler = serde_json::from_value(json!({
+        "start": 0,
+        "end": 3,
+        "handler": { "type": "RECV", "part": "STATUS_CODE" }
+    }))
+    .unwrap();
+    assert!(bare.handler.params.is_none());
+    assert!(serde_json::to_value(&bare).unwrap()["handler"]
+        .get("params")
+        .is_none());
+}
+
+#[test]
+fn decodes_key_and_typed_value() {
+    assert_eq!(
+        decode_json_field(r#""balance": 1500.25"#, &params("accounts.0.balance")),
+        (Some(json!(1500.25)), Some("balance".to_string()))
+    );
+    assert_eq!(
+        decode_json_field(r#""verified":true,"#, &params("verified")),
+        (Some(json!(true)), Some("verified".to_string()))
+    );
+    assert_eq!(
+        decode_json_field(r#""user": {"id": "u-\"42\""}"#, &params("user")),
+        (Some(json!({ "id": "u-\"42\"" })), Some("user".to_string()))
+    );
+}
+
+#[test]
+fn reports_only_revealed_keys() {
+    // hideKey: only the value is revealed
+    assert_eq!(
+        decode_json_field(r#"{"EUR": 10}"#, &params("accounts")),
+        (Some(json!({ "EUR": 10 })), None)
+    );
+    assert_eq!(
+        decode_json_field(r#""alice""#, &params("0.screen_name")),
+        (Some(json!("alice")), None)
+    );
+
+    // hideValue: only the key is revealed
+    let hide_value = HandlerParams {
+        hide_value: Some(true),
+        ..params("stats.rating")
+    };
+    assert_eq!(
+        decode_json_field(r#""rating""#, &hide_value),
+        (None, Some("rating".to_string()))
+    );
+
+    // Not JSON: the raw value is still reported, just without a typed value
+    assert_eq!(
+        decode_json_field("ERROR: Invalid range [0, 5)", &params("id")),
+        (None, None)
+    );
+}
+
+#[test]
+fn path_does_not_name_the_key() {
+    // The path claims `EUR`, but the revealed bytes hold `USD`
+    assert_eq!(
+        decode_json_field(r#""USD": 1500.25"#, &params("accounts.EUR")),
+        (Some(json!(1500.25)), Some("USD".to_string()))
+    );
+    assert_eq!(
+        decode_json_field("1500.25", &params("accounts.EUR")),
+        (Some(json!(1500.25)), None)
+    );
+}
@@ -0,0 +1,314 @@
+//! Tests for checking range labels against the HTTP layout of the transcript.
+
+use std::ops::Range;
+
+use crate::http_layout::{check_labels, spans_whole_part, validate_labels, LabelCheck};
+use crate::{Handler, HandlerAction, HandlerPart, HandlerType, RangeWithHandler, RevealConfig};
+
+const REQUEST: &[u8] = b"GET /api HTTP/1.1\r\nHost: api.example.com\r\nCookie: secret\r\n\r\n";
+const RESPONSE: &[u8] =
+    b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: 13\r\n\r\n{\"balance\":5}";
+
+fn range(
+    handler_type: HandlerType,
+    part: HandlerPart,
+    start: usize,
+    end: usize,
+) -> RangeWithHandler {
+    RangeWithHandler {
+        start,
+        end,
+        handler: Handler {
+            handler_type,
+            part,
+            action: HandlerAction::Reveal,
+            params: None,
+        },
+        connection: 0,
+    }
+}
+
+/// Position of the first occurrence of `needle`.
+fn find(bytes: &[u8], needle: &str) -> usize {
+    bytes
+        .windows(needle.len())
+        .position(|window| window == needle.as_bytes())
+        .expect("needle in transcript")
+}
+
+/// Reveals every byte.
+fn all(_: usize) -> bool {
+    true
+}
+
+/// Reveals only the bytes within `ranges`.
+fn only(ranges: &[Range<usize>]) -> impl Fn(usize) -> bool + '_ {
+    |i| ranges.iter().any(|range| range.contains(&i))
+}
+
+/// Checks one range against a transcript in which `is_known` bytes are revealed.
+fn check(
+    bytes: &[u8],
+    is_known: impl Fn(usize) -> bool,
+    part: HandlerPart,
+    start: usize,
+    end: usize,
+) -> LabelCheck {
+    let is_request = bytes.starts_with(b"GET") || bytes.starts_with(b"POST");
+    let handler_type = if is_request {
+        HandlerType::Sent
+    } else {
+        HandlerType::Recv
+    };
+    let ranges = [range(handler_type, part, start, end)];
+    check_labels(bytes, is_known, &ranges, is_request)[0]
+}
+
+#[test]
+fn fully_revealed_request_parts() {
+    let host = find(REQUEST, "Host");
+
+    assert_eq!(
+        check(REQUEST, all, HandlerPart::StartLine, 0, 17),
+        LabelCheck::Verified
+    );
+    assert_eq!(
+        check(REQUEST, all, HandlerPart::StartLine, 0, 19),
+        LabelCheck::Verified
+    );
+    assert_eq!(
+        check(REQUEST, all, HandlerPart::Method, 0, 3),
+        LabelCheck::Verified
+    );
+    assert_eq!(
+        check(REQUEST, all, HandlerPart::RequestTarget, 4, 8),
+        LabelCheck::Verified
+    );
+    assert_eq!(
+        check(REQUEST, all, HandlerPart::Protocol, 9, 17),
+        LabelCheck::Verified
+    );
+    assert_eq!(
+        check(REQUEST, all, HandlerPart::Headers, host, host + 23),
+        LabelCheck::Verified
+    );
+    assert_eq!(
+        check(REQUEST, all, HandlerPart::All, 0, REQUEST.len()),
+        LabelCheck::Verified
+    );
+
+    // A header passed off as the start line, the start line as a header
+    assert_eq!(
+        check(REQUEST, all, HandlerPart::StartLine, host, host + 4),
+        LabelCheck::Mislabelled
+    );
+    assert_eq!(
+        check(REQUEST, all, HandlerPart::Headers, 0, 17),
+        LabelCheck::Mislabelled
+    );
+    assert_eq!(
+        check(REQUEST, all, HandlerPart::StatusCode, 9, 12),
+        LabelCheck::Mislabelled
+    );
+}
+
+#[test]
+fn status_code_and_body_of_response() {
+    let body = find(RESPONSE, "{");
+
+    assert_eq!(
+        check(RESPONSE, all, HandlerPart::StatusCode, 9, 12),
+        LabelCheck::Verified
+    );
+    assert_eq!(
+        check(RESPONSE, all, HandlerPart::Protocol, 0, 8),
+        LabelCheck::Verified
+    );
+    assert_eq!(
+        check(RESPONSE, all, HandlerPart::Body, body, RESPONSE.len()),
+        LabelCheck::Verified
+    );
+
+    // Body bytes cannot be relabelled as a status code or header
+    assert_eq!(
+        check(RESPONSE, all, HandlerPart::StatusCode, body + 11, body + 12),
+        LabelCheck::Mislabelled
+    );
+    assert_eq!(
+        check(RESPONSE, all, HandlerPart::Headers, body, body + 5),
+        LabelCheck::Mislabelled
+    );
+    assert_eq!(
+        check(RESPONSE, all, HandlerPart::Body, 0, 15),
+        LabelCheck::Mislabelled
+    );
+    assert_eq!(
+        check(RESPONSE, all, HandlerPart::Method, 0, 4),
+        LabelCheck::Mislabelled
+    );
+}
+
+#[test]
+fn hidden_bytes_make_labels_unverifiable_but_not_mislabelled() {
+    // Only the status code and the body value are revealed
+    let body = find(RESPONSE, "{");
+    let revealed = [9..12, body..RESPONSE.len()];
+
+    assert_eq!(
+        check(RESPONSE, only(&revealed), HandlerPart::StatusCode, 9, 12),
+        LabelCheck::Verified
+    );
+    // The blank line ending the headers is hidden, so the body start is unknown
+    assert_eq!(
+        check(
+            RESPONSE,
+            only(&revealed),
+            HandlerPart::Body,
+            body,
+            RESPONSE.len()
+        ),
+        LabelCheck::Unverifiable
+    );
+    // Still provably not the status code
+    assert_eq!(
+        check(
+            RESPONSE,
+            only(&revealed),
+            HandlerPart::StatusCode,
+            body,
+            body + 3
+        ),
+        LabelCheck::Mislabelled
+    );
+
+    // The request line is complete, so its hidden CRLF is implied, but the
+    // hidden Cookie header may hide the end of the headers
+    let host = find(REQUEST, "Host");
+    let cookie = find(REQUEST, "Cookie");
+    let revealed = [0..17, host..cookie];
+    assert_eq!(
+        check(REQUEST, only(&revealed), HandlerPart::Headers, host, cookie),
+        LabelCheck::Verified
+    );
+    assert_eq!(
+        check(
+            REQUEST,
+            only(&revealed),
+            HandlerPart::Headers,
+            cookie,
+            cookie + 14
+        ),
+        LabelCheck::Unverifiable
+    );
+}
+
+#[test]
+fn chunked_framing_delimits_pipelined_responses() {
+    let transcript: &[u8] = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+        4\r\nabcd\r\n3;ext=1\r\nefg\r\n0\r\n\r\n\
+        HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n";
+    let second = find(transcript, "HTTP/1.1 404");
+    let first_body = find(transcript, "4\r\nabcd");
+
+    assert_eq!(
+        check(transcript, all, HandlerPart::Body, first_body, second),
+        LabelCheck::Verified
+    );
+    assert_eq!(
+        check(
+            transcript,
+            all,
+            HandlerPart::StatusCode,
+            second + 9,
+            second + 12
+        ),
+        LabelCheck::Verified
+    );
+    assert_eq!(
+        check(transcript, all, HandlerPart::Body, first_body, second + 8),
+        LabelCheck::Mislabelled
+    );
+}
+
+#[test]
+fn framing_lengths_past_the_address_space_are_unknown() {
+    let transcript: &[u8] =
+        b"HTTP/1.1 200 OK\r\nContent-Length: 18446744073709551615\r\n\r\n{\"ok\":true}";
+    let body = find(transcript, "{");
+    assert_eq!(
+        check(transcript, all, HandlerPart::Body, body, transcript.len()),
+        LabelCheck::Verified
+    );
+
+    // A chunk that would end just short of `usize::MAX`
+    let head = "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n";
+    let size = format!("{:x}", usize::MAX - 1 - (head.len() + 18));
+    let transcript = format!("{}{}\r\nabcd", head, size);
+    assert_eq!(size.len(), 16);
+    assert_eq!(
+        check(
+            transcript.as_bytes(),
+            all,
+            HandlerPart::Body,
+            head.len(),
+            transcript.len()
+        ),
+        LabelCheck::Verified
+    );
+}
+
+#[test]
+fn reveal_config_reports_first_mislabelled_range() {
+    let host = find(REQUEST, "Host");
+    let mut reveal_config = RevealConfig {
+        sent: vec![range(
+            HandlerType::Sent,
+            HandlerPart::Headers,
+            host,
+            host + 23,
+        )],
+        recv: vec![range(HandlerType::Recv, HandlerPart::StatusCode, 9, 12)],
+    };
+    let unverified = validate_labels(&reveal_config, (REQUEST, all), (RESPONSE, all))
+        .expect("labels are correct");
+    assert!(unverified.is_empty());
+
+    reveal_config
+        .recv
+        .push(range(HandlerType::Recv, HandlerPart::StatusCode, 16, 19));
+    let err = validate_labels(&reveal_config, (REQUEST, all), (RESPONSE, all)).unwrap_err();
+    assert!(err.contains("recv range [16, 19)"), "{}", err);
+    assert!(err.contains("RECV STATUS_CODE"), "{}", err);
+
+    // Direction must match the transcript the range is in
+    reveal_config.recv = vec![range(HandlerType::Sent, HandlerPart::StatusCode, 9, 12)];
+    assert!(validate_labels(&reveal_config, (REQUEST, all), (RESPONSE, all)).is_err());
+}
+
+#[test]
+fn whole_part_ranges_need_both_ends_revealed() {
+    let body = find(RESPONSE, "{");
+    let ranges = [
+        range(HandlerType::Recv, HandlerPart::Body, body, RESPONSE.len()),
+        range(
+            HandlerType::Recv,
+            HandlerPart::Body,
+            body + 1,
+            RESPONSE.len() - 1,
+        ),
+        range(HandlerType::Recv, HandlerPart::StatusCode, 9, 12),
+        range(HandlerType::Recv, HandlerPart::All, 0, RESPONSE.len()),
+        range(HandlerType::Recv, HandlerPart::All, 0, body),
+    ];
+    assert_eq!(
+        spans_whole_part(RESPONSE, all, &ranges, false),
+        [true, false, true, true, false]
+    );
+
+    // With the header block hidden, the body could start anywhere
+    let revealed = [0..12, body..RESPONSE.len()];
+    assert_eq!(
+        spans_whole_part(RESPONSE, only(&revealed), &ranges[..1], false),
+        [false]
+    );
+}
@@ -0,0 +1,134 @@
+//! Tests for the /info capabilities and /ready readiness endpoints.
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde_json::{json, Value};
+
+use super::app_state;
+use crate::{info_handler, ready_handler, SessionData};
+
+const CONFIG: &str = r#"
+attestation:
+  algorithm: ed25519
+  key: "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318"
+limits:
+  max_sent_data: 4096
+  max_concurrent_sessions: 1
+auth:
+  api_keys:
+    - { key: "key-acme", tenant: acme }
+policies:
+  "blocked.example.com":
+    allow: false
+  "api.example.com":
+    max_recv_data: 8192
+    forbid_reveal:
+      - { type: SENT, part: HEADERS, header: cookie }
+    hash_algorithms: [SHA256]
+"#;
+
+async fn json_body(response: Response) -> (StatusCode, Value) {
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+#[tokio::test]
+async fn info_publishes_capabilities() {
+    let state = app_state(CONFIG);
+    let (status, info) = json_body(info_handler(State(state.clone())).await.into_response()).await;
+    assert_eq!(status, StatusCode::OK);
+
+    assert_eq!(info["modes"], json!(["Mpc", "Proxy"]));
+    assert_eq!(
+        info["hash_algorithms"],
+        json!(["BLAKE3", "SHA256", "KECCAK256"])
+    );
+    assert_eq!(info["limits"]["max_sent_data"], 4096);
+    assert_eq!(info["limits"]["max_recv_data"], Value::Null);
+    assert_eq!(info["limits"]["oversized_registration"], "reject");
+    assert_eq!(info["api_key_required"], true);
+    assert_eq!(info["allow_unverified_labels"], false);
+
+    // Sorted by server name, with only the enforced parts
+    assert_eq!(
+        info["policies"],
+        json!([
+            {
+                "server_name": "api.example.com",
+                "allow": true,
+                "max_sent_data": null,
+                "max_recv_data": 8192,
+                "ports": [],
+                "require_reveal": [],
+                "forbid_reveal": [{ "type": "SENT", "part": "HEADERS", "header": "cookie" }],
+                "hash_algorithms": ["SHA256"],
+                "require_verified_labels": false
+            },
+            {
+                "server_name": "blocked.example.com",
+                "allow": false,
+                "max_sent_data": null,
+                "max_recv_data": null,
+                "ports": [],
+                "require_reveal": [],
+                "forbid_reveal": [],
+                "hash_algorithms": null,
+                "require_verified_labels": false
+            }
+        ])
+    );
+
+    let public_key = state.attestor.as_ref().unwrap().public_key();
+    assert_eq!(info["attestation_key"]["algorithm"], "ed25519");
+    assert_eq!(info["attestation_key"]["key_id"], public_key.key_id);
+
+    // A bare config publishes no key and no policies
+    let (_, info) = json_body(info_handler(State(app_state("{}"))).await.into_response()).await;
+    assert!(info.get("attestation_key").is_none());
+    assert_eq!(info["policies"], json!([]));
+    assert_eq!(info["api_key_required"], false);
+}
+
+#[tokio::test]
+async fn ready_reflects_capacity_and_draining() {
+    let state = app_state(CONFIG);
+    let ready =
+        || async { json_body(ready_handler(State(state.clone())).await.into_response()).await };
+
+    let (status, body) = ready().await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body,
+        json!({
+            "ready": true,
+            "draining": false,
+            "active_sessions": 0,
+            "max_concurrent_sessions": 1
+        })
+    );
+
+    state.sessions.lock().await.insert(
+        "s1".to_string(),
+        SessionData {
+            prover_socket_txs: vec![],
+            tenant: None,
+            ..Default::default()
+        },
+    );
+    let (status, body) = ready().await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["ready"], false);
+    assert_eq!(body["active_sessions"], 1);
+
+    state.sessions.lock().await.clear();
+    state.shutdown.start_draining();
+    let (status, body) = ready().await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["draining"], true);
+}
@@ -178,16 +178,31 @@ webhooks:
         webhook_port
     );
 
-    let config: crate::Config = serde_yaml_ng::from_str(&config_yaml).unwrap();
+    serve_verifier(&config_yaml, verifier_port).await
+}
 
-    let app_state = Arc::new(crate::AppState {
-        sessions: Arc::new(Mutex::new(HashMap::new())),
-        config: Arc::new(config),
-    });
+/// Serves the verifier's public routes with the given `config.yaml` contents
+async fn serve_verifier(config_yaml: &str, verifier_port: u16) -> JoinHandle<()> {
+    let config: crate::Config = serde_yaml_ng::from_str(config_yaml).unwrap();
+
+    let app_state = Arc::new(crate::AppState::new(config).unwrap());
 
     let app = Router::new()
-        .route("/health", axum::routing::get(|| async { "ok" }))
+        .route("/health", axum::routing::get(crate::health_handler))
+        .route("/ready", axum::routing::get(crate::ready_handler))
         .route("/info", axum::routing::get(crate::info_handler))
+        .route(
+            "/sessions",
+            axum::routing::get(crate::list_sessions_handler),
+        )
+        .route(
+            "/sessions/{id}",
+            axum::routing::get(crate::get_session_handler),
+        )
+        .route(
+            "/sessions/{id}/openings",
+            axum::routing::post(crate::open_commitment_handler),
+        )
         .route("/session", axum::routing::get(crate::session_ws_handler))
         .route("/verifier", axum::routing::get(crate::verifier_ws_handler))
         .route("/proxy", axum::routing::get(crate::proxy_ws_handler))
@@ -574,7 +589,7 @@ async fn run_prover(
 // Integration Tests
 // ============================================================================
 
-/// Test the /health endpoint
+/// Test the /health and /ready endpoints
 #[tokio::test]
 async fn health() {
     let _ = tracing_subscriber::fmt()
@@ -594,6 +609,15 @@ async fn health() {
     assert_eq!(resp.status(), StatusCode::OK);
     assert_eq!(resp.text().await.unwrap(), "ok");
 
+    let resp = client
+        .get(format!("http://127.0.0.1:{}/ready", VERIFIER_PORT + 1))
+        .send()
+        .await
+        .expect("Failed to send request");
+    assert_eq!(resp.status(), StatusCode::OK);
+    let ready: Value = resp.json().await.expect("Failed to parse JSON");
+    assert_eq!(ready["ready"], true);
+
     verifier_handle.abort();
 }
 
@@ -623,6 +647,70 @@ async fn info() {
     info.get("git_hash").expect("Missing git_hash field");
     info.get("tlsn_version")
         .expect("Missing tlsn_version field");
+    info.get("modes").expect("Missing modes field");
+    info.get("hash_algorithms")
+        .expect("Missing hash_algorithms field");
+    info.get("limits").expect("Missing limits field");
+
+    verifier_handle.abort();
+}
+
+/// Test that stored sessions are only served with a valid API key
+#[tokio::test]
+async fn stored_sessions_require_an_api_key() {
+    let config_yaml = r#"
+auth:
+  api_keys:
+    - key: "key-acme"
+      tenant: acme
+store:
+  backend: sqlite
+  path: ":memory:"
+"#;
+    let verifier_handle = serve_verifier(config_yaml, VERIFIER_PORT + 3).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let client = reqwest::Client::new();
+    let base = format!("http://127.0.0.1:{}", VERIFIER_PORT + 3);
+    for path in ["/sessions", "/sessions/missing"] {
+        let resp = client
+            .get(format!("{}{}", base, path))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED, "{}", path);
+
+        let resp = client
+            .get(format!("{}{}", base, path))
+            .header("X-API-Key", "key-other")
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED, "{}", path);
+    }
+    let resp = client
+        .post(format!("{}/sessions/missing/openings", base))
+        .json(&json!({ "start": 0, "end": 1, "direction": "recv", "plaintext": "a", "blinder": "00" }))
+        .send()
+        .await
+        .expect("Failed to send request");
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    // With a key the request reaches the store
+    let resp = client
+        .get(format!("{}/sessions", base))
+        .header("X-API-Key", "key-acme")
+        .send()
+        .await
+        .expect("Failed to send request");
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = client
+        .get(format!("{}/sessions/missing", base))
+        .bearer_auth("key-acme")
+        .send()
+        .await
+        .expect("Failed to send request");
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
 
     verifier_handle.abort();
 }
@@ -699,6 +787,7 @@ async fn test_webhook_integration_with_github() {
             handler_type: HandlerType::Sent,
             part: HandlerPart::All,
             action: HandlerAction::Reveal,
+            params: None,
         },
     }];
     let recv_ranges = vec![RangeWithHandler {
@@ -708,6 +797,7 @@ async fn test_webhook_integration_with_github() {
             handler_type: HandlerType::Recv,
             part: HandlerPart::All,
             action: HandlerAction::Reveal,
+            params: None,
         },
     }];
 
@@ -0,0 +1,156 @@
+//! Tests for configurable timeouts and server-wide limits.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::error::ErrorCode;
+use crate::limits::{LimitsConfig, OversizedRegistration};
+use crate::{AppState, Config};
+
+fn limits(yaml: &str) -> LimitsConfig {
+    serde_yaml_ng::from_str(yaml).expect("valid limits")
+}
+
+/// Env lookup backed by a fixed set of variables.
+fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+    let vars: HashMap<String, String> = vars
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+    move |name| vars.get(name).cloned()
+}
+
+#[test]
+fn config_file_is_optional_but_must_parse() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("config.yaml");
+    let config = Config::load(&path).expect("missing file gives defaults");
+    assert!(config.webhooks.is_empty());
+
+    std::fs::write(&path, "limits: { max_concurrent_sessions: 5 }").unwrap();
+    let config = Config::load(&path).expect("valid file");
+    assert_eq!(config.limits.max_concurrent_sessions, Some(5));
+
+    std::fs::write(&path, "limits: [not, a, map]").unwrap();
+    let err = Config::load(&path).unwrap_err().to_string();
+    assert!(err.contains("Failed to parse config file"), "{}", err);
+}
+
+#[test]
+fn defaults_match_previous_constants() {
+    let config: Config = serde_yaml_ng::from_str("webhooks: {}").unwrap();
+    let defaults = config.limits;
+    assert_eq!(defaults.prover_connect_timeout(), Duration::from_secs(30));
+    assert_eq!(defaults.verification_timeout(), Duration::from_secs(120));
+    assert_eq!(defaults.reveal_config_timeout(), Duration::from_secs(30));
+    assert_eq!(defaults.max_session_lifetime(), None);
+    assert_eq!(defaults.max_concurrent_sessions, None);
+    assert_eq!(defaults.drain_timeout(), Duration::from_secs(180));
+    assert_eq!(defaults.resume_buffer(), Duration::from_secs(60));
+    assert_eq!(
+        defaults.admit_data_limits(1 << 20, 1 << 24),
+        Ok((1 << 20, 1 << 24))
+    );
+    assert_eq!(defaults.max_connections_per_session, 4);
+    assert!(defaults.validate().is_ok());
+
+    let partial = limits("verification_timeout_secs: 300");
+    assert_eq!(partial.verification_timeout(), Duration::from_secs(300));
+    assert_eq!(partial.prover_connect_timeout(), Duration::from_secs(30));
+}
+
+#[test]
+fn env_overrides_config_values() {
+    let mut config =
+        limits("verification_timeout_secs: 300\nmax_sent_data: 4096\nmax_concurrent_sessions: 10");
+    config
+        .apply_env(env(&[
+            ("TLSN_VERIFIER_VERIFICATION_TIMEOUT_SECS", "60"),
+            ("TLSN_VERIFIER_MAX_RECV_DATA", "65536"),
+            ("TLSN_VERIFIER_MAX_CONCURRENT_SESSIONS", ""),
+            ("TLSN_VERIFIER_OVERSIZED_REGISTRATION", "clamp"),
+            ("TLSN_VERIFIER_MAX_SESSION_LIFETIME_SECS", "600"),
+            ("TLSN_VERIFIER_DRAIN_TIMEOUT_SECS", "30"),
+        ]))
+        .expect("valid overrides");
+
+    assert_eq!(config.verification_timeout_secs, 60);
+    assert_eq!(config.max_sent_data, Some(4096));
+    assert_eq!(config.max_recv_data, Some(65536));
+    assert_eq!(config.max_concurrent_sessions, None);
+    assert_eq!(config.oversized_registration, OversizedRegistration::Clamp);
+    assert_eq!(
+        config.max_session_lifetime(),
+        Some(Duration::from_secs(600))
+    );
+    assert_eq!(config.drain_timeout(), Duration::from_secs(30));
+
+    let err = config
+        .apply_env(env(&[("TLSN_VERIFIER_PROVER_CONNECT_TIMEOUT_SECS", "30s")]))
+        .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("TLSN_VERIFIER_PROVER_CONNECT_TIMEOUT_SECS"),
+        "{}",
+        err
+    );
+    assert!(config
+        .apply_env(env(&[("TLSN_VERIFIER_OVERSIZED_REGISTRATION", "truncate")]))
+        .is_err());
+}
+
+#[test]
+fn validation_rejects_zero_values() {
+    for yaml in [
+        "prover_connect_timeout_secs: 0",
+        "verification_timeout_secs: 0",
+        "max_sent_data: 0",
+        "max_concurrent_sessions: 0",
+        "max_session_lifetime_secs: 0",
+        "max_connections_per_session: 0",
+    ] {
+        let field = yaml.split(':').next().unwrap();
+        let err = limits(yaml).validate().unwrap_err();
+        assert!(err.to_string().contains(field), "{}: {}", yaml, err);
+    }
+
+    // Invalid limits stop the server from starting
+    let config = Config {
+        limits: limits("reveal_config_timeout_secs: 0"),
+        ..Config::default()
+    };
+    assert!(AppState::new(config).is_err());
+}
+
+#[test]
+fn oversized_registrations_are_rejected_or_clamped() {
+    let reject = limits("max_sent_data: 4096\nmax_recv_data: 16384");
+    assert_eq!(reject.admit_data_limits(4096, 16384), Ok((4096, 16384)));
+    let err = reject.admit_data_limits(8192, 1024).unwrap_err();
+    assert_eq!(err.code, ErrorCode::LimitExceeded);
+    assert_eq!(err.message, "maxSentData 8192 exceeds server limit 4096");
+    let details = err.details.unwrap();
+    assert_eq!(details.direction.as_deref(), Some("sent"));
+    assert_eq!((details.requested, details.limit), (Some(8192), Some(4096)));
+    assert!(reject.admit_data_limits(1024, 16385).is_err());
+
+    let clamp = limits("max_recv_data: 16384\noversized_registration: clamp");
+    assert_eq!(clamp.admit_data_limits(8192, 65536), Ok((8192, 16384)));
+    assert_eq!(clamp.admit_data_limits(8192, 1024), Ok((8192, 1024)));
+}
+
+#[test]
+fn registrations_are_limited_to_max_connections() {
+    let config = limits("max_connections_per_session: 2");
+    assert_eq!(config.admit_connections(1), Ok(()));
+    assert_eq!(config.admit_connections(2), Ok(()));
+
+    let err = config.admit_connections(3).unwrap_err();
+    assert_eq!(err.code, ErrorCode::LimitExceeded);
+    assert_eq!(err.message, "connections 3 exceeds server limit 2");
+    let details = err.details.unwrap();
+    assert_eq!((details.requested, details.limit), (Some(3), Some(2)));
+
+    let err = config.admit_connections(0).unwrap_err();
+    assert_eq!(err.code, ErrorCode::InvalidMessage);
+}
@@ -0,0 +1,87 @@
+//! Tests for the Prometheus metrics registry.
+
+use std::time::Duration;
+
+use crate::metrics::{Metrics, MAX_SERVER_NAME_LABELS, UNKNOWN_SERVER_NAME};
+
+#[test]
+fn renders_recorded_metrics() {
+    let metrics = Metrics::new().with_server_names(["api.example.com"]);
+    metrics.session_registered(Some("api.example.com"));
+    metrics.session_registered(None);
+    metrics.session_completed("api.example.com");
+    metrics.session_failed(Some("api.example.com"), "policy");
+    metrics.verifier_mode("proxy", Some("api.example.com"));
+    metrics.verification_duration(Some("api.example.com"), Duration::from_millis(1500));
+    metrics.prover_socket_wait(Duration::from_millis(200));
+    metrics.proxy_bytes("from_server", "api.example.com", 4096);
+    metrics.proxy_rejected("forbidden");
+    metrics.upstream_connect("verifier", "connected", Duration::from_millis(20));
+    metrics.dns_lookup("miss");
+    metrics.webhook_attempt("api.example.com", "failure", Duration::from_millis(30));
+    metrics.set_active_sessions(3);
+
+    let text = metrics.render().expect("render metrics");
+    for line in [
+        r#"tlsn_sessions_registered_total{server_name="api.example.com"} 1"#,
+        r#"tlsn_sessions_registered_total{server_name="unknown"} 1"#,
+        r#"tlsn_sessions_completed_total{server_name="api.example.com"} 1"#,
+        r#"tlsn_sessions_failed_total{reason="policy",server_name="api.example.com"} 1"#,
+        r#"tlsn_verifier_mode_total{mode="proxy",server_name="api.example.com"} 1"#,
+        r#"tlsn_verification_duration_seconds_count{server_name="api.example.com"} 1"#,
+        r#"tlsn_prover_socket_wait_seconds_count 1"#,
+        r#"tlsn_proxy_bytes_total{direction="from_server",server_name="api.example.com"} 4096"#,
+        r#"tlsn_proxy_rejections_total{reason="forbidden"} 1"#,
+        r#"tlsn_upstream_connections_total{outcome="connected",route="verifier"} 1"#,
+        r#"tlsn_upstream_connect_seconds_count{route="verifier"} 1"#,
+        r#"tlsn_dns_lookups_total{result="miss"} 1"#,
+        r#"tlsn_webhook_deliveries_total{outcome="failure",server_name="api.example.com"} 1"#,
+        r#"tlsn_webhook_latency_seconds_count{server_name="api.example.com"} 1"#,
+        "tlsn_active_sessions 3",
+    ] {
+        assert!(text.contains(line), "missing {:?} in:\n{}", line, text);
+    }
+}
+
+#[test]
+fn server_name_labels_are_capped() {
+    let metrics = Metrics::new();
+    for i in 0..MAX_SERVER_NAME_LABELS {
+        metrics.session_completed(&format!("host-{}.example.com", i));
+    }
+    metrics.session_completed("one-too-many.example.com");
+    metrics.session_completed("host-0.example.com");
+    metrics.session_failed(None, "prover_timeout");
+
+    let text = metrics.render().unwrap();
+    assert!(text.contains(r#"tlsn_sessions_completed_total{server_name="other"} 1"#));
+    assert!(text.contains(r#"tlsn_sessions_completed_total{server_name="host-0.example.com"} 2"#));
+    assert!(!text.contains("one-too-many"));
+    assert!(text.contains(&format!(
+        r#"tlsn_sessions_failed_total{{reason="prover_timeout",server_name="{}"}} 1"#,
+        UNKNOWN_SERVER_NAME
+    )));
+}
+
+#[test]
+fn unverified_server_names_are_not_labelled() {
+    let metrics = Metrics::new().with_server_names(["api.example.com"]);
+    metrics.session_registered(Some("api.example.com"));
+    metrics.session_registered(Some("junk-1.invalid"));
+    metrics.proxy_bytes("to_server", "junk-2.invalid", 10);
+    metrics.session_failed(Some("junk-3.invalid"), "policy");
+
+    let text = metrics.render().unwrap();
+    assert!(text.contains(r#"tlsn_sessions_registered_total{server_name="api.example.com"} 1"#));
+    assert!(text.contains(r#"tlsn_sessions_registered_total{server_name="unknown"} 1"#));
+    assert!(
+        text.contains(r#"tlsn_proxy_bytes_total{direction="to_server",server_name="unknown"} 10"#)
+    );
+    assert!(!text.contains("junk"), "{}", text);
+
+    // A name gets its own label once a session verifies it
+    metrics.verification_duration(Some("www.example.org"), Duration::from_secs(1));
+    metrics.session_registered(Some("www.example.org"));
+    let text = metrics.render().unwrap();
+    assert!(text.contains(r#"tlsn_sessions_registered_total{server_name="www.example.org"} 1"#));
+}
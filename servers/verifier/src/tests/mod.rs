@@ -1 +1,47 @@
+mod admin_test;
+mod assertion_test;
+mod attestation_test;
+mod auth_test;
+mod commitment_test;
+mod connections_test;
+mod connector_test;
+mod egress_test;
+mod error_test;
+mod handler_params_test;
+mod http_layout_test;
+mod info_test;
 mod integration_test;
+mod limits_test;
+mod metrics_test;
+mod opening_test;
+mod policy_test;
+mod progress_test;
+mod proxy_guard_test;
+mod resume_test;
+mod shutdown_test;
+mod store_test;
+mod trust_test;
+mod webhook_test;
+
+use std::sync::Arc;
+
+use crate::{AppState, Config, SessionConfig};
+
+/// Application state built from a `config.yaml` document.
+fn app_state(yaml: &str) -> Arc<AppState> {
+    let config: Config = serde_yaml_ng::from_str(yaml).expect("valid config");
+    Arc::new(AppState::new(config).expect("valid state"))
+}
+
+/// Session with `connections` TLS connections and no declared server.
+fn session_config(connections: usize) -> SessionConfig {
+    SessionConfig {
+        max_recv_data: 16384,
+        max_sent_data: 4096,
+        registered_at: 0,
+        server_name: None,
+        server_port: None,
+        tenant: None,
+        connections,
+    }
+}
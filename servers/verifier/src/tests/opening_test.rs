@@ -0,0 +1,109 @@
+//! Tests for openings of hash commitments.
+
+use axum::http::StatusCode;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+use crate::commitment::{CommitmentInfo, CommitmentKind};
+use crate::opening::{self, OpeningOutcome, OpeningRequest};
+
+const BLINDER: [u8; 16] = [7; 16];
+
+fn commitment(plaintext: &str) -> CommitmentInfo {
+    let digest = Sha256::new()
+        .chain_update(plaintext)
+        .chain_update(BLINDER)
+        .finalize();
+    CommitmentInfo {
+        kind: CommitmentKind::Hash,
+        connection: 0,
+        direction: Some("recv".to_string()),
+        ranges: vec![[120, 125]],
+        algorithm: "SHA256".to_string(),
+        value: hex::encode(digest),
+    }
+}
+
+fn request(plaintext: &str, blinder: &str) -> OpeningRequest {
+    serde_json::from_value(json!({
+        "direction": "recv",
+        "start": 120,
+        "end": 125,
+        "plaintext": plaintext,
+        "blinder": blinder,
+    }))
+    .unwrap()
+}
+
+#[test]
+fn openings_are_checked_against_the_committed_digest() {
+    let commitments = [commitment("94103")];
+    let blinder = hex::encode(BLINDER);
+
+    assert_eq!(
+        opening::verify(&commitments, &request("94103", &blinder)).unwrap(),
+        OpeningOutcome {
+            valid: true,
+            algorithm: "SHA256".to_string(),
+            digest: commitments[0].value.clone(),
+        }
+    );
+
+    // A different plaintext or blinder does not open the commitment
+    assert!(
+        !opening::verify(&commitments, &request("94104", &blinder))
+            .unwrap()
+            .valid
+    );
+    let other_blinder = hex::encode([8u8; 16]);
+    assert!(
+        !opening::verify(&commitments, &request("94103", &other_blinder))
+            .unwrap()
+            .valid
+    );
+}
+
+#[test]
+fn openings_need_a_commitment_to_exactly_their_range() {
+    let commitments = [commitment("94103")];
+    let blinder = hex::encode(BLINDER);
+
+    let mut wider = request("94103 ", &blinder);
+    wider.end = 126;
+    let (status, _) = opening::verify(&commitments, &wider).unwrap_err();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let mut sent = request("94103", &blinder);
+    sent.direction = "sent".to_string();
+    let (status, _) = opening::verify(&commitments, &sent).unwrap_err();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let mut other_connection = request("94103", &blinder);
+    other_connection.connection = 1;
+    let (status, _) = opening::verify(&commitments, &other_connection).unwrap_err();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn blinders_must_be_sixteen_hex_bytes() {
+    let commitments = [commitment("94103")];
+    for blinder in ["not hex", "0707", &"07".repeat(17)] {
+        let (status, message) =
+            opening::verify(&commitments, &request("94103", blinder)).unwrap_err();
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(message, "blinder must be 16 hex-encoded bytes");
+    }
+}
+
+#[test]
+fn blinded_digests_follow_the_commitment_algorithm() {
+    let sha256 = opening::blinded_digest("SHA256", b"94103", &BLINDER).unwrap();
+    let keccak = opening::blinded_digest("KECCAK256", b"94103", &BLINDER).unwrap();
+    let blake = opening::blinded_digest("BLAKE3", b"94103", &BLINDER).unwrap();
+    assert_eq!(sha256.len(), 32);
+    assert_ne!(sha256, keccak);
+    assert_ne!(sha256, blake);
+    let preimage = [b"94103".as_slice(), &BLINDER].concat();
+    assert_eq!(blake, blake3::hash(&preimage).as_bytes());
+    assert!(opening::blinded_digest("MD5", b"94103", &BLINDER).is_none());
+}
@@ -0,0 +1,263 @@
+//! Tests for per-server-name verification policies.
+
+use std::collections::HashMap;
+
+use crate::policy::PolicyConfig;
+use crate::{
+    match_server_name, Handler, HandlerAction, HandlerPart, HandlerType, HashAlgorithm,
+    RangeWithHandler, RevealConfig,
+};
+
+const SENT: &[u8] = b"GET /api HTTP/1.1\r\nHost: api.example.com\r\nCookie: secret\r\n\r\n";
+const RECV: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{}";
+
+fn policy(yaml: &str) -> PolicyConfig {
+    serde_yaml_ng::from_str(yaml).expect("valid policy")
+}
+
+fn range(
+    handler_type: HandlerType,
+    part: HandlerPart,
+    action: HandlerAction,
+    start: usize,
+    end: usize,
+) -> RangeWithHandler {
+    RangeWithHandler {
+        start,
+        end,
+        handler: Handler {
+            handler_type,
+            part,
+            action,
+            params: None,
+        },
+        connection: 0,
+    }
+}
+
+/// Reveals the start line, the Host header and the status code.
+fn reveal_config() -> RevealConfig {
+    RevealConfig {
+        sent: vec![
+            range(
+                HandlerType::Sent,
+                HandlerPart::StartLine,
+                HandlerAction::Reveal,
+                0,
+                17,
+            ),
+            range(
+                HandlerType::Sent,
+                HandlerPart::Headers,
+                HandlerAction::Reveal,
+                19,
+                42,
+            ),
+            range(
+                HandlerType::Sent,
+                HandlerPart::Headers,
+                HandlerAction::Hash {
+                    algorithm: HashAlgorithm::Sha256,
+                },
+                42,
+                56,
+            ),
+        ],
+        recv: vec![range(
+            HandlerType::Recv,
+            HandlerPart::StatusCode,
+            HandlerAction::Reveal,
+            9,
+            12,
+        )],
+    }
+}
+
+/// Checks a reveal config whose REVEAL ranges are the revealed transcript
+/// bytes, as in the prover's proof.
+fn check(policy: &PolicyConfig, reveal_config: &RevealConfig) -> Result<(), String> {
+    let revealed = |ranges: &[RangeWithHandler], i: usize| {
+        ranges.iter().any(|range| {
+            matches!(range.handler.action, HandlerAction::Reveal)
+                && (range.start..range.end).contains(&i)
+        })
+    };
+    policy.check_reveal(
+        (SENT, |i| revealed(&reveal_config.sent, i)),
+        (RECV, |i| revealed(&reveal_config.recv, i)),
+    )
+}
+
+#[test]
+fn server_name_lookup_falls_back_to_catch_all() {
+    let entries = HashMap::from([("api.example.com".to_string(), 1), ("*".to_string(), 2)]);
+    assert_eq!(match_server_name(&entries, "api.example.com"), Some(&1));
+    assert_eq!(match_server_name(&entries, "www.example.com"), Some(&2));
+
+    let no_fallback = HashMap::from([("api.example.com".to_string(), 1)]);
+    assert_eq!(match_server_name(&no_fallback, "other.org"), None);
+}
+
+#[test]
+fn deny_and_limits() {
+    let denied = policy("allow: false");
+    assert!(denied.check_allowed("api.example.com").is_err());
+
+    let capped = policy("max_sent_data: 1024\nmax_recv_data: 4096");
+    assert!(capped.check_allowed("api.example.com").is_ok());
+    assert!(capped.check_limits(1024, 4096).is_ok());
+    assert!(capped.check_limits(1025, 4096).is_err());
+    assert!(capped.check_limits(1024, 4097).is_err());
+}
+
+#[test]
+fn required_and_forbidden_reveals() {
+    let satisfied = policy(
+        r#"
+require_reveal:
+  - { type: RECV, part: STATUS_CODE }
+  - { type: SENT, part: HEADERS, header: host }
+forbid_reveal:
+  - { type: SENT, part: HEADERS, header: Cookie }
+"#,
+    );
+    check(&satisfied, &reveal_config()).expect("reveal config satisfies policy");
+
+    let missing = policy("require_reveal: [{ type: RECV, part: BODY }]");
+    let err = check(&missing, &reveal_config()).unwrap_err();
+    assert!(err.contains("RECV BODY must be revealed"), "{}", err);
+
+    // Revealing the whole request also reveals the Cookie header
+    let mut reveal_all = reveal_config();
+    reveal_all.sent = vec![range(
+        HandlerType::Sent,
+        HandlerPart::All,
+        HandlerAction::Reveal,
+        0,
+        SENT.len(),
+    )];
+    let forbidden = policy("forbid_reveal: [{ type: SENT, part: HEADERS, header: cookie }]");
+    assert!(check(&forbidden, &reveal_all).is_err());
+}
+
+#[test]
+fn hash_algorithms_of_commitments() {
+    let sha256 = ["SHA256".to_string()];
+    policy("{}")
+        .check_hash_algorithms(&sha256)
+        .expect("any algorithm without a rule");
+    policy("hash_algorithms: [SHA256, BLAKE3]")
+        .check_hash_algorithms(&sha256)
+        .expect("SHA256 is allowed");
+
+    let blake3_only = policy("hash_algorithms: [BLAKE3]");
+    let err = blake3_only.check_hash_algorithms(&sha256).unwrap_err();
+    assert!(err.contains("SHA256"), "{}", err);
+    blake3_only
+        .check_hash_algorithms(&[])
+        .expect("no hash commitments");
+}
+
+#[test]
+fn forbidden_parts_are_found_whatever_the_label() {
+    let no_cookie = policy("forbid_reveal: [{ type: SENT, part: HEADERS, header: cookie }]");
+
+    // The Cookie line passed off as the request body
+    let mut mislabelled = reveal_config();
+    mislabelled.sent[2] = range(
+        HandlerType::Sent,
+        HandlerPart::Body,
+        HandlerAction::Reveal,
+        42,
+        56,
+    );
+    let err = check(&no_cookie, &mislabelled).unwrap_err();
+    assert!(
+        err.contains("SENT HEADERS (cookie) must not be revealed (bytes [42, 56))"),
+        "{}",
+        err
+    );
+
+    // Only the value revealed, with the header name hidden
+    let mut value_only = reveal_config();
+    value_only.sent[2] = range(
+        HandlerType::Sent,
+        HandlerPart::Headers,
+        HandlerAction::Reveal,
+        50,
+        56,
+    );
+    let err = check(&no_cookie, &value_only).unwrap_err();
+    assert!(err.contains("bytes [50, 56)"), "{}", err);
+
+    // The response body revealed under a HEADERS label
+    let no_body = policy("forbid_reveal: [{ type: RECV, part: BODY }]");
+    let mut body_as_headers = reveal_config();
+    body_as_headers.recv = vec![range(
+        HandlerType::Recv,
+        HandlerPart::Headers,
+        HandlerAction::Reveal,
+        0,
+        RECV.len(),
+    )];
+    let err = check(&no_body, &body_as_headers).unwrap_err();
+    assert!(
+        err.contains("RECV BODY must not be revealed (bytes [38, 40))"),
+        "{}",
+        err
+    );
+    check(&no_body, &reveal_config()).expect("body is not revealed");
+}
+
+#[test]
+fn required_parts_must_be_revealed_in_full() {
+    let status = policy("require_reveal: [{ type: RECV, part: STATUS_CODE }]");
+    let recv_only = |ranges: Vec<RangeWithHandler>| RevealConfig {
+        recv: ranges,
+        ..reveal_config()
+    };
+
+    // One byte labelled ALL or STATUS_CODE leaves the status code hidden
+    for part in [HandlerPart::All, HandlerPart::StatusCode] {
+        let one_byte = recv_only(vec![range(
+            HandlerType::Recv,
+            part,
+            HandlerAction::Reveal,
+            9,
+            10,
+        )]);
+        let err = check(&status, &one_byte).unwrap_err();
+        assert!(err.contains("RECV STATUS_CODE must be revealed"), "{}", err);
+    }
+
+    // The status code revealed under another label still counts
+    let as_all = recv_only(vec![range(
+        HandlerType::Recv,
+        HandlerPart::All,
+        HandlerAction::Reveal,
+        0,
+        RECV.len(),
+    )]);
+    check(&status, &as_all).expect("status code is revealed");
+
+    // A header needs its whole line, name included
+    let host = policy("require_reveal: [{ type: SENT, part: HEADERS, header: Host }]");
+    let mut value_only = reveal_config();
+    value_only.sent[1] = range(
+        HandlerType::Sent,
+        HandlerPart::Headers,
+        HandlerAction::Reveal,
+        25,
+        42,
+    );
+    assert!(check(&host, &value_only).is_err());
+    let mut no_line_break = reveal_config();
+    no_line_break.sent[1] = range(
+        HandlerType::Sent,
+        HandlerPart::Headers,
+        HandlerAction::Reveal,
+        19,
+        40,
+    );
+    assert!(check(&host, &no_line_break).is_err());
+}
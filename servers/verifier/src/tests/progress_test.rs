@@ -0,0 +1,97 @@
+//! Tests for session progress events.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde_json::json;
+use tokio::sync::oneshot;
+use tokio::time::timeout;
+
+use crate::progress::{Phase, ProgressEvent};
+use crate::resume::{ResumableSessions, SessionLink};
+
+fn phases(events: &[ProgressEvent]) -> Vec<Phase> {
+    events.iter().map(|event| event.phase).collect()
+}
+
+#[test]
+fn events_serialize_flat() {
+    let event = ProgressEvent {
+        phase: Phase::ModeSelected { mode: "Mpc" },
+        connection: Some(1),
+        timestamp_ms: 1_700_000_000_000,
+        elapsed_ms: 1500,
+    };
+    assert_eq!(
+        serde_json::to_value(&event).unwrap(),
+        json!({
+            "phase": "mode_selected",
+            "mode": "Mpc",
+            "connection": 1,
+            "timestampMs": 1_700_000_000_000u64,
+            "elapsedMs": 1500,
+        })
+    );
+
+    let event = ProgressEvent::new(Phase::WebhookDispatched, None, Instant::now());
+    let value = serde_json::to_value(&event).unwrap();
+    assert_eq!(value["phase"], "webhook_dispatched");
+    assert!(value.get("mode").is_none());
+    assert!(value.get("connection").is_none());
+}
+
+#[tokio::test]
+async fn sockets_receive_each_event_once() {
+    let sessions = ResumableSessions::default();
+    let (reveal_config_tx, _rx) = oneshot::channel();
+    let link = Arc::new(SessionLink::new(None, reveal_config_tx));
+    sessions.insert("s1", link.clone());
+
+    let mut attachment = link.attach();
+    assert!(attachment.unsent_progress().is_empty());
+
+    sessions.report("s1", Phase::ProverConnected);
+    sessions.report("s1", Phase::ModeSelected { mode: "Proxy" });
+    // Unknown sessions are ignored
+    sessions.report("s2", Phase::TlsComplete);
+
+    let events = timeout(Duration::from_secs(1), attachment.progress())
+        .await
+        .expect("events are pending");
+    assert_eq!(
+        phases(&events),
+        [
+            Phase::ProverConnected,
+            Phase::ModeSelected { mode: "Proxy" }
+        ]
+    );
+    assert!(events[0].elapsed_ms <= events[1].elapsed_ms);
+
+    // Nothing new until the next phase is reported
+    assert!(timeout(Duration::from_millis(50), attachment.progress())
+        .await
+        .is_err());
+    sessions.report_connection("s1", 1, Phase::CommitmentAccepted);
+    let events = attachment.unsent_progress();
+    assert_eq!(phases(&events), [Phase::CommitmentAccepted]);
+    assert_eq!(events[0].connection, Some(1));
+}
+
+#[tokio::test]
+async fn resumed_sockets_catch_up_on_missed_events() {
+    let (reveal_config_tx, _rx) = oneshot::channel();
+    let link = SessionLink::new(None, reveal_config_tx);
+
+    let mut first = link.attach();
+    link.report(Phase::ProverConnected);
+    assert_eq!(phases(&first.progress().await), [Phase::ProverConnected]);
+
+    // Reported while no socket is attached
+    link.report(Phase::ModeSelected { mode: "Mpc" });
+
+    let mut resumed = link.attach();
+    assert_eq!(
+        phases(&resumed.progress().await),
+        [Phase::ProverConnected, Phase::ModeSelected { mode: "Mpc" }]
+    );
+}
@@ -0,0 +1,277 @@
+//! Tests for /proxy target rules and per-client limits.
+
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+
+use crate::error::ErrorCode;
+use crate::proxy_guard::{
+    is_non_public, parse_target, ProxyClients, ProxyConfig, ProxyRejection, TargetRule,
+};
+use crate::{check_server_port, connect_proxy_server, AppState, Config};
+
+fn proxy(yaml: &str) -> ProxyConfig {
+    serde_yaml_ng::from_str(yaml).expect("valid proxy config")
+}
+
+fn addrs(ips: &[&str]) -> Vec<SocketAddr> {
+    ips.iter()
+        .map(|ip| SocketAddr::new(ip.parse().unwrap(), 443))
+        .collect()
+}
+
+fn ip(ip: &str) -> IpAddr {
+    ip.parse().unwrap()
+}
+
+#[test]
+fn parses_targets_and_rules() {
+    assert_eq!(
+        parse_target("api.example.com"),
+        Ok(("api.example.com".to_string(), 443))
+    );
+    assert_eq!(
+        parse_target("API.example.com.:8443"),
+        Ok(("api.example.com".to_string(), 8443))
+    );
+    assert_eq!(parse_target("[::1]:8080"), Ok(("::1".to_string(), 8080)));
+    assert_eq!(
+        parse_target("2001:db8::1"),
+        Ok(("2001:db8::1".to_string(), 443))
+    );
+    for invalid in [
+        "",
+        ":443",
+        "host:0",
+        "host:https",
+        "[::1",
+        "[::1]443",
+        "a:b:c",
+    ] {
+        assert!(
+            matches!(parse_target(invalid), Err(ProxyRejection::InvalidTarget(_))),
+            "{:?}",
+            invalid
+        );
+    }
+
+    assert_eq!(
+        TargetRule::try_from("10.0.0.0/8".to_string()),
+        Ok(TargetRule::Net {
+            addr: ip("10.0.0.0"),
+            prefix: 8
+        })
+    );
+    assert_eq!(
+        TargetRule::try_from("::1".to_string()),
+        Ok(TargetRule::Net {
+            addr: ip("::1"),
+            prefix: 128
+        })
+    );
+    assert_eq!(
+        TargetRule::try_from("*.Example.com".to_string()),
+        Ok(TargetRule::Host("*.example.com".to_string()))
+    );
+    assert!(TargetRule::try_from("10.0.0.0/33".to_string()).is_err());
+    assert!(TargetRule::try_from("example.com/8".to_string()).is_err());
+}
+
+#[test]
+fn blocks_non_public_addresses_by_default() {
+    for blocked in [
+        "127.0.0.1",
+        "10.1.2.3",
+        "172.16.0.1",
+        "192.168.1.1",
+        "169.254.169.254",
+        "100.64.0.1",
+        "0.0.0.0",
+        "::1",
+        "fd00::1",
+        "fe80::1",
+        "::ffff:127.0.0.1",
+    ] {
+        assert!(is_non_public(ip(blocked)), "{}", blocked);
+    }
+    assert!(!is_non_public(ip("93.184.216.34")));
+    assert!(!is_non_public(ip("2606:4700::1111")));
+
+    let config = ProxyConfig::default();
+    let err = config
+        .check_addrs("internal.example.com", &addrs(&["10.0.0.5"]))
+        .unwrap_err();
+    assert_eq!(err.reason(), "forbidden");
+    assert_eq!(err.into_http_error().0, StatusCode::FORBIDDEN);
+
+    // Only the public addresses of a mixed resolution are used
+    assert_eq!(
+        config.check_addrs("example.com", &addrs(&["127.0.0.1", "93.184.216.34"])),
+        Ok(addrs(&["93.184.216.34"]))
+    );
+
+    // An allow CIDR opts a private range back in
+    let config = proxy("allow: [\"10.0.0.0/24\"]");
+    assert!(config.check_addrs("db", &addrs(&["10.0.0.5"])).is_ok());
+    assert!(config.check_addrs("db", &addrs(&["10.0.1.5"])).is_err());
+}
+
+#[test]
+fn applies_allow_deny_and_port_rules() {
+    let config = proxy(
+        r#"
+allow: ["api.example.com", "*.github.com", "203.0.113.0/24"]
+deny: ["gist.github.com", "203.0.113.66"]
+ports: [443, 8443]
+"#,
+    );
+
+    assert!(config.check_target("api.example.com", 443).is_ok());
+    assert!(config.check_target("api.example.com", 22).is_err());
+    assert!(config.check_target("gist.github.com", 443).is_err());
+
+    let public = addrs(&["93.184.216.34"]);
+    assert!(config.check_addrs("api.example.com", &public).is_ok());
+    assert!(config.check_addrs("raw.github.com", &public).is_ok());
+    // "*.github.com" does not cover the apex, nor unrelated hosts
+    assert!(config.check_addrs("github.com", &public).is_err());
+    assert!(config.check_addrs("evil.com", &public).is_err());
+    // Allowed by address, unless that address is denied
+    assert!(config
+        .check_addrs("evil.com", &addrs(&["203.0.113.7"]))
+        .is_ok());
+    assert!(config
+        .check_addrs("api.example.com", &addrs(&["203.0.113.66"]))
+        .is_err());
+}
+
+#[test]
+fn unresolved_names_need_a_host_rule() {
+    let config = proxy(r#"allow: ["api.example.com", "*.github.com", "203.0.113.0/24"]"#);
+    assert!(config.check_name("api.example.com").is_ok());
+    assert!(config.check_name("raw.github.com").is_ok());
+    // An allow CIDR cannot vouch for a name that is never resolved here
+    assert!(config.check_name("evil.com").is_err());
+
+    let config = ProxyConfig::default();
+    assert!(config.check_name("example.com").is_ok());
+    for local in [
+        "localhost",
+        "LOCALHOST.",
+        "db.localhost",
+        "metadata.google.internal",
+        "nas.home.arpa",
+        "intranet",
+    ] {
+        assert!(config.check_name(local).is_err(), "{}", local);
+    }
+    assert!(proxy("block_private: false")
+        .check_name("localhost")
+        .is_ok());
+    assert!(proxy(r#"allow: ["api.internal"]"#)
+        .check_name("api.internal")
+        .is_ok());
+}
+
+#[test]
+fn client_ip_prefers_configured_header() {
+    let peer = Some(SocketAddr::new(ip("10.0.0.2"), 50000));
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "x-forwarded-for",
+        HeaderValue::from_static("198.51.100.7, 10.0.0.2"),
+    );
+
+    // Headers are ignored unless configured, as clients can forge them
+    assert_eq!(
+        ProxyConfig::default().client_ip(&headers, peer),
+        Some(ip("10.0.0.2"))
+    );
+    let config = proxy("client_ip_header: x-forwarded-for");
+    assert_eq!(config.client_ip(&headers, peer), Some(ip("198.51.100.7")));
+    assert_eq!(config.client_ip(&HeaderMap::new(), None), None);
+
+    assert!(proxy("idle_timeout_secs: 0").validate().is_err());
+    assert!(proxy("max_connections_per_ip: 0").validate().is_err());
+    assert!(ProxyConfig::default().validate().is_ok());
+}
+
+#[test]
+fn limits_connections_and_bandwidth_per_ip() {
+    let clients = Arc::new(ProxyClients::default());
+    let client = ip("198.51.100.7");
+
+    let first = clients.acquire(client, Some(2)).unwrap();
+    let second = clients.acquire(client, Some(2)).unwrap();
+    let err = clients.acquire(client, Some(2)).unwrap_err();
+    assert_eq!(err.into_http_error().0, StatusCode::TOO_MANY_REQUESTS);
+    // Other clients are unaffected
+    assert!(clients.acquire(ip("198.51.100.8"), Some(2)).is_ok());
+
+    drop(first);
+    assert_eq!(clients.connections(client), 1);
+    let _third = clients.acquire(client, Some(2)).unwrap();
+    drop(second);
+
+    // 1000 B/s with a one-second burst: the first 1000 bytes pass at once,
+    // the next 500 wait half a second
+    let now = Instant::now();
+    assert_eq!(clients.reserve(client, 1000, 1000, now), Duration::ZERO);
+    assert_eq!(
+        clients.reserve(client, 1000, 500, now),
+        Duration::from_millis(500)
+    );
+    // Quota recovers as time passes
+    assert_eq!(
+        clients.reserve(client, 1000, 100, now + Duration::from_secs(2)),
+        Duration::ZERO
+    );
+}
+
+#[tokio::test]
+async fn proxy_mode_dials_declared_ports_and_upstreams() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let local = listener.local_addr().unwrap();
+    let config: Config = serde_yaml_ng::from_str(&format!(
+        r#"
+proxy:
+  upstreams:
+    "api.test": "{}"
+policies:
+  "api.test":
+    ports: [8443]
+"#,
+        local
+    ))
+    .unwrap();
+    let state = AppState::new(config).unwrap();
+
+    // The upstream stands in for the server on the declared port
+    connect_proxy_server(&state, "api.test", 8443)
+        .await
+        .unwrap();
+    let error = connect_proxy_server(&state, "api.test", 443)
+        .await
+        .unwrap_err();
+    assert_eq!(error.code, ErrorCode::PolicyDenied);
+
+    // Without an upstream, targets are checked like on the /proxy bridge
+    let error = connect_proxy_server(&state, "127.0.0.1", local.port())
+        .await
+        .unwrap_err();
+    assert_eq!(error.code, ErrorCode::PolicyDenied);
+
+    assert!(proxy("upstreams: { \"api.test\": \"host:0\" }")
+        .validate()
+        .is_err());
+}
+
+#[test]
+fn server_ports_need_a_server_name() {
+    assert_eq!(check_server_port(Some("api.test"), Some(8443)), Ok(()));
+    assert_eq!(check_server_port(None, None), Ok(()));
+    assert!(check_server_port(None, Some(8443)).is_err());
+    assert!(check_server_port(Some("api.test"), Some(0)).is_err());
+}
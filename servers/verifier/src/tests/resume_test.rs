@@ -0,0 +1,111 @@
+//! Tests for resuming dropped session sockets.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::oneshot;
+use tokio::time::timeout;
+
+use crate::error::{ErrorCode, SessionError};
+use crate::resume::{ResumableSessions, SessionLink};
+use crate::{RevealConfig, VerificationResult};
+
+fn link(tenant: Option<&str>) -> (Arc<SessionLink>, oneshot::Receiver<RevealConfig>) {
+    let (reveal_config_tx, reveal_config_rx) = oneshot::channel();
+    let link = SessionLink::new(tenant.map(str::to_string), reveal_config_tx);
+    (Arc::new(link), reveal_config_rx)
+}
+
+fn failed(code: ErrorCode, error: &str) -> VerificationResult {
+    VerificationResult {
+        results: vec![],
+        assertions: vec![],
+        commitments: vec![],
+        attestation: None,
+        error: Some(SessionError::new(code, error)),
+    }
+}
+
+#[test]
+fn resume_requires_token_and_tenant() {
+    let sessions = ResumableSessions::default();
+    let (acme, _rx) = link(Some("acme"));
+    let token = acme.token().to_string();
+    sessions.insert("s1", acme.clone());
+
+    let resumed = sessions.resume("s1", &token, Some("acme")).unwrap();
+    assert!(Arc::ptr_eq(&resumed, &acme));
+
+    assert!(sessions
+        .resume("s1", "not-the-token", Some("acme"))
+        .is_none());
+    assert!(sessions.resume("s1", &token, Some("globex")).is_none());
+    assert!(sessions.resume("s1", &token, None).is_none());
+    assert!(sessions.resume("s2", &token, Some("acme")).is_none());
+
+    // Tokens are unique per session
+    let (other, _rx) = link(Some("acme"));
+    assert_ne!(other.token(), token);
+
+    sessions.remove("s1");
+    assert!(sessions.resume("s1", &token, Some("acme")).is_none());
+}
+
+#[tokio::test]
+async fn reveal_config_is_forwarded_once() {
+    let (link, reveal_config_rx) = link(None);
+    assert!(link.awaiting_reveal_config());
+
+    let reveal_config_tx = link.take_reveal_config_tx().unwrap();
+    assert!(!link.awaiting_reveal_config());
+    assert!(link.take_reveal_config_tx().is_none());
+
+    reveal_config_tx
+        .send(RevealConfig {
+            sent: vec![],
+            recv: vec![],
+        })
+        .unwrap();
+    assert!(reveal_config_rx.await.is_ok());
+}
+
+#[tokio::test]
+async fn result_is_buffered_for_later_sockets() {
+    let (link, reveal_config_rx) = link(None);
+    assert!(timeout(Duration::from_millis(20), link.result())
+        .await
+        .is_err());
+
+    link.complete(failed(
+        ErrorCode::TimeoutProverConnect,
+        "Prover connection timeout",
+    ));
+    // A finished session no longer takes a reveal config
+    assert!(!link.awaiting_reveal_config());
+    assert!(reveal_config_rx.await.is_err());
+
+    // Every socket attaching afterwards gets the result
+    for _ in 0..2 {
+        let result = link.result().await;
+        let error = result.error.expect("failed session");
+        assert_eq!(error.code, ErrorCode::TimeoutProverConnect);
+        assert_eq!(error.message, "Prover connection timeout");
+    }
+}
+
+#[tokio::test]
+async fn attaching_supersedes_earlier_sockets() {
+    let (link, _rx) = link(None);
+    let mut first = link.attach();
+    assert!(timeout(Duration::from_millis(20), first.superseded())
+        .await
+        .is_err());
+
+    let mut second = link.attach();
+    timeout(Duration::from_secs(1), first.superseded())
+        .await
+        .expect("first socket is superseded");
+    assert!(timeout(Duration::from_millis(20), second.superseded())
+        .await
+        .is_err());
+}
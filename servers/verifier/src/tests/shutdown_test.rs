@@ -0,0 +1,61 @@
+//! Tests for graceful shutdown and session draining.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse};
+use tokio::time::timeout;
+
+use crate::shutdown::{Shutdown, DRAINING_MESSAGE};
+use crate::{check_session_quotas, health_handler, AppState, Config};
+
+#[tokio::test]
+async fn drained_waits_for_tracked_sessions() {
+    let shutdown = Arc::new(Shutdown::default());
+    // Nothing in flight drains at once
+    timeout(Duration::from_secs(1), shutdown.drained())
+        .await
+        .expect("idle server drains immediately");
+
+    let first = shutdown.track();
+    let second = shutdown.track();
+    assert_eq!(shutdown.active(), 2);
+    assert!(timeout(Duration::from_millis(50), shutdown.drained())
+        .await
+        .is_err());
+
+    let waiter = tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move { shutdown.drained().await }
+    });
+    drop(first);
+    drop(second);
+    timeout(Duration::from_secs(1), waiter)
+        .await
+        .expect("drained once the last session ends")
+        .unwrap();
+    assert_eq!(shutdown.active(), 0);
+}
+
+#[tokio::test]
+async fn draining_refuses_new_sessions() {
+    let state = Arc::new(AppState::new(Config::default()).unwrap());
+    let health = || async {
+        health_handler(State(state.clone()))
+            .await
+            .into_response()
+            .status()
+    };
+
+    assert_eq!(health().await, StatusCode::OK);
+    assert!(check_session_quotas(&state, &HashMap::new(), None).is_ok());
+
+    assert!(state.shutdown.start_draining());
+    assert!(!state.shutdown.start_draining());
+
+    assert_eq!(health().await, StatusCode::SERVICE_UNAVAILABLE);
+    let (reason, msg) = check_session_quotas(&state, &HashMap::new(), None).unwrap_err();
+    assert_eq!(reason, "draining");
+    assert_eq!(msg, DRAINING_MESSAGE);
+}
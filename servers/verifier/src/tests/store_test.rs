@@ -0,0 +1,245 @@
+//! Tests for the persistent session result store.
+
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::sync::Arc;
+
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::response::IntoResponse;
+use serde_json::{json, Value};
+use tempfile::TempDir;
+
+use crate::auth::API_KEY_HEADER;
+use crate::store::{ResultStore, SessionQuery, StoreBackend, StoreConfig, StoredSession};
+use crate::{get_session_handler, list_sessions_handler, AppState, Config};
+
+fn session(id: &str, server_name: &str, completed_at: u64) -> StoredSession {
+    StoredSession {
+        id: id.to_string(),
+        tenant: None,
+        server_name: Some(server_name.to_string()),
+        results: vec![],
+        assertions: vec![],
+        commitments: vec![],
+        transcript: None,
+        connections: vec![],
+        session_data: HashMap::from([("user".to_string(), id.to_string())]),
+        registered_at: completed_at - 1,
+        completed_at,
+        attestation: None,
+        error: None,
+        error_code: None,
+    }
+}
+
+/// A store whose files are deleted when it is dropped
+struct TestStore {
+    store: ResultStore,
+    // Declared after `store` so it is removed once the store is closed
+    _dir: Option<TempDir>,
+}
+
+impl Deref for TestStore {
+    type Target = ResultStore;
+
+    fn deref(&self) -> &ResultStore {
+        &self.store
+    }
+}
+
+fn open(backend: StoreBackend) -> TestStore {
+    let (path, dir) = match backend {
+        StoreBackend::Sqlite => (":memory:".into(), None),
+        StoreBackend::Sled => {
+            let dir = TempDir::new().expect("temp dir");
+            (dir.path().join("store"), Some(dir))
+        }
+    };
+    let store = ResultStore::open(&StoreConfig { backend, path }).expect("store opens");
+    TestStore { store, _dir: dir }
+}
+
+#[test]
+fn put_get_and_list() {
+    for backend in [StoreBackend::Sqlite, StoreBackend::Sled] {
+        let store = open(backend);
+        store.put(&session("a", "api.x.com", 100)).unwrap();
+        store.put(&session("b", "api.github.com", 200)).unwrap();
+        store.put(&session("c", "api.x.com", 300)).unwrap();
+
+        let stored = store.get("b").unwrap().expect("session b is stored");
+        assert_eq!(stored.server_name.as_deref(), Some("api.github.com"));
+        assert_eq!(stored.session_data["user"], "b");
+        assert!(store.get("missing").unwrap().is_none());
+
+        // Newest first, filtered by server name
+        let by_host = store
+            .list(&SessionQuery {
+                server_name: Some("api.x.com".to_string()),
+                limit: 10,
+                ..Default::default()
+            })
+            .unwrap();
+        let ids: Vec<_> = by_host.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, ["c", "a"], "{:?}", backend);
+
+        // `since` is inclusive; pagination applies after filtering
+        let page = store
+            .list(&SessionQuery {
+                since: Some(200),
+                limit: 1,
+                offset: 1,
+                ..Default::default()
+            })
+            .unwrap();
+        let ids: Vec<_> = page.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, ["b"], "{:?}", backend);
+    }
+}
+
+#[test]
+fn put_replaces_existing_record() {
+    for backend in [StoreBackend::Sqlite, StoreBackend::Sled] {
+        let store = open(backend);
+        store.put(&session("a", "api.x.com", 100)).unwrap();
+
+        let mut updated = session("a", "api.x.com", 150);
+        updated.error = Some("Verification failed".to_string());
+        store.put(&updated).unwrap();
+
+        let stored = store.get("a").unwrap().unwrap();
+        assert_eq!(stored.completed_at, 150);
+        assert_eq!(stored.error.as_deref(), Some("Verification failed"));
+    }
+}
+
+#[test]
+fn list_is_scoped_by_tenant() {
+    for backend in [StoreBackend::Sqlite, StoreBackend::Sled] {
+        let store = open(backend);
+        for (id, tenant) in [("a", Some("acme")), ("b", Some("globex")), ("c", None)] {
+            let mut stored = session(id, "api.x.com", 100);
+            stored.tenant = tenant.map(str::to_string);
+            store.put(&stored).unwrap();
+        }
+
+        let list = |tenant: Option<&str>| {
+            let sessions = store
+                .list(&SessionQuery {
+                    tenant: tenant.map(str::to_string),
+                    limit: 10,
+                    ..Default::default()
+                })
+                .unwrap();
+            sessions.into_iter().map(|s| s.id).collect::<Vec<_>>()
+        };
+        assert_eq!(list(Some("acme")), ["a"], "{:?}", backend);
+        assert_eq!(list(None), ["a", "b", "c"], "{:?}", backend);
+        assert_eq!(
+            store.get("b").unwrap().unwrap().tenant.as_deref(),
+            Some("globex")
+        );
+    }
+}
+
+#[test]
+fn sqlite_store_without_tenant_column_is_migrated() {
+    let dir = TempDir::new().expect("temp dir");
+    let path = dir.path().join("sessions.db");
+    let old = rusqlite::Connection::open(&path).unwrap();
+    old.execute_batch(
+        "CREATE TABLE sessions (
+             id TEXT PRIMARY KEY,
+             server_name TEXT,
+             completed_at INTEGER NOT NULL,
+             record TEXT NOT NULL
+         );",
+    )
+    .unwrap();
+    let record = serde_json::to_string(&session("old", "api.x.com", 100)).unwrap();
+    old.execute(
+        "INSERT INTO sessions (id, server_name, completed_at, record)
+         VALUES ('old', 'api.x.com', 100, ?1)",
+        [record],
+    )
+    .unwrap();
+    drop(old);
+
+    let store = ResultStore::open(&StoreConfig {
+        backend: StoreBackend::Sqlite,
+        path,
+    })
+    .expect("old store opens");
+    let mut stored = session("new", "api.x.com", 200);
+    stored.tenant = Some("acme".to_string());
+    store.put(&stored).unwrap();
+
+    let scoped = store
+        .list(&SessionQuery {
+            tenant: Some("acme".to_string()),
+            limit: 10,
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(scoped.len(), 1);
+    assert_eq!(scoped[0].id, "new");
+    assert!(store.get("old").unwrap().unwrap().tenant.is_none());
+}
+
+#[tokio::test]
+async fn handlers_only_serve_the_callers_tenant() {
+    let config: Config = serde_yaml_ng::from_str(
+        r#"
+auth:
+  api_keys:
+    - { key: "key-acme", tenant: acme }
+    - { key: "key-globex", tenant: globex }
+store:
+  backend: sqlite
+  path: ":memory:"
+"#,
+    )
+    .unwrap();
+    let state = Arc::new(AppState::new(config).expect("valid state"));
+    let mut stored = session("a", "api.x.com", 100);
+    stored.tenant = Some("acme".to_string());
+    state.store.as_ref().unwrap().put(&stored).unwrap();
+
+    for (key, visible) in [("key-acme", true), ("key-globex", false)] {
+        let mut headers = HeaderMap::new();
+        headers.insert(API_KEY_HEADER, HeaderValue::from_static(key));
+
+        let found =
+            get_session_handler(State(state.clone()), headers.clone(), Path("a".to_string())).await;
+        match found {
+            Ok(_) => assert!(visible, "{}", key),
+            Err((status, _)) => {
+                assert!(!visible, "{}", key);
+                assert_eq!(status, StatusCode::NOT_FOUND);
+            }
+        }
+
+        let query = Query::try_from_uri(&"/sessions".parse().unwrap()).unwrap();
+        let response = list_sessions_handler(State(state.clone()), headers, query)
+            .await
+            .unwrap()
+            .into_response();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let page: Value = serde_json::from_slice(&body).unwrap();
+        let expected = if visible {
+            json!([{ "id": "a", "tenant": "acme" }])
+        } else {
+            json!([])
+        };
+        let listed: Vec<_> = page["sessions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|session| json!({ "id": session["id"], "tenant": session["tenant"] }))
+            .collect();
+        assert_eq!(Value::from(listed), expected, "{}", key);
+    }
+}
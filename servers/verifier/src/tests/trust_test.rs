@@ -0,0 +1,107 @@
+//! Tests for the trusted root certificates.
+
+use std::path::PathBuf;
+
+use tlsn::webpki::{CertificateDer, RootCertStore};
+
+use crate::trust::{TrustConfig, TrustRoots, TrustStore};
+use crate::Config;
+
+// The store only splits bundles, so the certificates need not be valid DER
+const BUNDLE: &str = "\
+-----BEGIN CERTIFICATE-----
+AQID
+-----END CERTIFICATE-----
+-----BEGIN CERTIFICATE-----
+BAUG
+-----END CERTIFICATE-----
+";
+
+fn write_bundle(contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("verifier-ca-{}.pem", uuid::Uuid::new_v4()));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+fn trust_config(yaml: &str) -> TrustConfig {
+    let config: Config = serde_yaml_ng::from_str(yaml).expect("valid config");
+    config.trust
+}
+
+#[test]
+fn mozilla_roots_are_trusted_by_default() {
+    let roots = TrustRoots::load(&TrustConfig::default()).unwrap();
+    assert_eq!(
+        roots.roots_for(Some("api.x.com")).roots,
+        RootCertStore::mozilla().roots
+    );
+    assert_eq!(roots.scope(Some("api.x.com")), None);
+}
+
+#[test]
+fn bundles_add_to_or_replace_the_default_roots() {
+    let bundle = write_bundle(BUNDLE);
+    let config = trust_config(&format!(
+        r#"
+trust:
+  ca_bundles: [{bundle:?}]
+  servers:
+    "git.internal.example.com":
+      mozilla: false
+      ca_bundles: [{bundle:?}]
+"#
+    ));
+    let roots = TrustRoots::load(&config).unwrap();
+
+    let mozilla = RootCertStore::mozilla().roots.len();
+    assert_eq!(roots.roots_for(None).roots.len(), mozilla + 2);
+    assert_eq!(roots.roots_for(Some("api.x.com")).roots.len(), mozilla + 2);
+
+    let internal = roots.roots_for(Some("git.internal.example.com"));
+    assert_eq!(
+        internal.roots,
+        [CertificateDer(vec![1, 2, 3]), CertificateDer(vec![4, 5, 6])]
+    );
+    assert_eq!(
+        roots.scope(Some("git.internal.example.com")),
+        Some("git.internal.example.com")
+    );
+    assert_eq!(roots.scope(None), None);
+}
+
+#[test]
+fn unusable_bundles_are_rejected() {
+    let missing = std::env::temp_dir().join("verifier-ca-missing.pem");
+    let empty = write_bundle("not a certificate\n");
+    for bundle in [missing, empty] {
+        let config = trust_config(&format!("trust:\n  ca_bundles: [{:?}]\n", bundle));
+        assert!(TrustRoots::load(&config).is_err(), "{:?}", bundle);
+    }
+
+    // Turning off the Mozilla roots needs a bundle in their place
+    let config = trust_config("trust:\n  servers:\n    'a.com': { mozilla: false }\n");
+    let error = TrustRoots::load(&config).unwrap_err().to_string();
+    assert_eq!(error, "trust: a.com: no certificate authority is trusted");
+}
+
+#[test]
+fn reload_rereads_bundles_and_keeps_old_roots_on_error() {
+    let bundle = write_bundle(BUNDLE);
+    let config = trust_config(&format!(
+        "trust:\n  mozilla: false\n  ca_bundles: [{:?}]\n",
+        bundle
+    ));
+    let store = TrustStore::new(config).unwrap();
+    let before = store.current();
+    assert_eq!(before.roots_for(None).roots.len(), 2);
+
+    std::fs::write(&bundle, &BUNDLE[..BUNDLE.len() / 2]).unwrap();
+    store.reload().unwrap();
+    assert_eq!(store.current().roots_for(None).roots.len(), 1);
+    // Sessions that started earlier keep their roots
+    assert_eq!(before.roots_for(None).roots.len(), 2);
+
+    std::fs::remove_file(&bundle).unwrap();
+    assert!(store.reload().is_err());
+    assert_eq!(store.current().roots_for(None).roots.len(), 1);
+}
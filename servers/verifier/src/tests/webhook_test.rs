@@ -0,0 +1,307 @@
+//! Tests for queued webhook delivery (retries, dead-lettering, replay) and
+//! request signing.
+
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{body::Bytes, extract::State, http::HeaderMap, routing::post, Router};
+use hyper::StatusCode;
+use serde_json::{json, Value};
+use tlsn_verifier_server::webhook::{verify_signature, DEFAULT_SIGNATURE_TOLERANCE_SECS};
+use tokio::sync::Mutex;
+
+use crate::metrics::Metrics;
+use crate::webhook::{
+    sign, WebhookConfig, WebhookQueue, WebhookQueueConfig, IDEMPOTENCY_KEY_HEADER, SIGNATURE_HEADER,
+};
+use crate::{AppState, Config};
+
+/// Webhook receiver that fails the first `failures` requests with a 503
+#[derive(Clone)]
+struct FlakyReceiver {
+    failures: Arc<AtomicU32>,
+    received: Arc<Mutex<Vec<(HeaderMap, Bytes)>>>,
+}
+
+impl FlakyReceiver {
+    async fn start(failures: u32) -> (Self, SocketAddr) {
+        let receiver = Self {
+            failures: Arc::new(AtomicU32::new(failures)),
+            received: Arc::new(Mutex::new(Vec::new())),
+        };
+        let app = Router::new()
+            .route("/", post(flaky_handler))
+            .with_state(receiver.clone());
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        (receiver, addr)
+    }
+
+    async fn received(&self) -> Vec<(HeaderMap, Bytes)> {
+        self.received.lock().await.clone()
+    }
+}
+
+async fn flaky_handler(
+    State(receiver): State<FlakyReceiver>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> StatusCode {
+    let remaining = receiver.failures.load(Ordering::SeqCst);
+    if remaining > 0 {
+        receiver.failures.store(remaining - 1, Ordering::SeqCst);
+        return StatusCode::SERVICE_UNAVAILABLE;
+    }
+    receiver.received.lock().await.push((headers, body));
+    StatusCode::OK
+}
+
+fn queue(addr: SocketAddr, max_attempts: u32, secret: Option<&str>) -> Arc<WebhookQueue> {
+    let metrics = Arc::new(Metrics::new());
+    capped_queue(addr, max_attempts, secret, Default::default(), metrics)
+}
+
+fn capped_queue(
+    addr: SocketAddr,
+    max_attempts: u32,
+    secret: Option<&str>,
+    caps: WebhookQueueConfig,
+    metrics: Arc<Metrics>,
+) -> Arc<WebhookQueue> {
+    let mut config = Config {
+        webhook_queue: caps,
+        ..Config::default()
+    };
+    config.webhooks.insert(
+        "*".to_string(),
+        WebhookConfig {
+            url: format!("http://{}", addr),
+            headers: Default::default(),
+            secret: secret.map(str::to_string),
+            max_attempts,
+            timeout_secs: 5,
+            backoff_initial_ms: 10,
+            backoff_max_ms: 50,
+            notify_failures: false,
+        },
+    );
+    Arc::new(WebhookQueue::new(Arc::new(config), None, metrics))
+}
+
+/// Polls `check` until it holds or the deadline passes
+async fn eventually<F, Fut>(mut check: F)
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = bool>,
+{
+    for _ in 0..100 {
+        if check().await {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("condition not met in time");
+}
+
+#[test]
+fn backoff_doubles_up_to_max() {
+    let webhook = WebhookConfig {
+        url: String::new(),
+        headers: Default::default(),
+        secret: None,
+        max_attempts: 10,
+        timeout_secs: 1,
+        backoff_initial_ms: 100,
+        backoff_max_ms: 500,
+        notify_failures: false,
+    };
+    assert_eq!(webhook.backoff(1), Duration::from_millis(100));
+    assert_eq!(webhook.backoff(2), Duration::from_millis(200));
+    assert_eq!(webhook.backoff(3), Duration::from_millis(400));
+    assert_eq!(webhook.backoff(4), Duration::from_millis(500));
+    assert_eq!(webhook.backoff(40), Duration::from_millis(500));
+}
+
+#[test]
+fn webhooks_that_cannot_deliver_fail_startup() {
+    for settings in ["max_attempts: 0", "timeout_secs: 0"] {
+        let config: Config = serde_yaml_ng::from_str(&format!(
+            "webhooks:\n  \"api.example.com\":\n    url: \"http://127.0.0.1:1\"\n    {}\n",
+            settings
+        ))
+        .unwrap();
+        let err = AppState::new(config).err().expect(settings).to_string();
+        assert!(err.contains("webhooks.api.example.com"), "{}", err);
+    }
+}
+
+#[tokio::test]
+async fn retries_until_receiver_recovers() {
+    let (receiver, addr) = FlakyReceiver::start(2).await;
+    let queue = queue(addr, 5, None);
+
+    queue
+        .enqueue("session-1", "api.example.com", json!({ "n": 1 }))
+        .await;
+
+    eventually(|| async { receiver.received().await.len() == 1 }).await;
+    let received = receiver.received().await;
+    assert_eq!(received[0].0[IDEMPOTENCY_KEY_HEADER], "session-1");
+    assert!(received[0].0.get(SIGNATURE_HEADER).is_none());
+    let body: Value = serde_json::from_slice(&received[0].1).unwrap();
+    assert_eq!(body["n"], 1);
+
+    eventually(|| async { queue.pending().await.is_empty() }).await;
+    assert!(queue.dead_letters().await.is_empty());
+}
+
+#[tokio::test]
+async fn exhausted_delivery_is_dead_lettered_and_replayable() {
+    let (receiver, addr) = FlakyReceiver::start(3).await;
+    let queue = queue(addr, 2, None);
+
+    queue
+        .enqueue("session-2", "api.example.com", json!({ "n": 2 }))
+        .await;
+
+    eventually(|| async { queue.dead_letters().await.len() == 1 }).await;
+    let dead = queue.dead_letters().await;
+    assert_eq!(dead[0].attempts, 2);
+    assert_eq!(
+        dead[0].last_error.as_deref(),
+        Some("HTTP 503 Service Unavailable")
+    );
+    assert!(receiver.received().await.is_empty());
+
+    // One failure left on the receiver, then the replay succeeds
+    queue.replay(&dead[0].id).await.expect("replay dead letter");
+    eventually(|| async { receiver.received().await.len() == 1 }).await;
+    eventually(|| async { queue.dead_letters().await.is_empty() }).await;
+
+    assert!(queue.replay("missing").await.is_err());
+}
+
+#[tokio::test]
+async fn flush_waits_for_pending_deliveries_until_deadline() {
+    let (receiver, addr) = FlakyReceiver::start(2).await;
+    let recovering = queue(addr, 5, None);
+    assert_eq!(recovering.flush(Duration::ZERO).await, 0);
+
+    recovering
+        .enqueue("session-4", "api.example.com", json!({ "n": 4 }))
+        .await;
+    assert_eq!(recovering.flush(Duration::from_secs(5)).await, 0);
+    assert_eq!(receiver.received().await.len(), 1);
+
+    // A receiver that keeps failing outlasts the deadline
+    let (_, addr) = FlakyReceiver::start(u32::MAX).await;
+    let failing = queue(addr, 100, None);
+    failing
+        .enqueue("session-5", "api.example.com", json!({ "n": 5 }))
+        .await;
+    assert_eq!(failing.flush(Duration::from_millis(50)).await, 1);
+}
+
+#[tokio::test]
+async fn queue_caps_drop_the_oldest_deliveries() {
+    let (_, addr) = FlakyReceiver::start(u32::MAX).await;
+    let metrics = Arc::new(Metrics::new());
+    let caps = WebhookQueueConfig {
+        max_pending: 2,
+        max_dead_letters: 1,
+    };
+    let retrying = capped_queue(addr, 100, None, caps.clone(), metrics.clone());
+    for n in 1..=3 {
+        retrying
+            .enqueue(
+                &format!("session-{}", n),
+                "api.example.com",
+                json!({ "n": n }),
+            )
+            .await;
+    }
+    let mut pending: Vec<_> = retrying
+        .pending()
+        .await
+        .into_iter()
+        .map(|d| d.session_id)
+        .collect();
+    pending.sort();
+    assert_eq!(pending, ["session-2", "session-3"]);
+
+    // The newest dead letter replaces the oldest
+    let failing = capped_queue(addr, 1, None, caps, metrics.clone());
+    for n in 4..=5 {
+        failing
+            .enqueue(
+                &format!("session-{}", n),
+                "api.example.com",
+                json!({ "n": n }),
+            )
+            .await;
+        eventually(|| async {
+            failing
+                .dead_letters()
+                .await
+                .iter()
+                .any(|d| d.session_id == format!("session-{}", n))
+        })
+        .await;
+    }
+    let dead = failing.dead_letters().await;
+    assert_eq!(dead.len(), 1);
+    assert_eq!(dead[0].session_id, "session-5");
+
+    let rendered = metrics.render().unwrap();
+    assert!(rendered.contains(r#"tlsn_webhook_evictions_total{status="pending"} 1"#));
+    assert!(rendered.contains(r#"tlsn_webhook_evictions_total{status="dead"} 1"#));
+}
+
+#[test]
+fn signature_roundtrip() {
+    let body = br#"{"server_name":"api.example.com"}"#;
+    let header = sign("whsec", 1_000, body);
+    assert!(header.starts_with("t=1000,v1="));
+
+    verify_signature(
+        "whsec",
+        &header,
+        body,
+        DEFAULT_SIGNATURE_TOLERANCE_SECS,
+        1_100,
+    )
+    .expect("signature should verify");
+
+    // Wrong secret, tampered body, stale timestamp
+    assert!(verify_signature("other", &header, body, 300, 1_000).is_err());
+    assert!(verify_signature("whsec", &header, b"{}", 300, 1_000).is_err());
+    assert!(verify_signature("whsec", &header, body, 300, 1_301).is_err());
+    assert!(verify_signature("whsec", "v1=00", body, 300, 1_000).is_err());
+}
+
+#[tokio::test]
+async fn signed_delivery_verifies_against_raw_body() {
+    let (receiver, addr) = FlakyReceiver::start(0).await;
+    let queue = queue(addr, 1, Some("whsec"));
+
+    queue
+        .enqueue("session-3", "api.example.com", json!({ "n": 3 }))
+        .await;
+
+    eventually(|| async { receiver.received().await.len() == 1 }).await;
+    let (headers, body) = receiver.received().await.remove(0);
+    let header = headers[SIGNATURE_HEADER].to_str().unwrap();
+    verify_signature(
+        "whsec",
+        header,
+        &body,
+        DEFAULT_SIGNATURE_TOLERANCE_SECS,
+        crate::unix_timestamp(),
+    )
+    .expect("delivered signature should verify");
+}
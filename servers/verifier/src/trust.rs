@@ -0,0 +1,162 @@
+//! Root certificates the server's TLS certificate is verified against.
+//!
+//! Configured in the `trust` section of `config.yaml`. By default only the
+//! Mozilla root program is trusted; PEM bundles add further certificate
+//! authorities, such as a corporate CA or the self-signed certificate of a
+//! test server. Entries under `servers` replace the default roots for the
+//! server names they match, so a private CA can be trusted for internal hosts
+//! only. Bundles are read at startup and re-read on reload (SIGHUP or
+//! `POST /admin/trust/reload`), so rotated CA files take effect without a
+//! restart.
+
+use rustls_pki_types::{pem::PemObject, CertificateDer as PemCertificate};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use tlsn::webpki::{CertificateDer, RootCertStore};
+
+use crate::match_server_name;
+
+/// A set of trusted roots: the Mozilla roots, PEM bundles or both.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct TrustRootsConfig {
+    /// Whether to trust the Mozilla root program
+    #[serde(default = "default_mozilla")]
+    pub(crate) mozilla: bool,
+    /// PEM files with further certificate authorities to trust
+    #[serde(default)]
+    pub(crate) ca_bundles: Vec<PathBuf>,
+}
+
+fn default_mozilla() -> bool {
+    true
+}
+
+impl Default for TrustRootsConfig {
+    fn default() -> Self {
+        Self {
+            mozilla: default_mozilla(),
+            ca_bundles: Vec::new(),
+        }
+    }
+}
+
+/// `trust` section of `config.yaml`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub(crate) struct TrustConfig {
+    /// Roots for server names without an entry in `servers`
+    #[serde(flatten)]
+    pub(crate) default: TrustRootsConfig,
+    /// Roots keyed by server name, with the same wildcards as `policies`
+    #[serde(default)]
+    pub(crate) servers: HashMap<String, TrustRootsConfig>,
+}
+
+/// Roots of one `servers` entry, with the pattern they were configured for.
+#[derive(Debug, Clone)]
+struct ServerRoots {
+    pattern: String,
+    roots: RootCertStore,
+}
+
+/// Loaded root certificates.
+#[derive(Debug, Clone)]
+pub(crate) struct TrustRoots {
+    default: RootCertStore,
+    servers: HashMap<String, ServerRoots>,
+}
+
+impl TrustRoots {
+    /// Reads every configured bundle.
+    pub(crate) fn load(config: &TrustConfig) -> Result<Self, eyre::ErrReport> {
+        let default = load_roots(&config.default).map_err(|e| eyre::eyre!("trust: {}", e))?;
+        let servers = config
+            .servers
+            .iter()
+            .map(|(pattern, roots)| {
+                let roots =
+                    load_roots(roots).map_err(|e| eyre::eyre!("trust: {}: {}", pattern, e))?;
+                let pattern = pattern.clone();
+                Ok((pattern.clone(), ServerRoots { pattern, roots }))
+            })
+            .collect::<Result<_, eyre::ErrReport>>()?;
+        Ok(Self { default, servers })
+    }
+
+    /// The `servers` entry that applies to `server_name`, or `None` when it
+    /// uses the default roots.
+    pub(crate) fn scope(&self, server_name: Option<&str>) -> Option<&str> {
+        server_name
+            .and_then(|server_name| match_server_name(&self.servers, server_name))
+            .map(|server| server.pattern.as_str())
+    }
+
+    /// Roots to verify `server_name` against; the default roots when it is
+    /// not known.
+    pub(crate) fn roots_for(&self, server_name: Option<&str>) -> &RootCertStore {
+        server_name
+            .and_then(|server_name| match_server_name(&self.servers, server_name))
+            .map_or(&self.default, |server| &server.roots)
+    }
+}
+
+/// The loaded roots, swapped out as a whole on reload.
+#[derive(Debug)]
+pub(crate) struct TrustStore {
+    config: TrustConfig,
+    roots: RwLock<Arc<TrustRoots>>,
+}
+
+impl TrustStore {
+    pub(crate) fn new(config: TrustConfig) -> Result<Self, eyre::ErrReport> {
+        let roots = TrustRoots::load(&config)?;
+        Ok(Self {
+            config,
+            roots: RwLock::new(Arc::new(roots)),
+        })
+    }
+
+    /// Roots as of the last (re)load; sessions keep the set they started with.
+    pub(crate) fn current(&self) -> Arc<TrustRoots> {
+        self.roots
+            .read()
+            .expect("trust roots lock poisoned")
+            .clone()
+    }
+
+    /// Re-reads the bundles. On error the roots loaded before stay in use.
+    pub(crate) fn reload(&self) -> Result<(), eyre::ErrReport> {
+        let roots = TrustRoots::load(&self.config)?;
+        *self.roots.write().expect("trust roots lock poisoned") = Arc::new(roots);
+        Ok(())
+    }
+}
+
+fn load_roots(config: &TrustRootsConfig) -> Result<RootCertStore, String> {
+    let mut store = if config.mozilla {
+        RootCertStore::mozilla()
+    } else {
+        RootCertStore::empty()
+    };
+    for path in &config.ca_bundles {
+        store.roots.extend(read_bundle(path)?);
+    }
+    if store.roots.is_empty() {
+        return Err("no certificate authority is trusted".to_string());
+    }
+    Ok(store)
+}
+
+/// Reads the certificates of a PEM bundle, which must hold at least one.
+fn read_bundle(path: &Path) -> Result<Vec<CertificateDer>, String> {
+    let pem = std::fs::read(path).map_err(|e| format!("failed to read {:?}: {}", path, e))?;
+    let certs = PemCertificate::pem_slice_iter(&pem)
+        .map(|cert| cert.map(|cert| CertificateDer(cert.to_vec())))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("invalid PEM in {:?}: {}", path, e))?;
+    if certs.is_empty() {
+        return Err(format!("no certificates in {:?}", path));
+    }
+    Ok(certs)
+}
@@ -1,5 +1,8 @@
-use crate::ws::TungsteniteStream;
-use eyre::eyre;
+use crate::commitment::EncodedRanges;
+use crate::error::{ErrorCode, SessionError};
+use crate::metrics::Metrics;
+use crate::progress::Phase;
+use std::future::Future;
 use tlsn::{
     config::verifier::VerifierConfig,
     connection::{DnsName, ServerName},
@@ -9,24 +12,43 @@ use tlsn::{
     Session,
 };
 use tokio::io::{AsyncRead, AsyncWrite};
-use tokio::sync::oneshot;
+use tokio::net::TcpStream;
 use tokio_util::compat::TokioAsyncReadCompatExt;
 use tracing::{debug, info};
 
 /// Core verifier logic that validates the TLS proof.
 /// Supports both MPC and Proxy modes — the prover picks via its commit config.
 ///
-/// `_proxy_socket_rx` is a leftover from the previous proxy plumbing
-/// (`/proxy?sessionId=…`). The current `tlsn` API tunnels prover↔verifier proxy
-/// traffic through the existing session multiplexer, so this channel is
-/// unused. Kept on the signature to avoid rippling changes through `main.rs`
-/// until that endpoint is removed.
-pub async fn verifier<T: AsyncWrite + AsyncRead + Send + Unpin + 'static>(
+/// The server's certificate chain is verified against `root_store`.
+///
+/// In Proxy mode, `connect_server` is called with the prover's server name
+/// and returns the connection to that server; an error aborts the
+/// verification.
+///
+/// `report` is called with each phase the verification reaches.
+///
+/// The mode the prover picks is counted in `metrics`. Errors carry the code
+/// of the step that failed.
+pub async fn verifier<
+    T: AsyncWrite + AsyncRead + Send + Unpin + 'static,
+    F: Future<Output = Result<TcpStream, SessionError>>,
+>(
     socket: T,
     max_sent_data: usize,
     max_recv_data: usize,
-    _proxy_socket_rx: Option<oneshot::Receiver<TungsteniteStream>>,
-) -> Result<(DnsName, PartialTranscript, Vec<TranscriptCommitment>), eyre::ErrReport> {
+    root_store: RootCertStore,
+    connect_server: impl FnOnce(String) -> F,
+    report: impl Fn(Phase),
+    metrics: &Metrics,
+) -> Result<
+    (
+        DnsName,
+        PartialTranscript,
+        Vec<TranscriptCommitment>,
+        EncodedRanges,
+    ),
+    SessionError,
+> {
     info!(
         "Starting verification with maxSentData={}, maxRecvData={}",
         max_sent_data, max_recv_data
@@ -47,13 +69,13 @@ pub async fn verifier<T: AsyncWrite + AsyncRead + Send + Unpin + 'static>(
     });
 
     let verifier_config = VerifierConfig::builder()
-        .root_store(RootCertStore::mozilla())
+        .root_store(root_store)
         .build()
-        .map_err(|e| eyre!("Failed to build verifier config: {}", e))?;
+        .map_err(fail(ErrorCode::Internal, "Failed to build verifier config"))?;
 
     let verifier = handle
         .new_verifier(verifier_config)
-        .map_err(|e| eyre!("Failed to create verifier: {}", e))?;
+        .map_err(fail(ErrorCode::Internal, "Failed to create verifier"))?;
 
     info!("Starting TLS commitment protocol");
 
@@ -61,23 +83,35 @@ pub async fn verifier<T: AsyncWrite + AsyncRead + Send + Unpin + 'static>(
     let verifier = match verifier
         .commit()
         .await
-        .map_err(|e| eyre!("Commitment failed: {}", e))?
+        .map_err(fail(ErrorCode::CommitFailed, "Commitment failed"))?
     {
         VerifierCommitStart::Mpc(verifier) => {
+            metrics.verifier_mode("mpc", None);
+            report(Phase::ModeSelected { mode: "Mpc" });
             let cfg = verifier.config();
             if cfg.max_sent_data() > max_sent_data {
-                return Err(eyre!(
-                    "Prover requested max_sent_data {} exceeds limit {}",
-                    cfg.max_sent_data(),
-                    max_sent_data
-                ));
+                return Err(SessionError::new(
+                    ErrorCode::LimitExceeded,
+                    format!(
+                        "Prover requested max_sent_data {} exceeds limit {}",
+                        cfg.max_sent_data(),
+                        max_sent_data
+                    ),
+                )
+                .with_direction("sent")
+                .with_limit(cfg.max_sent_data(), max_sent_data));
             }
             if cfg.max_recv_data() > max_recv_data {
-                return Err(eyre!(
-                    "Prover requested max_recv_data {} exceeds limit {}",
-                    cfg.max_recv_data(),
-                    max_recv_data
-                ));
+                return Err(SessionError::new(
+                    ErrorCode::LimitExceeded,
+                    format!(
+                        "Prover requested max_recv_data {} exceeds limit {}",
+                        cfg.max_recv_data(),
+                        max_recv_data
+                    ),
+                )
+                .with_direction("recv")
+                .with_limit(cfg.max_recv_data(), max_recv_data));
             }
             info!(
                 "Accepting MPC TLS commitment with max_sent={}, max_recv={}",
@@ -85,41 +119,53 @@ pub async fn verifier<T: AsyncWrite + AsyncRead + Send + Unpin + 'static>(
                 cfg.max_recv_data()
             );
 
-            verifier
+            let verifier = verifier
                 .accept()
                 .await
-                .map_err(|e| eyre!("Accept failed: {}", e))?
+                .map_err(fail(ErrorCode::CommitFailed, "Accept failed"))?;
+            report(Phase::CommitmentAccepted);
+            verifier
                 .run()
                 .await
-                .map_err(|e| eyre!("Run failed: {}", e))?
+                .map_err(fail(ErrorCode::CommitFailed, "Run failed"))?
         }
         VerifierCommitStart::Proxy(verifier) => {
             let host = verifier.config().server_name().as_str().to_string();
+            metrics.verifier_mode("proxy", Some(&host));
+            report(Phase::ModeSelected { mode: "Proxy" });
             info!("Accepting Proxy TLS commitment for server: {}", host);
 
-            let server_addr = format!("{}:443", host);
-            let server_stream = tokio::net::TcpStream::connect(&server_addr)
-                .await
-                .map_err(|e| eyre!("Failed to connect to target server {}: {}", server_addr, e))?;
-            info!("Connected to target server {}", server_addr);
+            let server_stream = connect_server(host.clone()).await?;
+            info!("Connected to target server {}", host);
 
-            verifier
+            let verifier = verifier
                 .accept()
                 .await
-                .map_err(|e| eyre!("Accept failed: {}", e))?
+                .map_err(fail(ErrorCode::CommitFailed, "Accept failed"))?;
+            report(Phase::CommitmentAccepted);
+            verifier
                 .run(server_stream.compat())
                 .await
-                .map_err(|e| eyre!("Run failed: {}", e))?
+                .map_err(fail(ErrorCode::CommitFailed, "Run failed"))?
         }
     };
 
     info!("TLS connection complete, starting verification");
+    report(Phase::TlsComplete);
 
     // Verify the proof
     let verifier = verifier
         .verify()
         .await
-        .map_err(|e| eyre!("Verification failed: {}", e))?;
+        .map_err(fail(ErrorCode::VerificationFailed, "Verification failed"))?;
+
+    // The encoding commitment only holds a root; the ranges it covers are
+    // named in the prover's request
+    let encoded = verifier
+        .request()
+        .transcript_commit()
+        .map(EncodedRanges::from_request)
+        .unwrap_or_default();
 
     let (
         VerifierOutput {
@@ -129,16 +175,17 @@ pub async fn verifier<T: AsyncWrite + AsyncRead + Send + Unpin + 'static>(
             ..
         },
         verifier,
-    ) = verifier
-        .accept()
-        .await
-        .map_err(|e| eyre!("Accept verification failed: {}", e))?;
+    ) = verifier.accept().await.map_err(fail(
+        ErrorCode::VerificationFailed,
+        "Accept verification failed",
+    ))?;
+    report(Phase::TranscriptVerified);
 
     // Close the verifier
-    verifier
-        .close()
-        .await
-        .map_err(|e| eyre!("Failed to close verifier: {}", e))?;
+    verifier.close().await.map_err(fail(
+        ErrorCode::VerificationFailed,
+        "Failed to close verifier",
+    ))?;
 
     // Close the session handle
     handle.close();
@@ -146,15 +193,23 @@ pub async fn verifier<T: AsyncWrite + AsyncRead + Send + Unpin + 'static>(
     // Wait for the driver to complete
     driver_task
         .await
-        .map_err(|e| eyre!("Driver task failed: {}", e))?
-        .map_err(|e| eyre!("Session driver error: {}", e))?;
+        .map_err(fail(ErrorCode::Internal, "Driver task failed"))?
+        .map_err(fail(ErrorCode::VerificationFailed, "Session driver error"))?;
 
     info!("verify() returned successfully");
 
-    let server_name =
-        server_name.ok_or_else(|| eyre!("prover should have revealed server name"))?;
-    let transcript =
-        transcript.ok_or_else(|| eyre!("prover should have revealed transcript data"))?;
+    let server_name = server_name.ok_or_else(|| {
+        SessionError::new(
+            ErrorCode::VerificationFailed,
+            "prover should have revealed server name",
+        )
+    })?;
+    let transcript = transcript.ok_or_else(|| {
+        SessionError::new(
+            ErrorCode::VerificationFailed,
+            "prover should have revealed transcript data",
+        )
+    })?;
 
     info!("server_name: {:?}", server_name);
     debug!("transcript: {:?}", &transcript);
@@ -180,7 +235,15 @@ pub async fn verifier<T: AsyncWrite + AsyncRead + Send + Unpin + 'static>(
         transcript_commitments.len()
     );
 
-    Ok((dns_name, transcript, transcript_commitments))
+    Ok((dns_name, transcript, transcript_commitments, encoded))
+}
+
+/// Maps the error of a failed step to a session error with `code`.
+fn fail<E: std::fmt::Display>(
+    code: ErrorCode,
+    context: &'static str,
+) -> impl FnOnce(E) -> SessionError {
+    move |e| SessionError::new(code, format!("{}: {}", context, e))
 }
 
 /// Compress long sequences of redacted emojis for better readability
@@ -191,8 +254,11 @@ fn compress_redacted_sequences(text: String) -> String {
 }
 
 /// Render redacted bytes as block characters.
-fn bytes_to_redacted_string(bytes: &[u8], to: &str) -> Result<String, eyre::ErrReport> {
+fn bytes_to_redacted_string(bytes: &[u8], to: &str) -> Result<String, SessionError> {
     Ok(String::from_utf8(bytes.to_vec())
-        .map_err(|err| eyre!("Failed to parse bytes to redacted string: {err}"))?
+        .map_err(fail(
+            ErrorCode::Internal,
+            "Failed to parse bytes to redacted string",
+        ))?
         .replace('\0', to))
 }
@@ -0,0 +1,564 @@
+//! At-least-once webhook delivery.
+//!
+//! Payloads are queued as [`WebhookDelivery`] records and posted by a
+//! background task per delivery. Failed attempts are retried with
+//! exponential backoff up to the webhook's `max_attempts`; deliveries that
+//! exhaust their attempts, or get a non-retryable response, move to the
+//! dead-letter list where operators can inspect and replay them through the
+//! admin API.
+//!
+//! Both lists are capped by the `webhook_queue` section of `config.yaml`:
+//! beyond the cap the oldest deliveries are dropped, and counted in the
+//! `tlsn_webhook_evictions_total` metric.
+//!
+//! When a result store is configured, deliveries are persisted there and
+//! resumed on startup, so a restart does not drop queued payloads. On
+//! shutdown the server [flushes](WebhookQueue::flush) the queue for as long
+//! as its drain deadline allows.
+//!
+//! Every request carries an `Idempotency-Key` header set to the session id,
+//! so receivers can deduplicate retried deliveries.
+//!
+//! When a webhook has a `secret`, each request also carries a
+//! [`SIGNATURE_HEADER`] of the form `t=<unix-seconds>,v1=<hex>`, where `v1`
+//! is HMAC-SHA256 keyed with the secret over `"<t>.<raw body>"`. The
+//! timestamp is refreshed on every attempt, so receivers should reject
+//! signatures older than a few minutes to bound replays. [`sign`] and its
+//! counterpart `verify_signature` live in the crate's library so receiving
+//! services can reuse the check.
+
+use eyre::eyre;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::metrics::Metrics;
+use crate::store::ResultStore;
+use crate::{unix_timestamp, Config};
+
+pub(crate) use tlsn_verifier_server::webhook::{sign, SIGNATURE_HEADER};
+
+/// Header carrying the session id so receivers can deduplicate retries
+pub(crate) const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// How often [`WebhookQueue::flush`] rechecks the pending deliveries
+const FLUSH_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Webhook configuration for a specific server
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct WebhookConfig {
+    pub(crate) url: String,
+    #[serde(default)]
+    pub(crate) headers: HashMap<String, String>,
+    /// Shared secret for signing requests; unsigned when absent
+    #[serde(default)]
+    pub(crate) secret: Option<String>,
+    /// Total delivery attempts before the payload is dead-lettered
+    #[serde(default = "default_max_attempts")]
+    pub(crate) max_attempts: u32,
+    /// Per-request timeout in seconds
+    #[serde(default = "default_timeout_secs")]
+    pub(crate) timeout_secs: u64,
+    /// Delay before the first retry; doubles on each subsequent retry
+    #[serde(default = "default_backoff_initial_ms")]
+    pub(crate) backoff_initial_ms: u64,
+    /// Upper bound on the delay between retries
+    #[serde(default = "default_backoff_max_ms")]
+    pub(crate) backoff_max_ms: u64,
+    /// Also POST failed sessions, with an `error` instead of results
+    #[serde(default)]
+    pub(crate) notify_failures: bool,
+}
+
+fn default_max_attempts() -> u32 {
+    5
+}
+
+fn default_timeout_secs() -> u64 {
+    10
+}
+
+fn default_backoff_initial_ms() -> u64 {
+    1_000
+}
+
+fn default_backoff_max_ms() -> u64 {
+    300_000
+}
+
+impl WebhookConfig {
+    /// Rejects settings under which no delivery could succeed.
+    pub(crate) fn validate(&self, server_name: &str) -> Result<(), eyre::ErrReport> {
+        if self.max_attempts == 0 || self.timeout_secs == 0 {
+            return Err(eyre!(
+                "webhooks.{}: `max_attempts` and `timeout_secs` must be greater than zero",
+                server_name
+            ));
+        }
+        Ok(())
+    }
+
+    /// Delay before retry number `attempt` (1-based count of failed attempts)
+    pub(crate) fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u64.saturating_pow(attempt.saturating_sub(1));
+        Duration::from_millis(
+            self.backoff_initial_ms
+                .saturating_mul(factor)
+                .min(self.backoff_max_ms),
+        )
+    }
+}
+
+/// `webhook_queue` section of `config.yaml`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub(crate) struct WebhookQueueConfig {
+    /// Deliveries still being attempted; the oldest is dropped to make room
+    pub(crate) max_pending: usize,
+    /// Dead-lettered deliveries kept for replay; the oldest are dropped first
+    pub(crate) max_dead_letters: usize,
+}
+
+impl Default for WebhookQueueConfig {
+    fn default() -> Self {
+        Self {
+            max_pending: 10_000,
+            max_dead_letters: 1_000,
+        }
+    }
+}
+
+impl WebhookQueueConfig {
+    /// Rejects caps that would drop every delivery.
+    pub(crate) fn validate(&self) -> Result<(), eyre::ErrReport> {
+        if self.max_pending == 0 || self.max_dead_letters == 0 {
+            return Err(eyre!(
+                "webhook_queue: `max_pending` and `max_dead_letters` must be greater than zero"
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum DeliveryStatus {
+    Pending,
+    Dead,
+}
+
+impl DeliveryStatus {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Dead => "dead",
+        }
+    }
+}
+
+/// A queued webhook payload and its delivery state.
+///
+/// The target webhook is resolved from `server_name` on every attempt, so
+/// config changes (e.g. a fixed URL) apply to retries and replays.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct WebhookDelivery {
+    pub(crate) id: String,
+    pub(crate) session_id: String,
+    pub(crate) server_name: String,
+    pub(crate) payload: serde_json::Value,
+    pub(crate) status: DeliveryStatus,
+    pub(crate) attempts: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) last_error: Option<String>,
+    pub(crate) created_at: u64,
+    pub(crate) updated_at: u64,
+    /// Order the delivery entered its current list, oldest first
+    #[serde(skip)]
+    seq: u64,
+}
+
+/// Outcome of a single delivery attempt that did not succeed
+struct AttemptError {
+    message: String,
+    retryable: bool,
+}
+
+pub(crate) struct WebhookQueue {
+    client: reqwest::Client,
+    config: Arc<Config>,
+    store: Option<Arc<ResultStore>>,
+    metrics: Arc<Metrics>,
+    deliveries: Mutex<HashMap<String, WebhookDelivery>>,
+    next_seq: AtomicU64,
+}
+
+impl WebhookQueue {
+    pub(crate) fn new(
+        config: Arc<Config>,
+        store: Option<Arc<ResultStore>>,
+        metrics: Arc<Metrics>,
+    ) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            config,
+            store,
+            metrics,
+            deliveries: Mutex::new(HashMap::new()),
+            next_seq: AtomicU64::new(0),
+        }
+    }
+
+    /// Reloads persisted deliveries and restarts the pending ones.
+    pub(crate) async fn resume(self: &Arc<Self>) -> Result<(), eyre::ErrReport> {
+        let Some(store) = self.store.clone() else {
+            return Ok(());
+        };
+        let mut stored = tokio::task::spawn_blocking(move || store.list_deliveries()).await??;
+        stored.sort_by_key(|d| d.created_at);
+
+        let (pending, evicted_pending, evicted_dead) = {
+            let mut deliveries = self.deliveries.lock().await;
+            for mut delivery in stored {
+                delivery.seq = self.next_seq.fetch_add(1, Ordering::Relaxed);
+                deliveries.insert(delivery.id.clone(), delivery);
+            }
+            let evicted_pending = self.evict_oldest(&mut deliveries, DeliveryStatus::Pending);
+            let evicted_dead = self.evict_oldest(&mut deliveries, DeliveryStatus::Dead);
+            let pending: Vec<_> = deliveries
+                .values()
+                .filter(|d| d.status == DeliveryStatus::Pending)
+                .map(|d| d.id.clone())
+                .collect();
+            (pending, evicted_pending, evicted_dead)
+        };
+        self.drop_evicted(DeliveryStatus::Pending, evicted_pending)
+            .await;
+        self.drop_evicted(DeliveryStatus::Dead, evicted_dead).await;
+
+        if !pending.is_empty() {
+            info!("Resuming {} pending webhook deliveries", pending.len());
+        }
+        for id in pending {
+            tokio::spawn(self.clone().deliver(id));
+        }
+        Ok(())
+    }
+
+    /// Queues a payload for delivery to the webhook configured for `server_name`.
+    pub(crate) async fn enqueue(
+        self: &Arc<Self>,
+        session_id: &str,
+        server_name: &str,
+        payload: serde_json::Value,
+    ) {
+        let now = unix_timestamp();
+        let delivery = WebhookDelivery {
+            id: Uuid::new_v4().to_string(),
+            session_id: session_id.to_string(),
+            server_name: server_name.to_string(),
+            payload,
+            status: DeliveryStatus::Pending,
+            attempts: 0,
+            last_error: None,
+            created_at: now,
+            updated_at: now,
+            seq: self.next_seq.fetch_add(1, Ordering::Relaxed),
+        };
+        let id = delivery.id.clone();
+
+        self.persist(&delivery).await;
+        let evicted = {
+            let mut deliveries = self.deliveries.lock().await;
+            deliveries.insert(id.clone(), delivery);
+            self.evict_oldest(&mut deliveries, DeliveryStatus::Pending)
+        };
+        self.drop_evicted(DeliveryStatus::Pending, evicted).await;
+        tokio::spawn(self.clone().deliver(id));
+    }
+
+    /// Deliveries that are still being attempted.
+    pub(crate) async fn pending(&self) -> Vec<WebhookDelivery> {
+        self.with_status(DeliveryStatus::Pending).await
+    }
+
+    /// Deliveries that exhausted their attempts.
+    pub(crate) async fn dead_letters(&self) -> Vec<WebhookDelivery> {
+        self.with_status(DeliveryStatus::Dead).await
+    }
+
+    /// Waits up to `wait` for pending deliveries to succeed or be
+    /// dead-lettered, returning how many are still pending.
+    pub(crate) async fn flush(&self, wait: Duration) -> usize {
+        let deadline = Instant::now() + wait;
+        loop {
+            let pending = self.pending().await.len();
+            let now = Instant::now();
+            if pending == 0 || now >= deadline {
+                return pending;
+            }
+            tokio::time::sleep(FLUSH_POLL_INTERVAL.min(deadline - now)).await;
+        }
+    }
+
+    async fn with_status(&self, status: DeliveryStatus) -> Vec<WebhookDelivery> {
+        let mut deliveries: Vec<_> = self
+            .deliveries
+            .lock()
+            .await
+            .values()
+            .filter(|d| d.status == status)
+            .cloned()
+            .collect();
+        deliveries.sort_by_key(|d| d.created_at);
+        deliveries
+    }
+
+    /// Moves a dead-lettered delivery back to pending with a fresh attempt budget.
+    pub(crate) async fn replay(self: &Arc<Self>, id: &str) -> Result<(), eyre::ErrReport> {
+        let (delivery, evicted) = {
+            let mut deliveries = self.deliveries.lock().await;
+            let delivery = deliveries
+                .get_mut(id)
+                .filter(|d| d.status == DeliveryStatus::Dead)
+                .ok_or_else(|| eyre!("No dead-lettered delivery with id {}", id))?;
+            delivery.status = DeliveryStatus::Pending;
+            delivery.attempts = 0;
+            delivery.updated_at = unix_timestamp();
+            delivery.seq = self.next_seq.fetch_add(1, Ordering::Relaxed);
+            let delivery = delivery.clone();
+            let evicted = self.evict_oldest(&mut deliveries, DeliveryStatus::Pending);
+            (delivery, evicted)
+        };
+        self.drop_evicted(DeliveryStatus::Pending, evicted).await;
+
+        info!(
+            "[{}] Replaying dead-lettered webhook delivery {}",
+            delivery.session_id, id
+        );
+        self.persist(&delivery).await;
+        tokio::spawn(self.clone().deliver(id.to_string()));
+        Ok(())
+    }
+
+    /// Attempts delivery until it succeeds, is dead-lettered, or is no longer pending.
+    async fn deliver(self: Arc<Self>, id: String) {
+        loop {
+            let Some(delivery) = self.deliveries.lock().await.get(&id).cloned() else {
+                return;
+            };
+            if delivery.status != DeliveryStatus::Pending {
+                return;
+            }
+            let session_id = delivery.session_id.clone();
+
+            let Some(webhook) = self.config.get_webhook(&delivery.server_name).cloned() else {
+                self.finish(
+                    &id,
+                    Err(AttemptError {
+                        message: format!("No webhook configured for {}", delivery.server_name),
+                        retryable: false,
+                    }),
+                    0,
+                )
+                .await;
+                return;
+            };
+
+            let started = Instant::now();
+            let result = self.attempt(&webhook, &delivery).await;
+            let attempts = delivery.attempts + 1;
+            self.metrics.webhook_attempt(
+                &delivery.server_name,
+                if result.is_ok() { "success" } else { "failure" },
+                started.elapsed(),
+            );
+            match result {
+                Ok(()) => {
+                    info!(
+                        "[{}] ✅ Webhook POST successful: {} (attempt {})",
+                        session_id, webhook.url, attempts
+                    );
+                    self.finish(&id, Ok(()), attempts).await;
+                    return;
+                }
+                Err(err) if err.retryable && attempts < webhook.max_attempts => {
+                    let delay = webhook.backoff(attempts);
+                    warn!(
+                        "[{}] Webhook POST to {} failed (attempt {}/{}): {} - retrying in {:?}",
+                        session_id, webhook.url, attempts, webhook.max_attempts, err.message, delay
+                    );
+                    self.record_failure(&id, attempts, err.message).await;
+                    tokio::time::sleep(delay).await;
+                }
+                Err(err) => {
+                    error!(
+                        "[{}] ❌ Webhook POST to {} failed (attempt {}/{}): {} - dead-lettered",
+                        session_id, webhook.url, attempts, webhook.max_attempts, err.message
+                    );
+                    self.finish(&id, Err(err), attempts).await;
+                    return;
+                }
+            }
+        }
+    }
+
+    async fn attempt(
+        &self,
+        webhook: &WebhookConfig,
+        delivery: &WebhookDelivery,
+    ) -> Result<(), AttemptError> {
+        // Serialize once so the signature covers the exact bytes sent
+        let body = serde_json::to_vec(&delivery.payload).map_err(|e| AttemptError {
+            message: format!("Failed to encode payload: {}", e),
+            retryable: false,
+        })?;
+
+        let mut request = self
+            .client
+            .post(&webhook.url)
+            .timeout(Duration::from_secs(webhook.timeout_secs))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(IDEMPOTENCY_KEY_HEADER, &delivery.session_id);
+
+        // Add custom headers from config
+        for (key, value) in &webhook.headers {
+            request = request.header(key, value);
+        }
+
+        if let Some(secret) = &webhook.secret {
+            request = request.header(SIGNATURE_HEADER, sign(secret, unix_timestamp(), &body));
+        }
+        let request = request.body(body);
+
+        let response = request.send().await.map_err(|e| AttemptError {
+            message: e.to_string(),
+            retryable: true,
+        })?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        Err(AttemptError {
+            message: format!("HTTP {}", status),
+            retryable: status.is_server_error()
+                || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                || status == reqwest::StatusCode::REQUEST_TIMEOUT,
+        })
+    }
+
+    async fn record_failure(&self, id: &str, attempts: u32, message: String) {
+        let delivery = {
+            let mut deliveries = self.deliveries.lock().await;
+            let Some(delivery) = deliveries.get_mut(id) else {
+                return;
+            };
+            delivery.attempts = attempts;
+            delivery.last_error = Some(message);
+            delivery.updated_at = unix_timestamp();
+            delivery.clone()
+        };
+        self.persist(&delivery).await;
+    }
+
+    /// Removes a delivered payload, or moves a failed one to the dead-letter list.
+    async fn finish(&self, id: &str, result: Result<(), AttemptError>, attempts: u32) {
+        match result {
+            Ok(()) => {
+                self.deliveries.lock().await.remove(id);
+                self.unpersist(id).await;
+            }
+            Err(err) => {
+                let (delivery, evicted) = {
+                    let mut deliveries = self.deliveries.lock().await;
+                    let Some(delivery) = deliveries.get_mut(id) else {
+                        return;
+                    };
+                    delivery.status = DeliveryStatus::Dead;
+                    delivery.attempts = attempts;
+                    delivery.last_error = Some(err.message);
+                    delivery.updated_at = unix_timestamp();
+                    delivery.seq = self.next_seq.fetch_add(1, Ordering::Relaxed);
+                    let delivery = delivery.clone();
+                    let evicted = self.evict_oldest(&mut deliveries, DeliveryStatus::Dead);
+                    (delivery, evicted)
+                };
+                self.persist(&delivery).await;
+                self.drop_evicted(DeliveryStatus::Dead, evicted).await;
+            }
+        }
+    }
+
+    /// Removes the oldest deliveries with `status` beyond its cap, returning
+    /// their ids.
+    fn evict_oldest(
+        &self,
+        deliveries: &mut HashMap<String, WebhookDelivery>,
+        status: DeliveryStatus,
+    ) -> Vec<String> {
+        let cap = match status {
+            DeliveryStatus::Pending => self.config.webhook_queue.max_pending,
+            DeliveryStatus::Dead => self.config.webhook_queue.max_dead_letters,
+        };
+        let mut queued: Vec<_> = deliveries
+            .values()
+            .filter(|d| d.status == status)
+            .map(|d| (d.seq, d.id.clone()))
+            .collect();
+        if queued.len() <= cap {
+            return Vec::new();
+        }
+        queued.sort_unstable();
+        let excess = queued.len() - cap;
+        let evicted: Vec<_> = queued.into_iter().take(excess).map(|(_, id)| id).collect();
+        for id in &evicted {
+            deliveries.remove(id);
+        }
+        evicted
+    }
+
+    /// Counts evicted deliveries and removes them from the store.
+    async fn drop_evicted(&self, status: DeliveryStatus, evicted: Vec<String>) {
+        if evicted.is_empty() {
+            return;
+        }
+        warn!(
+            "Dropped {} {} webhook deliveries over the queue cap",
+            evicted.len(),
+            status.label()
+        );
+        self.metrics.webhook_evicted(status.label(), evicted.len());
+        for id in evicted {
+            self.unpersist(&id).await;
+        }
+    }
+
+    async fn persist(&self, delivery: &WebhookDelivery) {
+        let Some(store) = self.store.clone() else {
+            return;
+        };
+        let delivery = delivery.clone();
+        match tokio::task::spawn_blocking(move || store.put_delivery(&delivery)).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => error!("Failed to persist webhook delivery: {}", e),
+            Err(e) => error!("Webhook store task failed: {}", e),
+        }
+    }
+
+    async fn unpersist(&self, id: &str) {
+        let Some(store) = self.store.clone() else {
+            return;
+        };
+        let id = id.to_string();
+        match tokio::task::spawn_blocking(move || store.delete_delivery(&id)).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => error!("Failed to remove webhook delivery: {}", e),
+            Err(e) => error!("Webhook store task failed: {}", e),
+        }
+    }
+}